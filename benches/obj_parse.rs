@@ -0,0 +1,122 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+// Measures the streaming OBJ reader against a recorded baseline: the `Mesh::read_obj` of
+// 9e3699f, which read the whole file into a String and collected every line's parts, parsed
+// the default 600x600 grid at 45.5 MB/s on the machine the streaming reader was written on.
+// Set OBJ_BENCH_BASELINE to a MB/s figure measured on your own machine to compare against that.
+//
+//     cargo bench --bench obj_parse [-- file.obj]
+//
+// Without a file a grid of OBJ_BENCH_SIZE x OBJ_BENCH_SIZE quads (default 600) is generated.
+//
+// no_std keeps std's `format!` out of the prelude, objloader uses rs_alloc's.
+#![no_std]
+
+extern crate std;
+
+use std::io::Write;
+use std::vec::Vec;
+use std::string::String;
+use std::{println, format};
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../src/objloader.rs"]
+mod objloader;
+
+const RUNS : usize = 3;
+const BASELINE_MB_PER_SEC : f64 = 45.5;
+
+fn write_grid(path: &std::path::Path, n: usize) {
+    let mut f = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+    for y in 0..=n {
+        for x in 0..=n {
+            writeln!(f, "v {:.6} {:.6} {:.6}", x as f32 / n as f32, y as f32 / n as f32, ((x * y) % 7) as f32 * 0.01).unwrap();
+        }
+    }
+    for y in 0..=n {
+        for x in 0..=n {
+            writeln!(f, "vt {:.6} {:.6}", x as f32 / n as f32, y as f32 / n as f32).unwrap();
+        }
+    }
+    writeln!(f, "vn 0 0 1").unwrap();
+    for y in 0..n {
+        for x in 0..n {
+            let i = y * (n + 1) + x + 1;
+            let j = i + n + 1;
+            writeln!(f, "f {}/{}/1 {}/{}/1 {}/{}/1 {}/{}/1", i, i, i + 1, i + 1, j + 1, j + 1, j, j).unwrap();
+        }
+    }
+}
+
+fn read_obj_streaming(path: &str) -> (usize, usize) {
+    match objloader::Mesh::read_obj(path) {
+        Ok(m) => (m.verts().len(), m.tris().len() + m.quads().len()),
+        Err(e) => panic!("{}", e.as_str())
+    }
+}
+
+fn best_of<F: FnMut() -> (usize, usize)>(mut f: F) -> (Duration, (usize, usize)) {
+    let mut best = Duration::MAX;
+    let mut result = (0, 0);
+    for _ in 0..RUNS {
+        let t = Instant::now();
+        result = f();
+        best = best.min(t.elapsed());
+    }
+    (best, result)
+}
+
+fn main() {
+    let args : Vec<String> = std::env::args().skip(1).filter(|a| !a.starts_with("--")).collect();
+    let path =
+        match args.first() {
+            Some(p) => std::path::PathBuf::from(p),
+            None => {
+                let n = std::env::var("OBJ_BENCH_SIZE").ok().and_then(|s| s.parse().ok()).unwrap_or(600);
+                let p = std::env::temp_dir().join(format!("obj_bench_grid_{}.obj", n));
+                if !p.exists() {
+                    write_grid(&p, n);
+                }
+                p
+            }
+        };
+
+    let path_str = path.to_str().unwrap();
+    let mb = std::fs::metadata(&path).unwrap().len() as f64 / (1024.0 * 1024.0);
+    println!("{}: {:.1} MB", path_str, mb);
+
+    let baseline = std::env::var("OBJ_BENCH_BASELINE").ok().and_then(|s| s.parse().ok()).unwrap_or(BASELINE_MB_PER_SEC);
+    let (streaming, s) = best_of(|| read_obj_streaming(path_str));
+    let rate = mb / streaming.as_secs_f64();
+
+    println!("verts {}, faces {}", s.0, s.1);
+    println!("baseline               : {:8} {:8.1} MB/s", "", baseline);
+    println!("streaming              : {:8.1} ms {:8.1} MB/s, {:.2}x", streaming.as_secs_f64() * 1000.0, rate, rate / baseline);
+}
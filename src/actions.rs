@@ -0,0 +1,554 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_ctypes::*;
+use rs_glfw3::bindings::*;
+use rs_alloc::*;
+
+use crate::input::*;
+use crate::objloader::read_bytes;
+
+/// What turns a digital action on
+#[derive(Clone, Copy)]
+pub enum Button {
+    Key(i32),
+    Mouse(i32),
+    Joystick { joystick: i32, button: i32 },
+    /// a stick or trigger past `threshold`, a negative threshold looks at the negative side
+    JoystickAxis { joystick: i32, axis: i32, threshold: f32 },
+}
+
+/// Where an analog axis reads from
+#[derive(Clone, Copy)]
+pub enum AxisSource {
+    /// -1 while `negative` is held, +1 while `positive` is held
+    Keys { negative: i32, positive: i32 },
+    /// cursor movement of the frame, in screen coordinates
+    MouseX,
+    MouseY,
+    ScrollX,
+    ScrollY,
+    /// raw joystick axis, GLFW 3.2 has no gamepad mapping so indices follow the driver
+    Joystick { joystick: i32, axis: i32 },
+}
+
+#[derive(Clone, Copy)]
+pub struct AxisBinding {
+    pub source      : AxisSource,
+    pub scale       : f32,
+    /// values under the dead zone read 0, the rest is rescaled to start from 0
+    pub dead_zone   : f32,
+}
+
+impl AxisBinding {
+    pub fn new(source: AxisSource) -> Self { Self { source: source, scale: 1.0, dead_zone: 0.0 } }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_dead_zone(mut self, dead_zone: f32) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+}
+
+struct Action {
+    name    : String,
+    buttons : Vec<Button>,
+    active  : bool,
+    was_active : bool,
+}
+
+struct Axis {
+    name    : String,
+    bindings: Vec<AxisBinding>,
+    value   : f32,
+}
+
+// joystick state copied once per update
+struct Pad {
+    axes    : Vec<f32>,
+    buttons : Vec<u8>,
+}
+
+const PAD_COUNT : usize = GLFW_JOYSTICK_LAST as usize + 1;
+
+/// Named digital actions and analog axes, bound to keys, mouse and joysticks. The runner updates
+/// the map once per frame after the input events, applications only query it by name.
+pub struct ActionMap {
+    actions : Vec<Action>,
+    axes    : Vec<Axis>,
+    pads    : Vec<Pad>,
+}
+
+fn apply_dead_zone(v: f32, dead_zone: f32) -> f32 {
+    let a = v.abs();
+    if a <= dead_zone || dead_zone >= 1.0 {
+        0.0
+    } else {
+        let r = (a - dead_zone) / (1.0 - dead_zone);
+        if v < 0.0 { -r } else { r }
+    }
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        let mut pads = Vec::with_capacity(PAD_COUNT);
+        for _ in 0..PAD_COUNT {
+            pads.push(Pad { axes: Vec::new(), buttons: Vec::new() });
+        }
+        Self { actions: Vec::new(), axes: Vec::new(), pads: pads }
+    }
+
+    /// Reads bindings in the `parse` format from a file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = read_bytes(path)?;
+        match core::str::from_utf8(bytes.as_slice()) {
+            Ok(text) => Self::parse(text),
+            Err(_) => Err(format!("{}: not valid utf8", path)),
+        }
+    }
+
+    /// One binding per line, `#` starts a comment:
+    ///
+    ///     action quit     key ESCAPE
+    ///     action orbit    mouse LEFT
+    ///     action orbit    joystick 0 button 4
+    ///     action forward  joystick 0 axis 1 -0.5
+    ///     axis   zoom     scroll y scale 0.1
+    ///     axis   zoom     keys MINUS EQUAL
+    ///     axis   orbit_x  mouse x scale 0.01
+    ///     axis   orbit_x  joystick 0 axis 0 deadzone 0.15 scale 2
+    ///
+    /// Keys and mouse buttons are GLFW names without the prefix, or their numeric codes.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = match line.find('#') { Some(p) => &line[..p], None => line };
+            let tokens : Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() == 0 {
+                continue
+            }
+
+            match map.parse_binding(tokens.as_slice()) {
+                Ok(()) => (),
+                Err(err) => return Err(format!("line {}: {}", i + 1, err))
+            }
+        }
+        Ok(map)
+    }
+
+    fn parse_binding(&mut self, t: &[&str]) -> Result<(), String> {
+        if t.len() < 3 {
+            return Err(String::from("expected a kind, a name and a binding"))
+        }
+
+        let (kind, name, args) = (t[0], t[1], &t[2..]);
+        match kind {
+            "action" => {
+                let b =
+                    match args {
+                        ["key", k] => Button::Key(parse_key(k)?),
+                        ["mouse", b] => Button::Mouse(parse_mouse_button(b)?),
+                        ["joystick", j, "button", b] => Button::Joystick { joystick: parse_joystick(j)?, button: parse_int(b)? },
+                        ["joystick", j, "axis", a, th] => Button::JoystickAxis { joystick: parse_joystick(j)?, axis: parse_int(a)?, threshold: parse_float(th)? },
+                        _ => return Err(format!("invalid action binding for {}", name))
+                    };
+                self.bind_action(name, b);
+            },
+
+            "axis" => {
+                // options come last, in any order
+                let mut end = args.len();
+                let mut scale = 1.0;
+                let mut dead_zone = 0.0;
+                while end >= 2 {
+                    match args[end - 2] {
+                        "scale" => scale = parse_float(args[end - 1])?,
+                        "deadzone" => dead_zone = parse_float(args[end - 1])?,
+                        _ => break
+                    }
+                    end -= 2;
+                }
+
+                let source =
+                    match &args[..end] {
+                        ["keys", n, p] => AxisSource::Keys { negative: parse_key(n)?, positive: parse_key(p)? },
+                        ["mouse", "x"] => AxisSource::MouseX,
+                        ["mouse", "y"] => AxisSource::MouseY,
+                        ["scroll", "x"] => AxisSource::ScrollX,
+                        ["scroll", "y"] => AxisSource::ScrollY,
+                        ["joystick", j, "axis", a] => AxisSource::Joystick { joystick: parse_joystick(j)?, axis: parse_int(a)? },
+                        _ => return Err(format!("invalid axis binding for {}", name))
+                    };
+                self.bind_axis(name, AxisBinding::new(source).with_scale(scale).with_dead_zone(dead_zone));
+            },
+
+            _ => return Err(format!("unknown binding kind {}", kind))
+        }
+        Ok(())
+    }
+
+    pub fn bind_action(&mut self, name: &str, button: Button) {
+        match self.actions.iter().position(|a| a.name.as_str() == name) {
+            Some(i) => self.actions[i].buttons.push(button),
+            None => {
+                let mut buttons = Vec::new();
+                buttons.push(button);
+                self.actions.push(Action { name: String::from(name), buttons: buttons, active: false, was_active: false });
+            }
+        }
+    }
+
+    pub fn bind_axis(&mut self, name: &str, binding: AxisBinding) {
+        match self.axes.iter().position(|a| a.name.as_str() == name) {
+            Some(i) => self.axes[i].bindings.push(binding),
+            None => {
+                let mut bindings = Vec::new();
+                bindings.push(binding);
+                self.axes.push(Axis { name: String::from(name), bindings: bindings, value: 0.0 });
+            }
+        }
+    }
+
+    /// Adds the bindings of `other`, actions and axes present in both get both sets
+    pub fn merge(&mut self, other: &ActionMap) {
+        for a in other.actions.iter() {
+            for b in a.buttons.iter() {
+                self.bind_action(a.name.as_str(), *b);
+            }
+        }
+        for a in other.axes.iter() {
+            for b in a.bindings.iter() {
+                self.bind_axis(a.name.as_str(), *b);
+            }
+        }
+    }
+
+    /// held this frame
+    pub fn is_active(&self, name: &str) -> bool { self.action(name).map(|a| a.active).unwrap_or(false) }
+    /// became active this frame
+    pub fn was_triggered(&self, name: &str) -> bool { self.action(name).map(|a| a.active && !a.was_active).unwrap_or(false) }
+    /// stopped being active this frame
+    // the viewer's actions all fire on the press
+    #[allow(dead_code)]
+    pub fn was_released(&self, name: &str) -> bool { self.action(name).map(|a| !a.active && a.was_active).unwrap_or(false) }
+    /// sum of the bound sources, 0 for unknown axes
+    pub fn axis(&self, name: &str) -> f32 { self.axes.iter().find(|a| a.name.as_str() == name).map(|a| a.value).unwrap_or(0.0) }
+
+    fn action(&self, name: &str) -> Option<&Action> { self.actions.iter().find(|a| a.name.as_str() == name) }
+
+    /// Samples every binding, once per frame after the input events were received
+    pub fn update(&mut self, input: &Input) {
+        let mut polled = [false; PAD_COUNT];
+        for a in self.actions.iter() {
+            for b in a.buttons.iter() {
+                match b {
+                    Button::Joystick { joystick, .. } | Button::JoystickAxis { joystick, .. } => Self::poll(&mut self.pads, &mut polled, *joystick),
+                    _ => (),
+                }
+            }
+        }
+        for a in self.axes.iter() {
+            for b in a.bindings.iter() {
+                match b.source {
+                    AxisSource::Joystick { joystick, .. } => Self::poll(&mut self.pads, &mut polled, joystick),
+                    _ => (),
+                }
+            }
+        }
+
+        let pads = &self.pads;
+        let pad_axis = |j: i32, a: i32| -> f32 {
+            if j < 0 || j as usize >= PAD_COUNT || a < 0 { return 0.0 }
+            pads[j as usize].axes.as_slice().get(a as usize).map(|v| *v).unwrap_or(0.0)
+        };
+
+        for a in self.actions.iter_mut() {
+            a.was_active = a.active;
+            a.active = a.buttons.iter().any(|b| {
+                match *b {
+                    // a tap between two frames still counts for the frame it happened in
+                    Button::Key(k) => input.is_down(k) || input.was_pressed(k),
+                    Button::Mouse(m) => input.is_button_down(m) || input.was_button_pressed(m),
+                    Button::Joystick { joystick, button } => {
+                        joystick >= 0 && (joystick as usize) < PAD_COUNT && button >= 0 &&
+                        pads[joystick as usize].buttons.as_slice().get(button as usize).map(|s| *s as c_int == GLFW_PRESS as c_int).unwrap_or(false)
+                    },
+                    Button::JoystickAxis { joystick, axis, threshold } => {
+                        let v = pad_axis(joystick, axis);
+                        if threshold < 0.0 { v < threshold } else { v > threshold }
+                    },
+                }
+            });
+        }
+
+        for a in self.axes.iter_mut() {
+            a.value = a.bindings.iter().map(|b| {
+                let v =
+                    match b.source {
+                        AxisSource::Keys { negative, positive } => {
+                            (if input.is_down(positive) { 1.0 } else { 0.0 }) - (if input.is_down(negative) { 1.0 } else { 0.0 })
+                        },
+                        AxisSource::MouseX => input.cursor_delta().x,
+                        AxisSource::MouseY => input.cursor_delta().y,
+                        AxisSource::ScrollX => input.scroll().x,
+                        AxisSource::ScrollY => input.scroll().y,
+                        AxisSource::Joystick { joystick, axis } => pad_axis(joystick, axis),
+                    };
+                apply_dead_zone(v, b.dead_zone) * b.scale
+            }).sum();
+        }
+    }
+
+    fn poll(pads: &mut Vec<Pad>, polled: &mut [bool; PAD_COUNT], joystick: i32) {
+        if joystick < 0 || joystick as usize >= PAD_COUNT || polled[joystick as usize] {
+            return
+        }
+        polled[joystick as usize] = true;
+
+        let pad = &mut pads[joystick as usize];
+        pad.axes = Vec::new();
+        pad.buttons = Vec::new();
+        unsafe {
+            if glfwJoystickPresent(joystick as c_int) == GLFW_FALSE as c_int {
+                return
+            }
+
+            let mut count = 0;
+            let axes = glfwGetJoystickAxes(joystick as c_int, &mut count);
+            if !axes.is_null() {
+                pad.axes.append(core::slice::from_raw_parts(axes, count as usize));
+            }
+
+            let mut count = 0;
+            let buttons = glfwGetJoystickButtons(joystick as c_int, &mut count);
+            if !buttons.is_null() {
+                pad.buttons.append(core::slice::from_raw_parts(buttons, count as usize));
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Names
+////////////////////////////////////////////////////////////////////////////////
+
+static KEY_NAMES : [(&'static str, u32); 52] = [
+    ("SPACE", GLFW_KEY_SPACE), ("APOSTROPHE", GLFW_KEY_APOSTROPHE), ("COMMA", GLFW_KEY_COMMA),
+    ("MINUS", GLFW_KEY_MINUS), ("PERIOD", GLFW_KEY_PERIOD), ("SLASH", GLFW_KEY_SLASH),
+    ("SEMICOLON", GLFW_KEY_SEMICOLON), ("EQUAL", GLFW_KEY_EQUAL), ("LEFT_BRACKET", GLFW_KEY_LEFT_BRACKET),
+    ("BACKSLASH", GLFW_KEY_BACKSLASH), ("RIGHT_BRACKET", GLFW_KEY_RIGHT_BRACKET), ("GRAVE_ACCENT", GLFW_KEY_GRAVE_ACCENT),
+    ("ESCAPE", GLFW_KEY_ESCAPE), ("ENTER", GLFW_KEY_ENTER), ("TAB", GLFW_KEY_TAB),
+    ("BACKSPACE", GLFW_KEY_BACKSPACE), ("INSERT", GLFW_KEY_INSERT), ("DELETE", GLFW_KEY_DELETE),
+    ("RIGHT", GLFW_KEY_RIGHT), ("LEFT", GLFW_KEY_LEFT), ("DOWN", GLFW_KEY_DOWN), ("UP", GLFW_KEY_UP),
+    ("PAGE_UP", GLFW_KEY_PAGE_UP), ("PAGE_DOWN", GLFW_KEY_PAGE_DOWN), ("HOME", GLFW_KEY_HOME), ("END", GLFW_KEY_END),
+    ("CAPS_LOCK", GLFW_KEY_CAPS_LOCK), ("PRINT_SCREEN", GLFW_KEY_PRINT_SCREEN), ("PAUSE", GLFW_KEY_PAUSE),
+    ("KP_DECIMAL", GLFW_KEY_KP_DECIMAL), ("KP_DIVIDE", GLFW_KEY_KP_DIVIDE), ("KP_MULTIPLY", GLFW_KEY_KP_MULTIPLY),
+    ("KP_SUBTRACT", GLFW_KEY_KP_SUBTRACT), ("KP_ADD", GLFW_KEY_KP_ADD), ("KP_ENTER", GLFW_KEY_KP_ENTER),
+    ("KP_EQUAL", GLFW_KEY_KP_EQUAL),
+    ("LEFT_SHIFT", GLFW_KEY_LEFT_SHIFT), ("LEFT_CONTROL", GLFW_KEY_LEFT_CONTROL), ("LEFT_ALT", GLFW_KEY_LEFT_ALT),
+    ("LEFT_SUPER", GLFW_KEY_LEFT_SUPER), ("RIGHT_SHIFT", GLFW_KEY_RIGHT_SHIFT), ("RIGHT_CONTROL", GLFW_KEY_RIGHT_CONTROL),
+    ("RIGHT_ALT", GLFW_KEY_RIGHT_ALT), ("RIGHT_SUPER", GLFW_KEY_RIGHT_SUPER), ("MENU", GLFW_KEY_MENU),
+    ("SHIFT", GLFW_KEY_LEFT_SHIFT), ("CONTROL", GLFW_KEY_LEFT_CONTROL), ("CTRL", GLFW_KEY_LEFT_CONTROL),
+    ("ALT", GLFW_KEY_LEFT_ALT), ("SUPER", GLFW_KEY_LEFT_SUPER), ("ESC", GLFW_KEY_ESCAPE), ("RETURN", GLFW_KEY_ENTER),
+];
+
+fn parse_int(s: &str) -> Result<i32, String> {
+    match s.parse::<i32>() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!("invalid integer {}", s))
+    }
+}
+
+fn parse_float(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!("invalid number {}", s))
+    }
+}
+
+fn parse_joystick(s: &str) -> Result<i32, String> {
+    let j = parse_int(s)?;
+    if j < 0 || j as usize >= PAD_COUNT {
+        return Err(format!("joystick {} out of range", j))
+    }
+    Ok(j)
+}
+
+/// GLFW key code of a name like `A`, `5`, `F1`, `ESCAPE`, `LEFT_SHIFT`, or of a numeric code
+pub fn parse_key(name: &str) -> Result<i32, String> {
+    let b = name.as_bytes();
+    if b.len() == 1 && (b[0].is_ascii_alphabetic() || b[0].is_ascii_digit()) {
+        return Ok(b[0].to_ascii_uppercase() as i32)     // GLFW uses ascii for letters and digits
+    }
+
+    if b.len() >= 2 && (b[0] == b'F' || b[0] == b'f') {
+        match name[1..].parse::<u32>() {
+            Ok(n) if n >= 1 && n <= 25 => return Ok((GLFW_KEY_F1 + n - 1) as i32),
+            _ => (),
+        }
+    }
+
+    match (name.get(..3), name.get(3..)) {
+        (Some(prefix), Some(digit)) if prefix.eq_ignore_ascii_case("KP_") => {
+            match digit.parse::<u32>() {
+                Ok(n) if n <= 9 => return Ok((GLFW_KEY_KP_0 + n) as i32),
+                _ => (),
+            }
+        },
+        _ => (),
+    }
+
+    match KEY_NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        Some((_, k)) => Ok(*k as i32),
+        None => name.parse::<i32>().map_err(|_| format!("unknown key {}", name))
+    }
+}
+
+/// GLFW mouse button of `LEFT`, `RIGHT`, `MIDDLE` or of a button number
+pub fn parse_mouse_button(name: &str) -> Result<i32, String> {
+    if name.eq_ignore_ascii_case("LEFT") {
+        Ok(GLFW_MOUSE_BUTTON_LEFT as i32)
+    } else if name.eq_ignore_ascii_case("RIGHT") {
+        Ok(GLFW_MOUSE_BUTTON_RIGHT as i32)
+    } else if name.eq_ignore_ascii_case("MIDDLE") {
+        Ok(GLFW_MOUSE_BUTTON_MIDDLE as i32)
+    } else {
+        match name.parse::<i32>() {
+            Ok(b) if b >= 0 && b <= GLFW_MOUSE_BUTTON_LAST as i32 => Ok(b),
+            _ => Err(format!("unknown mouse button {}", name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names() {
+        assert_eq!(parse_key("a").ok(), Some(GLFW_KEY_A as i32));
+        assert_eq!(parse_key("7").ok(), Some(GLFW_KEY_7 as i32));
+        assert_eq!(parse_key("f12").ok(), Some(GLFW_KEY_F12 as i32));
+        assert_eq!(parse_key("KP_3").ok(), Some(GLFW_KEY_KP_3 as i32));
+        assert_eq!(parse_key("kp_add").ok(), Some(GLFW_KEY_KP_ADD as i32));
+        assert_eq!(parse_key("Escape").ok(), Some(GLFW_KEY_ESCAPE as i32));
+        assert_eq!(parse_key("290").ok(), Some(290));
+        // multibyte names do not split inside a character
+        assert!(parse_key("é").is_err());
+        assert!(parse_key("KéY").is_err());
+        assert!(parse_key("F").is_ok() && parse_key("F0").is_err());
+    }
+
+    #[test]
+    fn parse_bindings() {
+        let map = ActionMap::parse("\
+            # camera
+            action quit     key ESCAPE      # comment after a binding
+            action orbit    mouse LEFT
+            action orbit    joystick 1 button 4
+            action forward  joystick 0 axis 1 -0.5
+
+            axis   zoom     scroll y scale 0.1
+            axis   zoom     keys MINUS EQUAL
+            axis   orbit_x  joystick 0 axis 0 deadzone 0.15 scale 2
+            axis   orbit_y  mouse y scale 0.01 deadzone 0.5
+        ").unwrap_or_else(|e| panic!("{}", e.as_str()));
+
+        assert_eq!(map.actions.len(), 3);
+        assert_eq!(map.actions[1].name.as_str(), "orbit");
+        match map.actions[1].buttons.as_slice() {
+            [Button::Mouse(m), Button::Joystick { joystick: 1, button: 4 }] => assert_eq!(*m, GLFW_MOUSE_BUTTON_LEFT as i32),
+            _ => panic!("orbit bindings")
+        }
+        match map.actions[2].buttons[0] {
+            Button::JoystickAxis { joystick: 0, axis: 1, threshold } => assert_eq!(threshold, -0.5),
+            _ => panic!("forward binding")
+        }
+
+        assert_eq!(map.axes.len(), 3);
+        assert_eq!(map.axes[0].bindings.len(), 2);
+        let zoom = map.axes[0].bindings[1];
+        match zoom.source {
+            AxisSource::Keys { negative, positive } => assert_eq!((negative, positive), (GLFW_KEY_MINUS as i32, GLFW_KEY_EQUAL as i32)),
+            _ => panic!("zoom keys")
+        }
+        // options in either order
+        for (a, scale, dead_zone) in [(1, 2.0, 0.15), (2, 0.01, 0.5)].iter() {
+            let b = map.axes[*a].bindings[0];
+            assert_eq!((b.scale, b.dead_zone), (*scale, *dead_zone));
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |text: &str| match ActionMap::parse(text) { Ok(_) => String::new(), Err(e) => e };
+        assert_eq!(err("action quit key ESCAPE\naction quit key NOPE").as_str(), "line 2: unknown key NOPE");
+        assert_eq!(err("\n\naction orbit").as_str(), "line 3: expected a kind, a name and a binding");
+        assert_eq!(err("action orbit mouse").as_str(), "line 1: invalid action binding for orbit");
+        assert_eq!(err("action orbit mouse FOURTH").as_str(), "line 1: unknown mouse button FOURTH");
+        assert_eq!(err("action jump joystick 99 button 1").as_str(), "line 1: joystick 99 out of range");
+        assert_eq!(err("axis zoom scroll z").as_str(), "line 1: invalid axis binding for zoom");
+        assert_eq!(err("axis zoom scroll y scale big").as_str(), "line 1: invalid number big");
+        assert_eq!(err("button jump key SPACE").as_str(), "line 1: unknown binding kind button");
+    }
+
+    #[test]
+    fn action_edges() {
+        let mut map = ActionMap::new();
+        map.bind_action("jump", Button::Key(GLFW_KEY_SPACE as i32));
+        map.bind_axis("walk", AxisBinding::new(AxisSource::Keys { negative: GLFW_KEY_A as i32, positive: GLFW_KEY_D as i32 }).with_scale(2.0));
+
+        let key = |k: u32, a: u32| { let mut v = Vec::new(); v.push(Event::Key { key: k as i32, scancode: 0, action: a as i32, mods: 0 }); v };
+        let mut input = Input::new();
+        input.begin_frame(key(GLFW_KEY_SPACE, GLFW_PRESS));
+        map.update(&input);
+        assert!(map.was_triggered("jump") && map.is_active("jump") && !map.was_released("jump"));
+
+        input.begin_frame(key(GLFW_KEY_D, GLFW_PRESS));
+        map.update(&input);
+        assert!(!map.was_triggered("jump") && map.is_active("jump"));
+        assert_eq!(map.axis("walk"), 2.0);
+
+        input.begin_frame(key(GLFW_KEY_SPACE, GLFW_RELEASE));
+        map.update(&input);
+        assert!(map.was_released("jump") && !map.is_active("jump"));
+        assert_eq!(map.axis("missing"), 0.0);
+    }
+
+    #[test]
+    fn dead_zone() {
+        assert_eq!(apply_dead_zone(0.1, 0.2), 0.0);
+        assert_eq!(apply_dead_zone(-0.75, 0.5), -0.5);
+        assert_eq!(apply_dead_zone(1.0, 0.2), 1.0);
+        assert_eq!(apply_dead_zone(0.5, 1.0), 0.0);
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_ctypes::*;
+use rs_glfw3::bindings::*;
+use rs_streams::{println, fprintln};
+use rs_alloc::*;
+
+use crate::input::*;
+use crate::actions::*;
+use crate::timing::*;
+use crate::window::*;
+
+#[cfg(target_arch = "wasm32")]
+type EmArgCallbackFunc = extern "C" fn(*mut c_void);
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn emscripten_set_main_loop_arg(func: EmArgCallbackFunc, arg: *mut c_void, fps: c_int, simulate_infinite_loop: c_int);
+    fn emscripten_cancel_main_loop();
+}
+
+pub struct AppConfig {
+    window      : WindowConfig,
+    fixed_step  : f32,
+    max_fps     : f32,
+}
+
+impl AppConfig {
+    pub fn new(window: WindowConfig) -> Self { Self { window: window, fixed_step: 1.0 / 60.0, max_fps: 0.0 } }
+
+    /// seconds between two `App::fixed_update` calls
+    // the viewer has no fixed update
+    #[allow(dead_code)]
+    pub fn with_fixed_step(mut self, step: f32) -> Self {
+        self.fixed_step = step;
+        self
+    }
+
+    /// caps the frame rate, 0 leaves it to vsync or the browser
+    // the viewer leaves its frame rate to vsync
+    #[allow(dead_code)]
+    pub fn with_max_fps(mut self, fps: f32) -> Self {
+        self.max_fps = fps;
+        self
+    }
+
+    pub fn window(&self) -> &WindowConfig { &self.window }
+    pub fn fixed_step(&self) -> f32 { self.fixed_step }
+    pub fn max_fps(&self) -> f32 { self.max_fps }
+}
+
+/// What the runner shares with the application: the window, input, timing and the quit request
+pub struct Context {
+    window  : Window,
+    timer   : FrameTimer,
+    quit    : bool,
+    input   : Input,
+    actions : ActionMap,
+}
+
+impl Context {
+    pub fn window(&self) -> &Window { &self.window }
+    pub fn window_mut(&mut self) -> &mut Window { &mut self.window }
+    /// framebuffer size in pixels, cursor positions are in `window().size()` coordinates
+    pub fn size(&self) -> (i32, i32) { self.window.framebuffer_size() }
+    /// seconds since the runner started, sampled at the beginning of the frame
+    pub fn time(&self) -> f64 { self.timer.time() }
+    /// frame times, fixed step and interpolation alpha
+    pub fn timer(&self) -> &FrameTimer { &self.timer }
+    /// leaves the loop after the current frame
+    pub fn quit(&mut self) { self.quit = true }
+    pub fn input(&self) -> &Input { &self.input }
+    /// named bindings, sampled every frame after the events and before `update`
+    pub fn actions(&self) -> &ActionMap { &self.actions }
+    pub fn actions_mut(&mut self) -> &mut ActionMap { &mut self.actions }
+}
+
+/// Application callbacks, all of them are called with the GL context current
+pub trait App {
+    fn init(&mut self, ctx: &mut Context) -> Result<(), String>;
+    /// called zero or more times per frame, always with the same step, before `update`
+    fn fixed_update(&mut self, _ctx: &mut Context, _step: f32) {}
+    fn update(&mut self, _ctx: &mut Context, _dt: f32) {}
+    fn render(&mut self, ctx: &mut Context);
+    /// called once after init and then whenever the framebuffer size or the content scale change,
+    /// with the framebuffer size in pixels
+    fn resize(&mut self, _ctx: &mut Context, _width: i32, _height: i32) {}
+    fn event(&mut self, _ctx: &mut Context, _event: &Event) {}
+    /// not called on emscripten unless the application quits, the page just goes away
+    fn shutdown(&mut self, _ctx: &mut Context) {}
+}
+
+struct Runner<A: App> {
+    app     : A,
+    ctx     : Context,
+    // written by the input callbacks while polling, never borrowed across `poll_events`
+    queue   : *mut EventQueue,
+}
+
+impl<A: App> Runner<A> {
+    fn frame(&mut self) {
+        let dt = self.ctx.timer.begin_frame();
+        let polled = unsafe { poll_events(self.queue) };
+        self.dispatch_events(polled);
+
+        if self.ctx.window.refresh() {
+            let (width, height) = self.ctx.window.framebuffer_size();
+            self.app.resize(&mut self.ctx, width, height);
+        }
+
+        self.update(dt);
+        self.ctx.window.swap_buffers();
+        self.ctx.timer.end_frame();
+    }
+
+    // events go to the app first, then stay readable from the input for the rest of the frame
+    fn dispatch_events(&mut self, polled: Vec<Event>) {
+        self.ctx.input.begin_frame(polled);
+        let events = self.ctx.input.take_events();
+        for e in events.iter() {
+            self.app.event(&mut self.ctx, e);
+        }
+        self.ctx.input.restore_events(events);
+        self.ctx.actions.update(&self.ctx.input);
+    }
+
+    fn update(&mut self, dt: f32) {
+        let step = self.ctx.timer.fixed_step();
+        while self.ctx.timer.next_fixed_step() {
+            self.app.fixed_update(&mut self.ctx, step);
+        }
+
+        self.app.update(&mut self.ctx, dt);
+        self.app.render(&mut self.ctx);
+    }
+
+    fn should_close(&self) -> bool {
+        self.ctx.quit || self.ctx.window.should_close()
+    }
+}
+
+extern "C" fn main_loop<A: App>(arg: *mut c_void) {
+    let runner = unsafe { &mut *(arg as *mut Runner<A>) };
+    runner.frame();
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        if runner.should_close() {
+            runner.app.shutdown(&mut runner.ctx);
+            unsafe { emscripten_cancel_main_loop() };
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn run_main_loop<A: App>(runner: &mut Runner<A>) {
+    // 0 frames per second means requestAnimationFrame
+    let fps = runner.ctx.timer.max_fps() as c_int;
+    unsafe { emscripten_set_main_loop_arg(main_loop::<A>, runner as *mut Runner<A> as *mut c_void, fps, 1) };
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_main_loop<A: App>(runner: &mut Runner<A>) {
+    while !runner.should_close() {
+        main_loop::<A>(runner as *mut Runner<A> as *mut c_void);
+    }
+    runner.app.shutdown(&mut runner.ctx);
+}
+
+/// Creates the window and its GLES2 context, then drives the application until it quits
+/// or the window is closed
+pub fn run<A: App>(config: &AppConfig, app: A) -> Result<(), String> {
+    init_glfw()?;
+
+    unsafe {
+        let window =
+            match Window::create(config.window()) {
+                Ok(w) => w,
+                Err(err) => {
+                    glfwTerminate();
+                    return Err(err)
+                }
+            };
+        println!("context   : {}", window.context());
+        let win = window.handle();
+
+        // the runner stays at the same address for the whole loop, the window points at the event
+        // queue and not at the runner, which is borrowed for the whole frame
+        let queue = install_callbacks(win);
+        let timer = FrameTimer::new().with_fixed_step(config.fixed_step()).with_max_fps(config.max_fps());
+        let ctx = Context { window: window, timer: timer, quit: false, input: Input::new(), actions: ActionMap::new() };
+        let runner = Box::into_raw(Box::new(Runner { app: app, ctx: ctx, queue: queue }));
+
+        let res = (*runner).app.init(&mut (*runner).ctx);
+        if res.is_ok() {
+            (*runner).ctx.window.refresh();
+            let (width, height) = (*runner).ctx.window.framebuffer_size();
+            (*runner).app.resize(&mut (*runner).ctx, width, height);
+            (*runner).ctx.timer.reset();
+
+            run_main_loop(&mut *runner);
+        }
+
+        // drops the window with the context, the callbacks can no longer reach the queue
+        drop(Box::from_raw(runner));
+        free_queue(queue);
+        glfwTerminate();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes down the calls it gets, quits after its second update
+    struct Recorder {
+        calls   : String,
+        updates : usize,
+    }
+
+    impl App for Recorder {
+        fn init(&mut self, _ctx: &mut Context) -> Result<(), String> { Ok(()) }
+
+        fn fixed_update(&mut self, _ctx: &mut Context, step: f32) {
+            assert_eq!(step, 0.01);
+            self.calls.push_str("fixed ");
+        }
+
+        fn update(&mut self, ctx: &mut Context, _dt: f32) {
+            // the actions were sampled from the events of the frame
+            if ctx.actions().was_triggered("jump") {
+                self.calls.push_str("jump ");
+            }
+            self.calls.push_str("update ");
+            self.updates += 1;
+            if self.updates == 2 {
+                ctx.quit();
+            }
+        }
+
+        fn render(&mut self, _ctx: &mut Context) { self.calls.push_str("render ") }
+
+        fn event(&mut self, ctx: &mut Context, e: &Event) {
+            // the input already reflects the event, its queue is handed to the app meanwhile
+            if let Event::Key { key, .. } = e {
+                assert!(ctx.input().is_down(*key));
+                assert_eq!(ctx.input().events().len(), 0);
+            }
+            self.calls.push_str("event ");
+        }
+    }
+
+    fn runner() -> Runner<Recorder> {
+        let mut actions = ActionMap::new();
+        actions.bind_action("jump", Button::Key(GLFW_KEY_SPACE as i32));
+        let ctx = Context { window: Window::detached(), timer: FrameTimer::new().with_fixed_step(0.01), quit: false, input: Input::new(), actions: actions };
+        Runner { app: Recorder { calls: String::new(), updates: 0 }, ctx: ctx, queue: core::ptr::null_mut() }
+    }
+
+    fn press(key: u32) -> Event { Event::Key { key: key as i32, scancode: 0, action: GLFW_PRESS as i32, mods: 0 } }
+
+    #[test]
+    fn frame_order() {
+        let mut r = runner();
+        r.ctx.timer.reset();
+        let t = now();
+
+        // events, then the fixed steps the frame time covers, then update and render
+        let dt = r.ctx.timer.begin_frame_at(t + 0.035);
+        let mut polled = Vec::new();
+        polled.push(press(GLFW_KEY_SPACE));
+        r.dispatch_events(polled);
+        r.update(dt);
+        assert_eq!(r.app.calls.as_str(), "event fixed fixed fixed jump update render ");
+        assert_eq!(r.ctx.input().events().len(), 1);
+        assert!(!r.ctx.quit);
+
+        // the remaining 5 ms and this frame's 10 ms make one more step, the key is no longer new
+        r.app.calls = String::new();
+        let dt = r.ctx.timer.begin_frame_at(t + 0.045);
+        r.dispatch_events(Vec::new());
+        r.update(dt);
+        assert_eq!(r.app.calls.as_str(), "fixed update render ");
+        assert!(r.ctx.quit);
+    }
+}
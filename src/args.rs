@@ -0,0 +1,272 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+
+use crate::simplify::LodTarget;
+
+pub const USAGE : &str = "\
+usage: viewer [options] [model]
+
+  model                 obj, ply, stl, gltf or glb file, suzane.obj by default and
+                        a sphere when that is missing
+  -s, --size WxH        window size, 1024x900 by default
+  --msaa N              MSAA samples, 0 disables multisampling
+  --fullscreen          borderless full screen on the primary monitor
+  --lod RATIO           simplifies the model to this fraction of its triangles
+  --lod-triangles N     simplifies the model down to N triangles
+  --lod-error E         simplifies the model while it moves by less than E times its
+                        bounding radius
+  --frames N            renders N frames in a hidden window and exits
+  --screenshot FILE     writes the last of --frames, or the first frame, to a ppm file
+  --export FILE         writes the model, simplified by the --lod options, to an obj or stl file
+                        and exits
+  --vertex FILE         vertex shader replacing the built-in one
+  --fragment FILE       fragment shader replacing the built-in one
+  -h, --help            prints this message
+
+shader overrides get the aPosition and aNormal attributes and the uPVM uniform";
+
+/// Viewer settings from the command line
+pub struct Options {
+    model       : Option<String>,
+    width       : i32,
+    height      : i32,
+    samples     : i32,
+    fullscreen  : bool,
+    lod         : Option<LodTarget>,
+    frames      : Option<u64>,
+    screenshot  : Option<String>,
+    export      : Option<String>,
+    vertex_shader   : Option<String>,
+    fragment_shader : Option<String>,
+    help        : bool,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            model: None, width: 1024, height: 900, samples: 8, fullscreen: false, lod: None,
+            frames: None, screenshot: None, export: None, vertex_shader: None, fragment_shader: None, help: false,
+        }
+    }
+
+    /// Parses the arguments following the program name, options and the model in any order.
+    /// `--name=value` is accepted as well as `--name value`.
+    pub fn parse(args: &[&str]) -> Result<Options, String> {
+        let mut o = Options::new();
+        let mut i = 0;
+        while i < args.len() {
+            let arg = args[i];
+            i += 1;
+
+            if !arg.starts_with("-") || arg == "-" {
+                if o.model.is_some() {
+                    return Err(format!("more than one model given: {}", arg))
+                }
+                o.model = Some(String::from(arg));
+                continue
+            }
+
+            let (name, inline) =
+                match arg.find('=') {
+                    Some(p) if arg.starts_with("--") => (&arg[..p], Some(&arg[p + 1..])),
+                    _ => (arg, None)
+                };
+
+            match name {
+                "-h" | "--help"     => o.help = true,
+                "--fullscreen"      => o.fullscreen = true,
+                "-s" | "--size" | "--msaa" | "--lod" | "--lod-triangles" | "--lod-error" | "--frames" | "--screenshot" | "--export" | "--vertex" | "--fragment" => {
+                    let value =
+                        match inline {
+                            Some(v) => v,
+                            None if i < args.len() => { i += 1; args[i - 1] },
+                            None => return Err(format!("{} expects a value", name))
+                        };
+
+                    match name {
+                        "-s" | "--size" => {
+                            let (w, h) = parse_size(value).ok_or_else(|| format!("{} expects WIDTHxHEIGHT, got '{}'", name, value))?;
+                            o.width = w;
+                            o.height = h;
+                        },
+                        "--msaa"        => o.samples = parse_samples(name, value)?,
+                        "--lod"         => {
+                            // keeping every triangle is no simplification at all
+                            let r = parse_ratio(name, value)?;
+                            o.lod = if r < 1.0 { Some(LodTarget::Ratio(r)) } else { None };
+                        },
+                        "--lod-triangles" => o.lod = Some(LodTarget::Triangles(parse_count(name, value)? as usize)),
+                        "--lod-error"   => o.lod = Some(LodTarget::Error(parse_error(name, value)?)),
+                        "--frames"      => o.frames = Some(parse_count(name, value)? as u64),
+                        "--screenshot"  => o.screenshot = Some(String::from(value)),
+                        "--export"      => o.export = Some(String::from(value)),
+                        "--vertex"      => o.vertex_shader = Some(String::from(value)),
+                        _               => o.fragment_shader = Some(String::from(value)),
+                    }
+                },
+                _ => return Err(format!("unknown option {}", name))
+            }
+        }
+
+        if o.frames == Some(0) {
+            return Err(String::from("--frames expects at least one frame"))
+        }
+        Ok(o)
+    }
+
+    pub fn model(&self) -> &str { self.model.as_ref().map(|m| m.as_str()).unwrap_or("suzane.obj") }
+    /// false when `model` is the default one
+    pub fn has_model(&self) -> bool { self.model.is_some() }
+    pub fn size(&self) -> (i32, i32) { (self.width, self.height) }
+    pub fn samples(&self) -> i32 { self.samples }
+    pub fn fullscreen(&self) -> bool { self.fullscreen }
+    /// how far to simplify the model, None leaves it alone
+    pub fn lod(&self) -> Option<LodTarget> { self.lod }
+    /// frames to render before exiting, None runs until the window is closed
+    pub fn frames(&self) -> Option<u64> { self.frames }
+    pub fn screenshot(&self) -> Option<&str> { self.screenshot.as_ref().map(|s| s.as_str()) }
+    /// where to convert the model to instead of viewing it
+    pub fn export(&self) -> Option<&str> { self.export.as_ref().map(|s| s.as_str()) }
+    pub fn vertex_shader(&self) -> Option<&str> { self.vertex_shader.as_ref().map(|s| s.as_str()) }
+    pub fn fragment_shader(&self) -> Option<&str> { self.fragment_shader.as_ref().map(|s| s.as_str()) }
+    pub fn help(&self) -> bool { self.help }
+}
+
+fn parse_count(name: &str, value: &str) -> Result<u32, String> {
+    value.parse::<u32>().map_err(|_| format!("{} expects a number, got '{}'", name, value))
+}
+
+fn parse_samples(name: &str, value: &str) -> Result<i32, String> {
+    match value.parse::<i32>() {
+        Ok(n) if n >= 0 => Ok(n),
+        _ => Err(format!("{} expects a number >= 0, got '{}'", name, value))
+    }
+}
+
+fn parse_ratio(name: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(r) if r > 0.0 && r <= 1.0 => Ok(r),
+        _ => Err(format!("{} expects a number in (0, 1], got '{}'", name, value))
+    }
+}
+
+fn parse_error(name: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(e) if e >= 0.0 && e.is_finite() => Ok(e),
+        _ => Err(format!("{} expects a number >= 0, got '{}'", name, value))
+    }
+}
+
+fn parse_size(value: &str) -> Option<(i32, i32)> {
+    let p = value.find(|c| c == 'x' || c == 'X')?;
+    let w = value[..p].parse::<i32>().ok()?;
+    let h = value[p + 1..].parse::<i32>().ok()?;
+    if w > 0 && h > 0 { Some((w, h)) } else { None }
+}
+
+/// The C `main` arguments as strings, the program name included. Fails on the first argument
+/// that is not utf-8.
+///
+/// `argv` must hold `argc` pointers to null terminated strings, or be null.
+pub unsafe fn args(argc: isize, argv: *const *const u8) -> Result<Vec<String>, String> {
+    let mut v = Vec::new();
+    for i in 0..(if argv.is_null() { 0 } else { argc.max(0) as usize }) {
+        let a = *argv.add(i);
+        if a.is_null() {
+            break
+        }
+        let bytes = core::slice::from_raw_parts(a, libc::strlen(a as *const libc::c_char));
+        match core::str::from_utf8(bytes) {
+            Ok(s) => v.push(String::from(s)),
+            Err(_) => return Err(format!("argument {} is not utf-8", i))
+        }
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Options { Options::parse(args).unwrap_or_else(|e| panic!("{}", e.as_str())) }
+
+    fn error(args: &[&str]) -> String {
+        match Options::parse(args) {
+            Ok(_) => String::new(),
+            Err(e) => e
+        }
+    }
+
+    #[test]
+    fn values() {
+        let o = parse(&["--size=800x600", "model.obj", "--msaa", "4", "--lod=0.5", "--frames", "3", "--export", "out.stl"]);
+        assert_eq!(o.model(), "model.obj");
+        assert!(o.has_model());
+        assert_eq!(o.size(), (800, 600));
+        assert_eq!(o.samples(), 4);
+        assert_eq!(o.lod(), Some(LodTarget::Ratio(0.5)));
+        assert_eq!(o.frames(), Some(3));
+        assert_eq!(o.export(), Some("out.stl"));
+
+        // the defaults, the last lod option wins and a full ratio is no lod at all
+        let o = parse(&["--lod-error", "0.01", "--lod-triangles=500"]);
+        assert_eq!((o.model(), o.has_model(), o.size(), o.samples()), ("suzane.obj", false, (1024, 900), 8));
+        assert_eq!(o.lod(), Some(LodTarget::Triangles(500)));
+        assert_eq!(parse(&["--lod", "1"]).lod(), None);
+        assert!(parse(&["-h"]).help());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(&["--msaa"]).as_str(), "--msaa expects a value");
+        assert_eq!(error(&["a.obj", "b.obj"]).as_str(), "more than one model given: b.obj");
+        assert_eq!(error(&["-s", "800"]).as_str(), "-s expects WIDTHxHEIGHT, got '800'");
+        assert_eq!(error(&["--size=0x600"]).as_str(), "--size expects WIDTHxHEIGHT, got '0x600'");
+        assert_eq!(error(&["--frames", "0"]).as_str(), "--frames expects at least one frame");
+        assert_eq!(error(&["--msaa", "-4"]).as_str(), "--msaa expects a number >= 0, got '-4'");
+        assert_eq!(error(&["--msaa", "4294967295"]).as_str(), "--msaa expects a number >= 0, got '4294967295'");
+        assert_eq!(error(&["--lod", "2"]).as_str(), "--lod expects a number in (0, 1], got '2'");
+        assert_eq!(error(&["--wat"]).as_str(), "unknown option --wat");
+    }
+
+    #[test]
+    fn c_arguments() {
+        let (name, model, bad) = (b"viewer\0", b"model.obj\0", b"m\xffdel.obj\0");
+        let argv = [name.as_ptr(), model.as_ptr(), bad.as_ptr()];
+        match unsafe { args(2, argv.as_ptr()) } {
+            Ok(a) => assert_eq!((a.len(), a[1].as_str()), (2, "model.obj")),
+            Err(e) => panic!("{}", e.as_str())
+        }
+        match unsafe { args(3, argv.as_ptr()) } {
+            Ok(_) => panic!("accepted a non utf-8 argument"),
+            Err(e) => assert_eq!(e.as_str(), "argument 2 is not utf-8")
+        }
+    }
+}
@@ -0,0 +1,663 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_math3d::*;
+
+use crate::actions::*;
+
+extern "C" {
+    fn sinf(x: f32) -> f32;
+    fn tanf(x: f32) -> f32;
+    fn atanf(x: f32) -> f32;
+    fn acosf(x: f32) -> f32;
+    fn asinf(x: f32) -> f32;
+    fn atan2f(y: f32, x: f32) -> f32;
+    fn expf(x: f32) -> f32;
+    fn sqrtf(x: f32) -> f32;
+}
+
+const X_AXIS : Vec3f = Vec3f { x: 1.0, y: 0.0, z: 0.0 };
+const Y_AXIS : Vec3f = Vec3f { x: 0.0, y: 1.0, z: 0.0 };
+
+#[derive(Clone, Copy)]
+pub enum Projection {
+    /// vertical field of view in radians
+    Perspective { fov_y: f32 },
+    /// world units visible vertically
+    Orthographic { height: f32 },
+}
+
+/// A camera looking down its local -z with +y up, `rotation` takes camera space to world space
+#[derive(Clone, Copy)]
+pub struct Camera {
+    position    : Vec3f,
+    rotation    : Quatf,
+    projection  : Projection,
+    near        : f32,
+    far         : f32,
+}
+
+impl Camera {
+    pub fn new(projection: Projection, near: f32, far: f32) -> Self {
+        Self { position: Vec3f::zero(), rotation: Quatf::identity(), projection: projection, near: near, far: far }
+    }
+
+    pub fn perspective(fov_y: f32, near: f32, far: f32) -> Self { Self::new(Projection::Perspective { fov_y: fov_y }, near, far) }
+    // the viewer's 2D mode switches its camera with set_projection
+    #[allow(dead_code)]
+    pub fn orthographic(height: f32, near: f32, far: f32) -> Self { Self::new(Projection::Orthographic { height: height }, near, far) }
+
+    pub fn position(&self) -> Vec3f { self.position }
+    pub fn projection(&self) -> Projection { self.projection }
+    // the viewer sets the clip planes and never reads them back
+    #[allow(dead_code)]
+    pub fn near(&self) -> f32 { self.near }
+    #[allow(dead_code)]
+    pub fn far(&self) -> f32 { self.far }
+
+    pub fn set_position(&mut self, position: Vec3f) { self.position = position }
+    pub fn set_rotation(&mut self, rotation: Quatf) { self.rotation = Quatf::normalize(&rotation) }
+    pub fn set_projection(&mut self, projection: Projection) { self.projection = projection }
+    pub fn set_clip(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+    }
+
+    pub fn forward(&self) -> Vec3f { self.rotation.mat3() * Vec3f::new(0.0, 0.0, -1.0) }
+
+    /// places the camera at `eye` looking at `target`, `up` must not be parallel to the view direction
+    // the viewer places its camera with the controllers
+    #[allow(dead_code)]
+    pub fn look_at(&mut self, eye: &Vec3f, target: &Vec3f, up: &Vec3f) {
+        let f = Vec3f::normalize(&(*target - *eye));
+        let s = Vec3f::normalize(&Vec3f::cross(&f, up));
+        let u = Vec3f::cross(&s, &f);
+        self.position = *eye;
+        self.rotation = Quatf::normalize(&Quatf::of_matrix3(&Mat3f::new(s.x, s.y, s.z, u.x, u.y, u.z, -f.x, -f.y, -f.z)));
+    }
+
+    pub fn view(&self) -> Mat4f {
+        Quatf::conjugate(&self.rotation).mat4() * rs_math3d::translate(-self.position)
+    }
+
+    pub fn projection_matrix(&self, aspect: f32) -> Mat4f {
+        match self.projection {
+            Projection::Perspective { fov_y } => rs_math3d::perspective(fov_y, aspect, self.near, self.far),
+            Projection::Orthographic { height } => {
+                let (h, w) = (height * 0.5, height * 0.5 * aspect);
+                rs_math3d::ortho4(-w, w, -h, h, self.near, self.far)
+            }
+        }
+    }
+
+    pub fn view_projection(&self, aspect: f32) -> Mat4f { self.projection_matrix(aspect) * self.view() }
+
+    /// distance from which a sphere of `radius` fits the view both vertically and horizontally,
+    /// for orthographic cameras the distance only has to clear the sphere
+    pub fn fit_distance(&self, radius: f32, aspect: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective { fov_y } => {
+                let half_y = fov_y * 0.5;
+                let half_x = unsafe { atanf(tanf(half_y) * aspect) };
+                radius / unsafe { sinf(half_y.min(half_x)) }
+            },
+            Projection::Orthographic { .. } => radius * 2.0,
+        }
+    }
+
+    /// moves back along the view direction until the sphere fits, orthographic cameras also get
+    /// their height, clip planes are set around the sphere
+    pub fn frame(&mut self, sphere: &Sphere3<f32>, aspect: f32) {
+        let radius = sphere.radius.max(f32::MIN_POSITIVE);
+        let dist = self.fit_distance(radius, aspect);
+        self.position = sphere.center - self.forward() * dist;
+        self.near = (dist - radius).max(dist * 0.01);
+        self.far = dist + radius;
+
+        if let Projection::Orthographic { .. } = self.projection {
+            self.projection = Projection::Orthographic { height: 2.0 * radius * (1.0f32).max(1.0 / aspect) };
+        }
+    }
+
+    /// clip planes tight around a sphere seen from the current position
+    pub fn clip_to(&mut self, sphere: &Sphere3<f32>) {
+        let dist = Vec3f::length(&(sphere.center - self.position));
+        self.far = (dist + sphere.radius).max(f32::MIN_POSITIVE);
+        self.near = (dist - sphere.radius).max(self.far * 0.001);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Damping
+////////////////////////////////////////////////////////////////////////////////
+
+// fraction of the remaining distance to cover this frame, `smoothing` is the time constant in
+// seconds, so the motion does not depend on the frame rate
+fn damping(smoothing: f32, dt: f32) -> f32 {
+    if smoothing <= 0.0 { 1.0 } else { 1.0 - unsafe { expf(-dt / smoothing) } }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 { a + (b - a) * t }
+
+fn lerp3(a: &Vec3f, b: &Vec3f, t: f32) -> Vec3f { *a + (*b - *a) * t }
+
+fn nlerp(a: &Quatf, b: &Quatf, t: f32) -> Quatf {
+    // shortest way around
+    let b = if Quatf::dot(a, b) < 0.0 { Quatf::neg(b) } else { *b };
+    Quatf::normalize(&(*a + (b - *a) * t))
+}
+
+fn axis_angle(axis: &Vec3f, angle: f32) -> Quatf { Quatf::of_axis_angle(axis, angle) }
+
+////////////////////////////////////////////////////////////////////////////////
+/// Orbit
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OrbitMode {
+    /// yaw around the world up and pitch, the horizon stays level
+    Turntable,
+    /// free rotation following the pointer on a virtual sphere
+    Arcball,
+}
+
+/// Rotates around a target, zooms in and out of it and pans it in the view plane
+pub struct OrbitController {
+    mode        : OrbitMode,
+    smoothing   : f32,
+    min_distance: f32,
+
+    target      : Vec3f,
+    rotation    : Quatf,
+    distance    : f32,
+
+    goal_target : Vec3f,
+    goal_rotation : Quatf,
+    goal_distance : f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Vec3f, distance: f32) -> Self {
+        // slightly above the horizon, looking at the target along -z
+        let rotation = axis_angle(&X_AXIS, -0.2);
+        Self {
+            mode: OrbitMode::Turntable, smoothing: 0.08, min_distance: distance * 0.001,
+            target: target, rotation: rotation, distance: distance,
+            goal_target: target, goal_rotation: rotation, goal_distance: distance,
+        }
+    }
+
+    /// damping time constant in seconds, 0 follows the input immediately
+    // the viewer keeps the default damping of every controller
+    #[allow(dead_code)]
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn mode(&self) -> OrbitMode { self.mode }
+    pub fn set_mode(&mut self, mode: OrbitMode) { self.mode = mode }
+    // the viewer only looks at the camera the controller places
+    #[allow(dead_code)]
+    pub fn target(&self) -> Vec3f { self.target }
+    #[allow(dead_code)]
+    pub fn distance(&self) -> f32 { self.distance }
+
+    /// turntable rotation in radians: `yaw` around the world up, `pitch` around the view right
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        let yawed = axis_angle(&Y_AXIS, -yaw) * self.goal_rotation;
+        let pitched = yawed * axis_angle(&X_AXIS, -pitch);
+        // refuse pitching over the poles, the view would flip
+        let back = pitched.mat3() * Vec3f::new(0.0, 0.0, 1.0);
+        self.goal_rotation = if back.y.abs() < 0.99 { pitched } else { yawed };
+    }
+
+    /// arcball rotation dragging from `from` to `to`, both in [-1, 1] viewport coordinates with y up
+    pub fn drag(&mut self, from: Vec2f, to: Vec2f) {
+        let project = |p: Vec2f| {
+            // sphere in the middle, hyperbolic sheet outside so that the edges still turn
+            let d = p.x * p.x + p.y * p.y;
+            let z = if d <= 0.5 { unsafe { sqrtf(1.0 - d) } } else { 0.5 / unsafe { sqrtf(d) } };
+            Vec3f::normalize(&Vec3f::new(p.x, p.y, z))
+        };
+
+        let (a, b) = (project(from), project(to));
+        let axis = Vec3f::cross(&a, &b);
+        let len = Vec3f::length(&axis);
+        if len <= f32::MIN_POSITIVE {
+            return
+        }
+
+        let angle = unsafe { acosf(Vec3f::dot(&a, &b).max(-1.0).min(1.0)) };
+        // the object follows the pointer, so the camera turns the other way
+        self.goal_rotation = Quatf::normalize(&(self.goal_rotation * axis_angle(&(axis / len), -angle)));
+    }
+
+    /// positive steps move closer, each step covers a tenth of the distance
+    pub fn zoom(&mut self, steps: f32) {
+        self.goal_distance = (self.goal_distance * unsafe { expf(-0.1 * steps) }).max(self.min_distance);
+    }
+
+    /// moves the target in the view plane, in fractions of the visible height at the target
+    pub fn pan(&mut self, dx: f32, dy: f32, camera: &Camera) {
+        let height =
+            match camera.projection() {
+                Projection::Perspective { fov_y } => 2.0 * self.goal_distance * unsafe { tanf(fov_y * 0.5) },
+                Projection::Orthographic { height } => height,
+            };
+        let m = self.goal_rotation.mat3();
+        self.goal_target = self.goal_target + (m * X_AXIS) * (-dx * height) + (m * Y_AXIS) * (dy * height);
+    }
+
+    /// heads for the sphere, keeping the current view direction
+    pub fn frame(&mut self, sphere: &Sphere3<f32>, camera: &Camera, aspect: f32) {
+        self.goal_target = sphere.center;
+        self.goal_distance = camera.fit_distance(sphere.radius.max(f32::MIN_POSITIVE), aspect);
+        self.min_distance = self.goal_distance * 0.001;
+    }
+
+    /// jumps to the goal, skipping the damping
+    pub fn snap(&mut self) {
+        self.target = self.goal_target;
+        self.rotation = self.goal_rotation;
+        self.distance = self.goal_distance;
+    }
+
+    /// Pointer drags while `orbit` or `pan` are held (`pointer_x`, `pointer_y` in pixels), rates
+    /// from `orbit_x`, `orbit_y` (radians per second), `pan_x`, `pan_y` (view heights per second)
+    /// and `zoom_rate` (steps per second), and `zoom` steps for the frame
+    pub fn apply_actions(&mut self, actions: &ActionMap, camera: &Camera, viewport: (i32, i32), dt: f32) {
+        let (w, h) = (viewport.0.max(1) as f32, viewport.1.max(1) as f32);
+        let (px, py) = (actions.axis("pointer_x"), actions.axis("pointer_y"));
+
+        if actions.is_active("orbit") {
+            match self.mode {
+                OrbitMode::Turntable => self.rotate(px / h * 4.0, py / h * 4.0),
+                OrbitMode::Arcball => {
+                    // only the delta matters, measured from the viewport center
+                    self.drag(Vec2f::new(0.0, 0.0), Vec2f::new(2.0 * px / w, -2.0 * py / h));
+                }
+            }
+        } else if actions.is_active("pan") {
+            self.pan(px / h, py / h, camera);
+        }
+
+        self.rotate(actions.axis("orbit_x") * dt, actions.axis("orbit_y") * dt);
+        self.pan(actions.axis("pan_x") * dt, actions.axis("pan_y") * dt, camera);
+        self.zoom(actions.axis("zoom") + actions.axis("zoom_rate") * dt);
+    }
+
+    /// Damps towards the goal and places the camera
+    pub fn update(&mut self, dt: f32, camera: &mut Camera) {
+        let t = damping(self.smoothing, dt);
+        self.target = lerp3(&self.target, &self.goal_target, t);
+        self.rotation = nlerp(&self.rotation, &self.goal_rotation, t);
+        self.distance = lerp(self.distance, self.goal_distance, t);
+
+        camera.set_rotation(self.rotation);
+        camera.set_position(self.target + self.rotation.mat3() * Vec3f::new(0.0, 0.0, self.distance));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Fly
+////////////////////////////////////////////////////////////////////////////////
+
+/// First person camera: looks around with yaw and pitch and moves along its own axes
+pub struct FlyController {
+    smoothing   : f32,
+    speed       : f32,
+
+    position    : Vec3f,
+    yaw         : f32,
+    pitch       : f32,
+    velocity    : Vec3f,
+
+    goal_yaw    : f32,
+    goal_pitch  : f32,
+    goal_velocity : Vec3f,
+}
+
+impl FlyController {
+    pub fn new(position: Vec3f, speed: f32) -> Self {
+        Self {
+            smoothing: 0.08, speed: speed,
+            position: position, yaw: 0.0, pitch: 0.0, velocity: Vec3f::zero(),
+            goal_yaw: 0.0, goal_pitch: 0.0, goal_velocity: Vec3f::zero(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// units per second at full stick or key
+    // the viewer sets the speed from the model size once, in new
+    #[allow(dead_code)]
+    pub fn set_speed(&mut self, speed: f32) { self.speed = speed }
+
+    /// starts from the camera's position and view direction
+    pub fn from_camera(&mut self, camera: &Camera) {
+        // forward is (-sin(yaw) cos(pitch), sin(pitch), -cos(yaw) cos(pitch))
+        let f = camera.forward();
+        self.position = camera.position();
+        self.goal_yaw = unsafe { atan2f(-f.x, -f.z) };
+        self.goal_pitch = unsafe { asinf(f.y.max(-1.0).min(1.0)) };
+        self.yaw = self.goal_yaw;
+        self.pitch = self.goal_pitch;
+        self.velocity = Vec3f::zero();
+        self.goal_velocity = Vec3f::zero();
+    }
+
+    /// radians, positive yaw turns left and positive pitch looks up
+    pub fn look(&mut self, yaw: f32, pitch: f32) {
+        let limit = core::f32::consts::FRAC_PI_2 * 0.99;
+        self.goal_yaw += yaw;
+        self.goal_pitch = (self.goal_pitch + pitch).max(-limit).min(limit);
+    }
+
+    /// movement in the camera's frame: x right, y up, z forward, each in [-1, 1]
+    pub fn set_motion(&mut self, motion: Vec3f) {
+        self.goal_velocity = motion * self.speed;
+    }
+
+    fn orientation(&self) -> Quatf { axis_angle(&Y_AXIS, self.yaw) * axis_angle(&X_AXIS, self.pitch) }
+
+    /// Looks with pointer drags while `look` is held, moves with `move_x`, `move_y`, `move_z`
+    /// and runs at 4x while `sprint` is held
+    pub fn apply_actions(&mut self, actions: &ActionMap, viewport: (i32, i32)) {
+        let h = viewport.1.max(1) as f32;
+        if actions.is_active("look") {
+            self.look(-actions.axis("pointer_x") / h * 2.0, -actions.axis("pointer_y") / h * 2.0);
+        }
+        let boost = if actions.is_active("sprint") { 4.0 } else { 1.0 };
+        self.set_motion(Vec3f::new(actions.axis("move_x"), actions.axis("move_y"), actions.axis("move_z")) * boost);
+    }
+
+    pub fn update(&mut self, dt: f32, camera: &mut Camera) {
+        let t = damping(self.smoothing, dt);
+        self.yaw = lerp(self.yaw, self.goal_yaw, t);
+        self.pitch = lerp(self.pitch, self.goal_pitch, t);
+        self.velocity = lerp3(&self.velocity, &self.goal_velocity, t);
+
+        let rotation = self.orientation();
+        let m = rotation.mat3();
+        let v = self.velocity;
+        self.position = self.position + (m * X_AXIS * v.x + m * Y_AXIS * v.y + m * Vec3f::new(0.0, 0.0, -1.0) * v.z) * dt;
+
+        camera.set_rotation(rotation);
+        camera.set_position(self.position);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// 2D pan and zoom
+////////////////////////////////////////////////////////////////////////////////
+
+/// Orthographic view of the xy plane, pans with the pointer and zooms around it
+pub struct PanZoomController {
+    smoothing   : f32,
+    center      : Vec2f,
+    height      : f32,
+    goal_center : Vec2f,
+    goal_height : f32,
+    depth       : f32,
+}
+
+impl PanZoomController {
+    /// `height` world units visible vertically, `depth` is the z range kept in front of the camera
+    pub fn new(center: Vec2f, height: f32, depth: f32) -> Self {
+        Self { smoothing: 0.08, center: center, height: height, goal_center: center, goal_height: height, depth: depth }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// moves the view by a pointer delta in pixels
+    pub fn pan(&mut self, dx: f32, dy: f32, viewport: (i32, i32)) {
+        let scale = self.goal_height / viewport.1.max(1) as f32;
+        self.goal_center = self.goal_center + Vec2f::new(-dx * scale, dy * scale);
+    }
+
+    /// zooms by `steps` keeping the point under `pointer` (pixels, origin top left) in place
+    pub fn zoom_at(&mut self, steps: f32, pointer: Vec2f, viewport: (i32, i32)) {
+        let (w, h) = (viewport.0.max(1) as f32, viewport.1.max(1) as f32);
+        let offset = Vec2f::new(pointer.x - w * 0.5, h * 0.5 - pointer.y) * (self.goal_height / h);
+        let height = self.goal_height * unsafe { expf(-0.1 * steps) };
+        let anchor = self.goal_center + offset;
+        self.goal_center = anchor - offset * (height / self.goal_height);
+        self.goal_height = height;
+    }
+
+    /// fits the sphere's projection on the xy plane
+    pub fn frame(&mut self, sphere: &Sphere3<f32>, aspect: f32) {
+        self.goal_center = Vec2f::new(sphere.center.x, sphere.center.y);
+        self.goal_height = 2.0 * sphere.radius.max(f32::MIN_POSITIVE) * (1.0f32).max(1.0 / aspect);
+        self.depth = self.depth.max(4.0 * sphere.radius);
+    }
+
+    pub fn snap(&mut self) {
+        self.center = self.goal_center;
+        self.height = self.goal_height;
+    }
+
+    /// Pans with pointer drags while `pan` or `orbit` are held, zooms with `zoom` around the cursor
+    pub fn apply_actions(&mut self, actions: &ActionMap, pointer: Vec2f, viewport: (i32, i32), dt: f32) {
+        if actions.is_active("pan") || actions.is_active("orbit") {
+            self.pan(actions.axis("pointer_x"), actions.axis("pointer_y"), viewport);
+        }
+
+        let h = viewport.1.max(1) as f32;
+        self.pan(-actions.axis("pan_x") * dt * h, -actions.axis("pan_y") * dt * h, viewport);
+        self.zoom_at(actions.axis("zoom") + actions.axis("zoom_rate") * dt, pointer, viewport);
+    }
+
+    pub fn update(&mut self, dt: f32, camera: &mut Camera) {
+        let t = damping(self.smoothing, dt);
+        self.center = self.center + (self.goal_center - self.center) * t;
+        self.height = lerp(self.height, self.goal_height, t);
+
+        camera.set_projection(Projection::Orthographic { height: self.height });
+        camera.set_rotation(Quatf::identity());
+        camera.set_position(Vec3f::new(self.center.x, self.center.y, self.depth * 0.5));
+        camera.set_clip(0.0, self.depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PI : f32 = core::f32::consts::PI;
+
+    fn close(a: Vec3f, b: Vec3f) -> bool { Vec3f::length(&(a - b)) < 1e-4 }
+
+    fn sphere(center: Vec3f, radius: f32) -> Sphere3<f32> { Sphere3 { center: center, radius: radius } }
+
+    // the camera looks at `target` from `distance` away
+    fn looks_at(camera: &Camera, target: Vec3f, distance: f32) -> bool {
+        let to = target - camera.position();
+        (Vec3f::length(&to) - distance).abs() < 1e-4 && close(camera.forward(), Vec3f::normalize(&to))
+    }
+
+    #[test]
+    fn frame_bounds() {
+        let s = sphere(Vec3f::new(1.0, 2.0, 3.0), 2.0);
+
+        // a 90 degree view: the sphere touches the frustum at sqrt(2) radii
+        let mut camera = Camera::perspective(PI * 0.5, 0.1, 100.0);
+        camera.frame(&s, 1.0);
+        let d = 2.0 * unsafe { sqrtf(2.0) };
+        assert!(close(camera.position(), Vec3f::new(1.0, 2.0, 3.0 + d)));
+        assert!((camera.near() - (d - 2.0)).abs() < 1e-4 && (camera.far() - (d + 2.0)).abs() < 1e-4);
+
+        // a narrow window is limited by its width, tan(half x) = 0.5
+        assert!((camera.fit_distance(1.0, 0.5) - unsafe { sqrtf(5.0) }).abs() < 1e-4);
+
+        // orthographic cameras grow their height instead
+        let mut ortho = Camera::orthographic(1.0, 0.1, 100.0);
+        ortho.frame(&s, 0.5);
+        match ortho.projection() {
+            Projection::Orthographic { height } => assert_eq!(height, 8.0),
+            _ => panic!("projection changed")
+        }
+        assert!(ortho.near() > 0.0 && ortho.far() - ortho.near() >= 4.0);
+    }
+
+    #[test]
+    fn look_at() {
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        camera.look_at(&Vec3f::new(0.0, 1.0, 5.0), &Vec3f::zero(), &Y_AXIS);
+        assert!(looks_at(&camera, Vec3f::zero(), unsafe { sqrtf(26.0) }));
+
+        // the view matrix takes the target in front of the camera, down -z
+        let p = camera.view() * Vec4f::new(0.0, 0.0, 0.0, 1.0);
+        assert!(p.x.abs() < 1e-5 && p.y.abs() < 1e-5 && p.z < 0.0);
+    }
+
+    #[test]
+    fn orbit_turntable() {
+        let target = Vec3f::new(0.0, 1.0, 0.0);
+        let mut orbit = OrbitController::new(target, 4.0).with_smoothing(0.0);
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        orbit.update(0.0, &mut camera);
+        assert!(looks_at(&camera, target, 4.0));
+
+        // a quarter turn, as from dragging right, takes the camera from +z round to -x
+        let y = camera.position().y;
+        orbit.rotate(PI * 0.5, 0.0);
+        orbit.update(0.016, &mut camera);
+        assert!(looks_at(&camera, target, 4.0));
+        assert!(camera.position().z.abs() < 1e-4 && camera.position().x < 0.0 && (camera.position().y - y).abs() < 1e-4);
+
+        // pitching stops short of the pole, zoom steps cover a tenth of the distance
+        for _ in 0..10 {
+            orbit.rotate(0.0, 1.0);
+        }
+        orbit.zoom(10.0);
+        orbit.update(0.016, &mut camera);
+        let back = Vec3f::normalize(&(camera.position() - target));
+        assert!(back.y.abs() < 0.99);
+        assert!((orbit.distance() - 4.0 * unsafe { expf(-1.0) }).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_damping() {
+        let mut orbit = OrbitController::new(Vec3f::zero(), 1.0).with_smoothing(0.1);
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        orbit.zoom(-10.0 * core::f32::consts::LN_2);
+        orbit.update(0.1, &mut camera);
+        // one time constant covers 1 - 1/e of the way from 1 to 2
+        assert!((orbit.distance() - (2.0 - unsafe { expf(-1.0) })).abs() < 1e-4);
+        orbit.snap();
+        assert!((orbit.distance() - 2.0).abs() < 1e-4);
+
+        orbit.frame(&sphere(Vec3f::new(5.0, 0.0, 0.0), 1.0), &camera, 1.0);
+        orbit.snap();
+        orbit.update(0.0, &mut camera);
+        assert_eq!(orbit.target().x, 5.0);
+        assert!((orbit.distance() - camera.fit_distance(1.0, 1.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_arcball() {
+        let mut orbit = OrbitController::new(Vec3f::zero(), 2.0).with_smoothing(0.0);
+        orbit.set_mode(OrbitMode::Arcball);
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        orbit.update(0.0, &mut camera);
+        let before = camera.position();
+
+        // dragging right turns the model right, the camera goes around to the left
+        orbit.drag(Vec2f::new(0.0, 0.0), Vec2f::new(0.5, 0.0));
+        orbit.update(0.0, &mut camera);
+        assert!(looks_at(&camera, Vec3f::zero(), 2.0));
+        assert!(camera.position().x < -0.1);
+
+        // and dragging back undoes it
+        orbit.drag(Vec2f::new(0.5, 0.0), Vec2f::new(0.0, 0.0));
+        orbit.update(0.0, &mut camera);
+        assert!(close(camera.position(), before));
+    }
+
+    #[test]
+    fn fly() {
+        let mut fly = FlyController::new(Vec3f::zero(), 2.0).with_smoothing(0.0);
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+
+        // forward is -z, half a second at 2 units per second
+        fly.set_motion(Vec3f::new(0.0, 0.0, 1.0));
+        fly.update(0.5, &mut camera);
+        assert!(close(camera.position(), Vec3f::new(0.0, 0.0, -1.0)));
+
+        // a quarter turn left looks down -x
+        fly.look(PI * 0.5, 0.0);
+        fly.update(0.5, &mut camera);
+        assert!(close(camera.forward(), Vec3f::new(-1.0, 0.0, 0.0)));
+        assert!(close(camera.position(), Vec3f::new(-1.0, 0.0, -1.0)));
+
+        // picking up a camera keeps its view
+        let mut other = Camera::perspective(1.0, 0.1, 100.0);
+        other.look_at(&Vec3f::new(1.0, 2.0, 3.0), &Vec3f::zero(), &Y_AXIS);
+        fly.set_motion(Vec3f::zero());
+        fly.from_camera(&other);
+        fly.update(0.1, &mut camera);
+        assert!(close(camera.position(), other.position()) && close(camera.forward(), other.forward()));
+    }
+
+    #[test]
+    fn pan_zoom_anchor() {
+        let viewport = (800, 600);
+        let mut pz = PanZoomController::new(Vec2f::new(1.0, 1.0), 10.0, 10.0).with_smoothing(0.0);
+        let world = |pz: &PanZoomController, p: Vec2f| {
+            let scale = pz.goal_height / viewport.1 as f32;
+            pz.goal_center + Vec2f::new(p.x - 400.0, 300.0 - p.y) * scale
+        };
+
+        // the point under the pointer stays put while zooming
+        let pointer = Vec2f::new(100.0, 50.0);
+        let before = world(&pz, pointer);
+        pz.zoom_at(5.0, pointer, viewport);
+        let after = world(&pz, pointer);
+        assert!((before.x - after.x).abs() < 1e-4 && (before.y - after.y).abs() < 1e-4);
+        assert!(pz.goal_height < 10.0);
+
+        // dragging right by the viewport height moves the view left by its height
+        let (x, h) = (pz.goal_center.x, pz.goal_height);
+        pz.pan(600.0, 0.0, viewport);
+        assert!((pz.goal_center.x - (x - h)).abs() < 1e-4);
+
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        pz.update(0.0, &mut camera);
+        assert!(close(camera.position(), Vec3f::new(pz.center.x, pz.center.y, 5.0)));
+    }
+}
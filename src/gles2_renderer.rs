@@ -32,6 +32,8 @@ use rs_ctypes::*;
 use rs_alloc::*;
 use rs_math3d::*;
 
+use crate::trace;
+
 pub struct GLProgram {
     prog_id     : GLuint,
     attribs     : Vec<(VertexAttributeDesc, GLuint)>,
@@ -79,9 +81,13 @@ impl GLProgram {
             let fragment_shader  = Self::load_shader(fs, GL_FRAGMENT_SHADER);
 
             match (vertex_shader, fragment_shader) {
-                (None, None) => (),
-                (None, Some(f)) => glDeleteShader(f),
-                (Some(v), None) => glDeleteShader(v),
+                (None, None) | (None, Some(_)) | (Some(_), None) => {
+                    if let Some(v) = vertex_shader { glDeleteShader(v) }
+                    if let Some(f) = fragment_shader { glDeleteShader(f) }
+                    trace::record(format_args!("program {}: compile failed", program_object));
+                    glDeleteProgram(program_object);
+                    return None
+                },
                 (Some(v), Some(f)) => {
                     glAttachShader(program_object, v);
                     glAttachShader(program_object, f);
@@ -99,6 +105,10 @@ impl GLProgram {
                             free_array(sptr, info_len as usize, info_len as usize);
                         }
 
+                        // attached shaders are only flagged, the program takes them along
+                        glDeleteShader(f);
+                        glDeleteShader(v);
+                        trace::record(format_args!("program {}: link failed", program_object));
                         glDeleteProgram(program_object);
                         return None
                     }
@@ -132,6 +142,7 @@ impl GLProgram {
                 prg_uniforms.push((u.clone(), au));
             }
 
+            trace::record(format_args!("program {}: {} attributes, {} uniforms", program_object, prg_attribs.len(), prg_uniforms.len()));
             let s = Box::new(Self { prog_id: program_object, attribs: prg_attribs, uniforms: prg_uniforms });
             Some(Box::from_raw(Box::into_raw(s) as *mut dyn Program))
         }
@@ -140,6 +151,7 @@ impl GLProgram {
 
 impl Drop for GLProgram {
     fn drop(&mut self) {
+        trace::record(format_args!("delete program {}", self.prog_id));
         unsafe { glDeleteProgram(self.prog_id) };
     }
 }
@@ -162,6 +174,7 @@ impl StaticVertexBuffer {
         glGenBuffers(1, &mut buff);
         glBindBuffer(GL_ARRAY_BUFFER, buff);
         glBufferData(GL_ARRAY_BUFFER, buff_size as GLsizeiptr, buff_data as *const rs_ctypes::c_void, GL_STATIC_DRAW);
+        trace::record(format_args!("vertex buffer {}: {} bytes, stride {}", buff, buff_size, stride));
         Self {
             stride  : stride,
             buff_id : buff,
@@ -178,6 +191,7 @@ impl StaticVertexBuffer {
 
 impl Drop for StaticVertexBuffer {
     fn drop(&mut self) {
+        trace::record(format_args!("delete vertex buffer {}", self.buff_id));
         unsafe { glDeleteBuffers(1, &self.buff_id as *const GLuint) }
     }
 }
@@ -213,6 +227,7 @@ impl StaticIndexBuffer {
         glGenBuffers(1, &mut buff);
         glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, buff);
         glBufferData(GL_ELEMENT_ARRAY_BUFFER, buff_size as GLsizeiptr, buff_data as *const rs_ctypes::c_void, GL_STATIC_DRAW);
+        trace::record(format_args!("index buffer {}: {} bytes", buff, buff_size));
         Self {
             index_type : index_type,
             buff_id : buff,
@@ -235,6 +250,7 @@ impl StaticIndexBuffer {
 
 impl Drop for StaticIndexBuffer {
     fn drop(&mut self) {
+        trace::record(format_args!("delete index buffer {}", self.buff_id));
         unsafe { glDeleteBuffers(1, &self.buff_id as *const GLuint) }
     }
 }
@@ -0,0 +1,914 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use core::result::Result;
+use rs_alloc::*;
+use rs_streams::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+use crate::renderer::{Filter, Wrap};
+use crate::json;
+use crate::json::Value;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Scene description
+////////////////////////////////////////////////////////////////////////////////
+
+// The viewer only draws `Scene::flatten`, the allows below cover what it does not read yet:
+// materials, textures, the extra vertex attributes and the node names.
+
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[allow(dead_code)]
+pub struct TextureRef {
+    pub texture     : usize,
+    pub tex_coord   : usize,
+    pub scale       : f32,  // normal map scale or occlusion strength, 1 otherwise
+}
+
+/// glTF metallic-roughness material
+#[allow(dead_code)]
+pub struct PbrMaterial {
+    pub name                : String,
+    pub base_color          : Vec4f,
+    pub base_color_texture  : Option<TextureRef>,
+    pub metallic            : f32,
+    pub roughness           : f32,
+    pub metallic_roughness_texture : Option<TextureRef>,
+    pub normal_texture      : Option<TextureRef>,
+    pub occlusion_texture   : Option<TextureRef>,
+    pub emissive            : Vec3f,
+    pub emissive_texture    : Option<TextureRef>,
+    pub alpha_mode          : AlphaMode,
+    pub alpha_cutoff        : f32,
+    pub double_sided        : bool,
+}
+
+#[allow(dead_code)]
+pub struct Sampler {
+    pub mag_filter  : Filter,
+    pub min_filter  : Filter,
+    pub wrap_s      : Wrap,
+    pub wrap_t      : Wrap,
+}
+
+/// an image is either an external file (resolved against the gltf's directory) or embedded bytes
+#[allow(dead_code)]
+pub enum Image {
+    File(String),
+    Embedded { mime: String, data: Vec<u8> },
+}
+
+#[allow(dead_code)]
+pub struct Texture {
+    pub image   : Option<usize>,
+    pub sampler : Sampler,
+}
+
+/// one draw call worth of geometry, attributes glTF has beyond `Mesh` are kept alongside
+#[allow(dead_code)]
+pub struct Primitive {
+    pub mesh        : Mesh,
+    pub tangents    : Vec<Vec4f>,
+    pub uvs1        : Vec<Vec2f>,
+    pub joints      : Vec<[u16; 4]>,
+    pub weights     : Vec<Vec4f>,
+    pub material    : Option<usize>,
+}
+
+#[allow(dead_code)]
+pub struct GltfMesh {
+    pub name        : String,
+    pub primitives  : Vec<Primitive>,
+}
+
+#[allow(dead_code)]
+pub struct Node {
+    pub name        : String,
+    pub mesh        : Option<usize>,
+    pub children    : Vec<usize>,
+    pub parent      : Option<usize>,
+    pub local       : Mat4f,
+}
+
+#[allow(dead_code)]
+pub struct Scene {
+    meshes      : Vec<GltfMesh>,
+    materials   : Vec<PbrMaterial>,
+    textures    : Vec<Texture>,
+    images      : Vec<Image>,
+    nodes       : Vec<Node>,
+    roots       : Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl Scene {
+    pub fn meshes(&self)    -> &[GltfMesh]      { self.meshes.as_slice() }
+    pub fn materials(&self) -> &[PbrMaterial]   { self.materials.as_slice() }
+    pub fn textures(&self)  -> &[Texture]       { self.textures.as_slice() }
+    pub fn images(&self)    -> &[Image]         { self.images.as_slice() }
+    pub fn nodes(&self)     -> &[Node]          { self.nodes.as_slice() }
+    pub fn roots(&self)     -> &[usize]         { self.roots.as_slice() }
+
+    /// node to scene space transform
+    pub fn world_transform(&self, node: usize) -> Mat4f {
+        let mut m = self.nodes[node].local;
+        let mut parent = self.nodes[node].parent;
+        while let Some(p) = parent {
+            m = self.nodes[p].local * m;
+            parent = self.nodes[p].parent;
+        }
+        m
+    }
+
+    /// The primitives of the nodes reachable from the roots, in scene space and merged in one
+    /// mesh. Materials and the attributes `Mesh` has no room for are left out.
+    pub fn flatten(&self) -> Mesh {
+        let mut parts = Vec::new();
+        let mut stack : Vec<usize> = self.roots.iter().cloned().collect();
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            for c in node.children.iter() {
+                stack.push(*c);
+            }
+
+            let mesh = match node.mesh.and_then(|m| self.meshes.as_slice().get(m)) { Some(m) => m, None => continue };
+            let world = self.world_transform(n);
+            for p in mesh.primitives.iter() {
+                let mut m = Mesh::merge(&[&p.mesh]);
+                m.transform(&world);
+                parts.push(m);
+            }
+        }
+
+        let refs : Vec<&Mesh> = parts.iter().collect();
+        Mesh::merge(refs.as_slice())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Buffers
+////////////////////////////////////////////////////////////////////////////////
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let v =
+            match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' | b'-' => 62,
+                b'/' | b'_' => 63,
+                b'=' => break,
+                b' ' | b'\n' | b'\r' | b'\t' => continue,
+                _ => return Err(String::from("invalid base64"))
+            };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+fn join_path(dir: &str, uri: &str) -> String {
+    let mut p = String::from(dir);
+    p.push_str(uri);
+    p
+}
+
+// "data:<mime>;base64,<payload>"
+fn decode_data_uri(uri: &str) -> Option<Result<(String, Vec<u8>), String>> {
+    if !uri.starts_with("data:") {
+        return None
+    }
+
+    let comma = match uri.find(',') { Some(c) => c, None => return Some(Err(String::from("invalid data uri"))) };
+    let head = &uri[5..comma];
+    if !head.ends_with(";base64") {
+        return Some(Err(String::from("only base64 data uris are supported")))
+    }
+
+    let mime = String::from(&head[..head.len() - 7]);
+    Some(base64_decode(&uri[comma + 1..]).map(|d| (mime, d)))
+}
+
+fn load_buffer(uri: Option<&str>, dir: &str, bin: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    match uri {
+        Some(uri) => {
+            match decode_data_uri(uri) {
+                Some(r) => r.map(|(_, d)| d),
+                None => read_bytes(join_path(dir, uri).as_str())
+            }
+        },
+        None => {
+            match bin {
+                Some(b) => {
+                    let mut v = Vec::with_capacity(b.len());
+                    v.append(b);
+                    Ok(v)
+                },
+                None => Err(String::from("buffer without uri outside of a glb"))
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Accessors
+////////////////////////////////////////////////////////////////////////////////
+
+// values of an accessor without buffer view, 64M is 512MB of f64
+const MAX_ZERO_VALUES : usize = 1 << 26;
+
+struct Accessor {
+    count   : usize,
+    comps   : usize,
+    values  : Vec<f64>,
+}
+
+fn component_size(ty: usize) -> Result<usize, String> {
+    match ty {
+        5120 | 5121 => Ok(1),
+        5122 | 5123 => Ok(2),
+        5125 | 5126 => Ok(4),
+        _ => Err(format!("unknown component type {}", ty))
+    }
+}
+
+fn read_component(data: &[u8], pos: usize, ty: usize, normalized: bool) -> f64 {
+    let b = &data[pos..];
+    match (ty, normalized) {
+        (5120, false) => b[0] as i8 as f64,
+        (5120, true)  => f64::max(b[0] as i8 as f64 / 127.0, -1.0),
+        (5121, false) => b[0] as f64,
+        (5121, true)  => b[0] as f64 / 255.0,
+        (5122, false) => i16::from_le_bytes([b[0], b[1]]) as f64,
+        (5122, true)  => f64::max(i16::from_le_bytes([b[0], b[1]]) as f64 / 32767.0, -1.0),
+        (5123, false) => u16::from_le_bytes([b[0], b[1]]) as f64,
+        (5123, true)  => u16::from_le_bytes([b[0], b[1]]) as f64 / 65535.0,
+        (5125, _)     => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+        _             => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+    }
+}
+
+fn type_components(ty: &str) -> Result<usize, String> {
+    match ty {
+        "SCALAR" => Ok(1),
+        "VEC2" => Ok(2),
+        "VEC3" => Ok(3),
+        "VEC4" => Ok(4),
+        "MAT2" => Ok(4),
+        "MAT3" => Ok(9),
+        "MAT4" => Ok(16),
+        _ => Err(format!("unknown accessor type {}", ty))
+    }
+}
+
+fn buffer_view<'a>(doc: &'a Value, buffers: &'a [Vec<u8>], view: usize) -> Result<(&'a Value, &'a Vec<u8>), String> {
+    let v = match doc.get("bufferViews").and_then(|b| b.as_array()).and_then(|b| b.get(view)) {
+        Some(v) => v,
+        None => return Err(String::from("invalid buffer view"))
+    };
+
+    match v.get("buffer").and_then(|b| b.as_usize()).and_then(|b| buffers.get(b)) {
+        Some(b) => Ok((v, b)),
+        None => Err(String::from("invalid buffer"))
+    }
+}
+
+// a copy of a buffer view's bytes, for the data that is not read through accessors
+fn view_bytes(doc: &Value, buffers: &[Vec<u8>], view: usize) -> Result<Vec<u8>, String> {
+    let (v, buffer) = buffer_view(doc, buffers, view)?;
+    let start = v.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+    let len = v.get("byteLength").and_then(|l| l.as_usize()).unwrap_or(0);
+    match start.checked_add(len) {
+        Some(end) if end <= buffer.len() => {
+            let mut data = Vec::with_capacity(len);
+            data.append(&buffer.as_slice()[start..end]);
+            Ok(data)
+        },
+        _ => Err(String::from("buffer view out of buffer range"))
+    }
+}
+
+// reads `count` elements of `comps` components from a buffer view
+fn read_view(doc: &Value, buffers: &[Vec<u8>], view: usize, offset: usize, count: usize, comps: usize, ty: usize, normalized: bool, out: &mut Vec<f64>) -> Result<(), String> {
+    let (v, buffer) = buffer_view(doc, buffers, view)?;
+
+    // every value comes from the file, a wrapped sum would pass the range check
+    let csize = component_size(ty)?;
+    let size = csize * comps;
+    let stride = v.get("byteStride").and_then(|o| o.as_usize()).unwrap_or(size);
+    let start = v.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0).checked_add(offset);
+    let end =
+        match (start, count) {
+            (Some(s), 0) => Some(s),
+            (Some(s), _) => (count - 1).checked_mul(stride).and_then(|n| n.checked_add(s)).and_then(|n| n.checked_add(size)),
+            (None, _) => None
+        };
+    let start =
+        match (start, end) {
+            (Some(s), Some(e)) if e <= buffer.len() => s,
+            _ => return Err(String::from("accessor out of buffer range"))
+        };
+
+    for i in 0..count {
+        for c in 0..comps {
+            out.push(read_component(buffer.as_slice(), start + i * stride + c * csize, ty, normalized));
+        }
+    }
+    Ok(())
+}
+
+fn read_accessor(doc: &Value, buffers: &[Vec<u8>], index: usize) -> Result<Accessor, String> {
+    let a = match doc.get("accessors").and_then(|a| a.as_array()).and_then(|a| a.get(index)) {
+        Some(a) => a,
+        None => return Err(format!("invalid accessor {}", index))
+    };
+
+    let count = a.get("count").and_then(|c| c.as_usize()).unwrap_or(0);
+    let comps = type_components(a.get("type").and_then(|t| t.as_str()).unwrap_or(""))?;
+    let ty = a.get("componentType").and_then(|t| t.as_usize()).unwrap_or(0);
+    let normalized = a.get("normalized").and_then(|n| n.as_bool()).unwrap_or(false);
+    let offset = a.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+
+    // read_view checks the count against the buffer, zero filled accessors have nothing to check
+    // it against but a size no asset gets near
+    let mut values = Vec::new();
+    match a.get("bufferView").and_then(|v| v.as_usize()) {
+        Some(view) => read_view(doc, buffers, view, offset, count, comps, ty, normalized, &mut values)?,
+        None => {
+            let n =
+                match count.checked_mul(comps) {
+                    Some(n) if n <= MAX_ZERO_VALUES => n,
+                    _ => return Err(format!("accessor {} is too large", index))
+                };
+            for _ in 0..n {
+                values.push(0.0);
+            }
+        }
+    }
+
+    // sparse accessors patch a few elements of the base data
+    match a.get("sparse") {
+        Some(s) => {
+            let n = s.get("count").and_then(|c| c.as_usize()).unwrap_or(0);
+            let (idx, val) =
+                match (s.get("indices"), s.get("values")) {
+                    (Some(i), Some(v)) => (i, v),
+                    _ => return Err(String::from("invalid sparse accessor"))
+                };
+
+            let mut indices = Vec::new();
+            read_view(doc, buffers, idx.get("bufferView").and_then(|v| v.as_usize()).unwrap_or(usize::MAX),
+                      idx.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0), n, 1,
+                      idx.get("componentType").and_then(|t| t.as_usize()).unwrap_or(0), false, &mut indices)?;
+
+            let mut patch = Vec::new();
+            read_view(doc, buffers, val.get("bufferView").and_then(|v| v.as_usize()).unwrap_or(usize::MAX),
+                      val.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0), n, comps, ty, normalized, &mut patch)?;
+
+            for i in 0..n {
+                let e = indices[i] as usize;
+                if e >= count {
+                    return Err(String::from("sparse index out of range"))
+                }
+                for c in 0..comps {
+                    values[e * comps + c] = patch[i * comps + c];
+                }
+            }
+        },
+        None => ()
+    }
+
+    Ok(Accessor { count: count, comps: comps, values: values })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Scene parsing
+////////////////////////////////////////////////////////////////////////////////
+
+fn get_f32(v: &Value, key: &str, default: f32) -> f32 {
+    v.get(key).and_then(|f| f.as_f64()).map(|f| f as f32).unwrap_or(default)
+}
+
+fn get_floats<const N: usize>(v: &Value, key: &str, default: [f32; N]) -> [f32; N] {
+    let mut r = default;
+    match v.get(key).and_then(|a| a.as_array()) {
+        Some(a) if a.len() == N => {
+            for i in 0..N {
+                r[i] = a[i].as_f64().unwrap_or(0.0) as f32;
+            }
+        },
+        _ => ()
+    }
+    r
+}
+
+fn get_name(v: &Value) -> String {
+    String::from(v.get("name").and_then(|n| n.as_str()).unwrap_or(""))
+}
+
+fn texture_ref(v: &Value, key: &str, scale_key: &str) -> Option<TextureRef> {
+    let t = v.get(key)?;
+    Some(TextureRef {
+        texture     : t.get("index")?.as_usize()?,
+        tex_coord   : t.get("texCoord").and_then(|c| c.as_usize()).unwrap_or(0),
+        scale       : get_f32(t, scale_key, 1.0),
+    })
+}
+
+fn parse_material(m: &Value) -> PbrMaterial {
+    let empty = Value::Null;
+    let pbr = m.get("pbrMetallicRoughness").unwrap_or(&empty);
+    let bc = get_floats(pbr, "baseColorFactor", [1.0, 1.0, 1.0, 1.0]);
+    let em = get_floats(m, "emissiveFactor", [0.0, 0.0, 0.0]);
+
+    PbrMaterial {
+        name                : get_name(m),
+        base_color          : Vec4f::new(bc[0], bc[1], bc[2], bc[3]),
+        base_color_texture  : texture_ref(pbr, "baseColorTexture", ""),
+        metallic            : get_f32(pbr, "metallicFactor", 1.0),
+        roughness           : get_f32(pbr, "roughnessFactor", 1.0),
+        metallic_roughness_texture : texture_ref(pbr, "metallicRoughnessTexture", ""),
+        normal_texture      : texture_ref(m, "normalTexture", "scale"),
+        occlusion_texture   : texture_ref(m, "occlusionTexture", "strength"),
+        emissive            : Vec3f::new(em[0], em[1], em[2]),
+        emissive_texture    : texture_ref(m, "emissiveTexture", ""),
+        alpha_mode          :
+            match m.get("alphaMode").and_then(|a| a.as_str()) {
+                Some("MASK") => AlphaMode::Mask,
+                Some("BLEND") => AlphaMode::Blend,
+                _ => AlphaMode::Opaque
+            },
+        alpha_cutoff        : get_f32(m, "alphaCutoff", 0.5),
+        double_sided        : m.get("doubleSided").and_then(|d| d.as_bool()).unwrap_or(false),
+    }
+}
+
+fn gl_filter(v: Option<usize>) -> Filter {
+    match v {
+        Some(9728) => Filter::Nearest,
+        Some(9984) => Filter::NearestMipmapNearest,
+        Some(9985) => Filter::LinearMipmapNearest,
+        Some(9986) => Filter::NearestMipmapLinear,
+        Some(9987) => Filter::LinearMipmapLinear,
+        _ => Filter::Linear
+    }
+}
+
+fn gl_wrap(v: Option<usize>) -> Wrap {
+    match v {
+        Some(33071) => Wrap::ClampToEdge,
+        Some(33648) => Wrap::MirroredRepeat,
+        _ => Wrap::Repeat
+    }
+}
+
+fn parse_texture(doc: &Value, t: &Value) -> Texture {
+    let s = t.get("sampler").and_then(|s| s.as_usize())
+        .and_then(|s| doc.get("samplers").and_then(|a| a.as_array()).and_then(|a| a.get(s)));
+    let field = |k: &str| s.and_then(|s| s.get(k)).and_then(|f| f.as_usize());
+
+    Texture {
+        image   : t.get("source").and_then(|s| s.as_usize()),
+        sampler : Sampler {
+            mag_filter  : gl_filter(field("magFilter")),
+            min_filter  : gl_filter(field("minFilter")),
+            wrap_s      : gl_wrap(field("wrapS")),
+            wrap_t      : gl_wrap(field("wrapT")),
+        },
+    }
+}
+
+fn parse_image(doc: &Value, buffers: &[Vec<u8>], dir: &str, i: &Value) -> Result<Image, String> {
+    match i.get("uri").and_then(|u| u.as_str()) {
+        Some(uri) => {
+            match decode_data_uri(uri) {
+                Some(r) => r.map(|(mime, data)| Image::Embedded { mime: mime, data: data }),
+                None => Ok(Image::File(join_path(dir, uri)))
+            }
+        },
+        None => {
+            let view = match i.get("bufferView").and_then(|v| v.as_usize()) { Some(v) => v, None => return Err(String::from("image without data")) };
+            let data = view_bytes(doc, buffers, view)?;
+            Ok(Image::Embedded { mime: String::from(i.get("mimeType").and_then(|m| m.as_str()).unwrap_or("")), data: data })
+        }
+    }
+}
+
+fn triangles(mode: usize, indices: &[u32]) -> Result<Vec<[u32; 3]>, String> {
+    let mut tris = Vec::new();
+    match mode {
+        4 => {
+            for t in 0..indices.len() / 3 {
+                tris.push([indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]]);
+            }
+        },
+        5 => {
+            for i in 2..indices.len().max(2) {
+                // keep the winding of every other triangle in the strip
+                if i % 2 == 0 {
+                    tris.push([indices[i - 2], indices[i - 1], indices[i]]);
+                } else {
+                    tris.push([indices[i - 1], indices[i - 2], indices[i]]);
+                }
+            }
+        },
+        6 => {
+            for i in 2..indices.len().max(2) {
+                tris.push([indices[0], indices[i - 1], indices[i]]);
+            }
+        },
+        _ => return Err(format!("unsupported primitive mode {}", mode))
+    }
+    Ok(tris)
+}
+
+fn parse_primitive(doc: &Value, buffers: &[Vec<u8>], p: &Value) -> Result<Primitive, String> {
+    let attrs = match p.get("attributes") { Some(a) => a, None => return Err(String::from("primitive without attributes")) };
+    let attr = |name: &str| -> Result<Option<Accessor>, String> {
+        match attrs.get(name).and_then(|a| a.as_usize()) {
+            Some(a) => read_accessor(doc, buffers, a).map(|a| Some(a)),
+            None => Ok(None)
+        }
+    };
+
+    let pos = match attr("POSITION")? { Some(p) => p, None => return Err(String::from("primitive without positions")) };
+    let vcount = pos.count;
+    let comp = |a: &Accessor, i: usize, c: usize, default: f64| if c < a.comps { a.values[i * a.comps + c] as f32 } else { default as f32 };
+
+    let mut verts = Vec::with_capacity(vcount);
+    for i in 0..vcount {
+        verts.push(Vec3f::new(comp(&pos, i, 0, 0.0), comp(&pos, i, 1, 0.0), comp(&pos, i, 2, 0.0)));
+    }
+
+    let mut normals = Vec::new();
+    let mut uvws = Vec::new();
+    let mut colors = Vec::new();
+    let mut tangents = Vec::new();
+    let mut uvs1 = Vec::new();
+    let mut joints = Vec::new();
+    let mut weights = Vec::new();
+
+    if let Some(a) = attr("NORMAL")? {
+        for i in 0..a.count.min(vcount) { normals.push(Vec3f::new(comp(&a, i, 0, 0.0), comp(&a, i, 1, 0.0), comp(&a, i, 2, 1.0))); }
+    }
+    if let Some(a) = attr("TEXCOORD_0")? {
+        for i in 0..a.count.min(vcount) { uvws.push(Vec3f::new(comp(&a, i, 0, 0.0), comp(&a, i, 1, 0.0), 0.0)); }
+    }
+    if let Some(a) = attr("TEXCOORD_1")? {
+        for i in 0..a.count.min(vcount) { uvs1.push(Vec2f::new(comp(&a, i, 0, 0.0), comp(&a, i, 1, 0.0))); }
+    }
+    if let Some(a) = attr("COLOR_0")? {
+        for i in 0..a.count.min(vcount) { colors.push(Vec4f::new(comp(&a, i, 0, 0.0), comp(&a, i, 1, 0.0), comp(&a, i, 2, 0.0), comp(&a, i, 3, 1.0))); }
+    }
+    if let Some(a) = attr("TANGENT")? {
+        for i in 0..a.count.min(vcount) { tangents.push(Vec4f::new(comp(&a, i, 0, 1.0), comp(&a, i, 1, 0.0), comp(&a, i, 2, 0.0), comp(&a, i, 3, 1.0))); }
+    }
+    if let Some(a) = attr("JOINTS_0")? {
+        for i in 0..a.count.min(vcount) { joints.push([comp(&a, i, 0, 0.0) as u16, comp(&a, i, 1, 0.0) as u16, comp(&a, i, 2, 0.0) as u16, comp(&a, i, 3, 0.0) as u16]); }
+    }
+    if let Some(a) = attr("WEIGHTS_0")? {
+        for i in 0..a.count.min(vcount) { weights.push(Vec4f::new(comp(&a, i, 0, 0.0), comp(&a, i, 1, 0.0), comp(&a, i, 2, 0.0), comp(&a, i, 3, 0.0))); }
+    }
+
+    let mut indices = Vec::new();
+    match p.get("indices").and_then(|i| i.as_usize()) {
+        Some(i) => {
+            let a = read_accessor(doc, buffers, i)?;
+            for v in a.values.iter() {
+                indices.push(*v as u32);
+            }
+        },
+        None => {
+            for i in 0..vcount {
+                indices.push(i as u32);
+            }
+        }
+    }
+
+    let mode = p.get("mode").and_then(|m| m.as_usize()).unwrap_or(4);
+    let has_uv = uvws.len() == vcount;
+    let has_nrm = normals.len() == vcount;
+    let attr_index = |v: u32, present: bool| if present { v } else { NO_INDEX };
+
+    let mut tris = Vec::new();
+    for t in triangles(mode, indices.as_slice())?.iter() {
+        tris.push(IdTri::new(*t, [attr_index(t[0], has_uv), attr_index(t[1], has_uv), attr_index(t[2], has_uv)],
+                             [attr_index(t[0], has_nrm), attr_index(t[1], has_nrm), attr_index(t[2], has_nrm)]));
+    }
+
+    Ok(Primitive {
+        mesh        : Mesh::from(verts, uvws, tris, Vec::new()).with_normals(normals).with_colors(colors),
+        tangents    : tangents,
+        uvs1        : uvs1,
+        joints      : joints,
+        weights     : weights,
+        material    : p.get("material").and_then(|m| m.as_usize()),
+    })
+}
+
+fn node_transform(n: &Value) -> Mat4f {
+    match n.get("matrix").and_then(|m| m.as_array()) {
+        Some(m) if m.len() == 16 => {
+            let f = |i: usize| m[i].as_f64().unwrap_or(0.0) as f32;
+            Mat4f::new(f(0), f(1), f(2), f(3), f(4), f(5), f(6), f(7), f(8), f(9), f(10), f(11), f(12), f(13), f(14), f(15))
+        },
+        _ => {
+            let t = get_floats(n, "translation", [0.0, 0.0, 0.0]);
+            let r = get_floats(n, "rotation", [0.0, 0.0, 0.0, 1.0]);
+            let s = get_floats(n, "scale", [1.0, 1.0, 1.0]);
+            rs_math3d::translate(Vec3f::new(t[0], t[1], t[2]))
+                * Quatf::new(r[0], r[1], r[2], r[3]).mat4()
+                * rs_math3d::scale(Vec3f::new(s[0], s[1], s[2]))
+        }
+    }
+}
+
+fn parse_scene(doc: &Value, dir: &str, bin: Option<&[u8]>) -> Result<Scene, String> {
+    let empty : [Value; 0] = [];
+    let list = |key: &str| doc.get(key).and_then(|a| a.as_array()).unwrap_or(&empty);
+
+    let mut buffers = Vec::new();
+    for b in list("buffers").iter() {
+        buffers.push(load_buffer(b.get("uri").and_then(|u| u.as_str()), dir, bin)?);
+    }
+
+    let mut meshes = Vec::new();
+    for m in list("meshes").iter() {
+        let mut primitives = Vec::new();
+        for p in m.get("primitives").and_then(|p| p.as_array()).unwrap_or(&empty).iter() {
+            // points and lines have no faces for Mesh to hold
+            let mode = p.get("mode").and_then(|m| m.as_usize()).unwrap_or(4);
+            if mode < 4 {
+                println!("warning   : mesh '{}': primitive mode {} is not triangles, skipped", get_name(m), mode);
+                continue
+            }
+            primitives.push(parse_primitive(doc, buffers.as_slice(), p)?);
+        }
+        meshes.push(GltfMesh { name: get_name(m), primitives: primitives });
+    }
+
+    let materials = list("materials").iter().map(|m| parse_material(m)).collect();
+    let textures = list("textures").iter().map(|t| parse_texture(doc, t)).collect();
+
+    let mut images = Vec::new();
+    for i in list("images").iter() {
+        images.push(parse_image(doc, buffers.as_slice(), dir, i)?);
+    }
+
+    let mut nodes = Vec::<Node>::new();
+    for n in list("nodes").iter() {
+        let mut children = Vec::new();
+        for c in n.get("children").and_then(|c| c.as_array()).unwrap_or(&empty).iter() {
+            match c.as_usize() {
+                Some(c) if c < list("nodes").len() => children.push(c),
+                _ => return Err(String::from("invalid child node"))
+            }
+        }
+        nodes.push(Node { name: get_name(n), mesh: n.get("mesh").and_then(|m| m.as_usize()), children: children, parent: None, local: node_transform(n) });
+    }
+
+    for i in 0..nodes.len() {
+        for j in 0..nodes[i].children.len() {
+            let c = nodes[i].children[j];
+            if nodes[c].parent.is_some() || c == i {
+                return Err(String::from("node hierarchy is not a tree"))
+            }
+            nodes[c].parent = Some(i);
+        }
+    }
+
+    // with one parent per node, a node no root leads to sits on a cycle
+    let mut reached = Vec::with_capacity(nodes.len());
+    let mut stack = Vec::new();
+    for (i, n) in nodes.iter().enumerate() {
+        reached.push(n.parent.is_none());
+        if n.parent.is_none() {
+            stack.push(i);
+        }
+    }
+    while let Some(i) = stack.pop() {
+        for c in nodes[i].children.iter() {
+            reached[*c] = true;
+            stack.push(*c);
+        }
+    }
+    if reached.iter().any(|r| !*r) {
+        return Err(String::from("node hierarchy has a cycle"))
+    }
+
+    // roots of the default scene, or every parentless node when there is none
+    let mut roots = Vec::new();
+    let scene = doc.get("scene").and_then(|s| s.as_usize()).unwrap_or(0);
+    match list("scenes").get(scene).and_then(|s| s.get("nodes")).and_then(|n| n.as_array()) {
+        Some(r) => {
+            for n in r.iter() {
+                match n.as_usize() {
+                    Some(n) if n < nodes.len() => roots.push(n),
+                    _ => return Err(String::from("invalid scene node"))
+                }
+            }
+        },
+        None => {
+            for (i, n) in nodes.iter().enumerate() {
+                if n.parent.is_none() {
+                    roots.push(i);
+                }
+            }
+        }
+    }
+
+    Ok(Scene { meshes: meshes, materials: materials, textures: textures, images: images, nodes: nodes, roots: roots })
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+impl Scene {
+    /// Loads a `.glb` or a `.gltf` with its external or embedded buffers
+    pub fn read_gltf(path: &str) -> Result<Scene, String> {
+        let data = read_bytes(path)?;
+        let dir = match path.rfind('/') { Some(p) => &path[..p + 1], None => "" };
+        Self::parse_gltf(data.as_slice(), dir)
+    }
+
+    /// `dir` is prepended to relative uris and must end with a separator
+    pub fn parse_gltf(data: &[u8], dir: &str) -> Result<Scene, String> {
+        if data.len() < 12 || &data[0..4] != b"glTF" {
+            let doc = json::parse(data)?;
+            return parse_scene(&doc, dir, None)
+        }
+
+        if u32_at(data, 4) != 2 {
+            return Err(String::from("unsupported glb version"))
+        }
+
+        // a JSON chunk followed by an optional BIN chunk
+        let len = (u32_at(data, 8) as usize).min(data.len());
+        let mut pos = 12;
+        let mut doc = None;
+        let mut bin = None;
+        while pos + 8 <= len {
+            let clen = u32_at(data, pos) as usize;
+            let ctype = u32_at(data, pos + 4);
+            if pos + 8 + clen > len {
+                return Err(String::from("truncated glb chunk"))
+            }
+
+            let chunk = &data[pos + 8..pos + 8 + clen];
+            match ctype {
+                0x4E4F534A => doc = Some(json::parse(chunk)?),
+                0x004E4942 => bin = Some(chunk),
+                _ => ()
+            }
+            pos += 8 + clen;
+        }
+
+        match doc {
+            Some(d) => parse_scene(&d, dir, bin),
+            None => Err(String::from("glb without json chunk"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one triangle, positions in an embedded 36 byte buffer
+    fn triangle(accessor: &str) -> String {
+        format!("{{ \"asset\": {{ \"version\": \"2.0\" }},
+            \"buffers\": [ {{ \"byteLength\": 36, \"uri\": \"data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA\" }} ],
+            \"bufferViews\": [ {{ \"buffer\": 0, \"byteLength\": 36 }} ],
+            \"accessors\": [ {} ],
+            \"meshes\": [ {{ \"primitives\": [ {{ \"attributes\": {{ \"POSITION\": 0 }} }} ] }} ] }}", accessor)
+    }
+
+    #[test]
+    fn accessor() {
+        let doc = triangle("{ \"bufferView\": 0, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\" }");
+        let s = Scene::parse_gltf(doc.as_bytes(), "").ok().unwrap();
+        assert_eq!(s.meshes()[0].primitives[0].mesh.verts().len(), 3);
+    }
+
+    #[test]
+    fn bogus_accessor_count() {
+        for count in ["4", "18446744073709551615", "1e30"].iter() {
+            let doc = triangle(format!("{{ \"bufferView\": 0, \"componentType\": 5126, \"count\": {}, \"type\": \"VEC3\" }}", count).as_str());
+            assert!(Scene::parse_gltf(doc.as_bytes(), "").is_err());
+        }
+
+        let doc = triangle("{ \"componentType\": 5126, \"count\": 1e18, \"type\": \"VEC3\" }");
+        assert!(Scene::parse_gltf(doc.as_bytes(), "").is_err());
+    }
+
+    #[test]
+    fn node_cycle() {
+        let doc = "{ \"nodes\": [ { \"children\": [1] }, { \"children\": [2] }, { \"children\": [1] } ] }";
+        assert!(Scene::parse_gltf(doc.as_bytes(), "").is_err());
+        let doc = "{ \"nodes\": [ { \"children\": [1] }, { \"children\": [0] } ] }";
+        assert!(Scene::parse_gltf(doc.as_bytes(), "").is_err());
+    }
+
+    #[test]
+    fn flatten() {
+        let doc = "{ \"asset\": { \"version\": \"2.0\" },
+            \"buffers\": [ { \"byteLength\": 36, \"uri\": \"data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA\" } ],
+            \"bufferViews\": [ { \"buffer\": 0, \"byteLength\": 36 } ],
+            \"accessors\": [ { \"bufferView\": 0, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\" } ],
+            \"meshes\": [ { \"primitives\": [ { \"attributes\": { \"POSITION\": 0 } } ] } ],
+            \"nodes\": [ { \"mesh\": 0, \"children\": [1] }, { \"mesh\": 0, \"translation\": [0, 0, 5] }, { \"mesh\": 0 } ],
+            \"scenes\": [ { \"nodes\": [0] } ] }";
+        let m = Scene::parse_gltf(doc.as_bytes(), "").ok().unwrap().flatten();
+        assert_eq!(m.verts().len(), 6);
+        assert_eq!(m.tris().len(), 2);
+        assert_eq!(m.verts().iter().filter(|v| v.z == 5.0).count(), 3);
+    }
+
+    #[test]
+    fn world_transform() {
+        let doc = "{ \"nodes\": [ { \"children\": [1], \"translation\": [1, 0, 0] },
+                                  { \"children\": [2], \"scale\": [2, 2, 2] },
+                                  { \"translation\": [0, 1, 0] } ] }";
+        let s = Scene::parse_gltf(doc.as_bytes(), "").ok().unwrap();
+        assert_eq!(s.roots(), &[0]);
+        let t = s.world_transform(2).col[3];
+        assert_eq!((t.x, t.y, t.z), (1.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn lines_skipped() {
+        let doc = "{ \"buffers\": [ { \"byteLength\": 36, \"uri\": \"data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA\" } ],
+            \"bufferViews\": [ { \"buffer\": 0, \"byteLength\": 36 } ],
+            \"accessors\": [ { \"bufferView\": 0, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\" } ],
+            \"meshes\": [ { \"primitives\": [ { \"attributes\": { \"POSITION\": 0 }, \"mode\": 1 }, { \"attributes\": { \"POSITION\": 0 } } ] } ] }";
+        let s = Scene::parse_gltf(doc.as_bytes(), "").unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(s.meshes()[0].primitives.len(), 1);
+        assert_eq!(s.meshes()[0].primitives[0].mesh.tris().len(), 1);
+    }
+
+    #[test]
+    fn glb_image() {
+        let json = b"{ \"buffers\": [ { \"byteLength\": 8 } ], \"bufferViews\": [ { \"buffer\": 0, \"byteOffset\": 3, \"byteLength\": 5 } ],
+                     \"images\": [ { \"bufferView\": 0, \"mimeType\": \"image/png\" } ] }";
+        let bin = [9u8, 9, 9, 0x89, b'P', b'N', b'G', 255];
+        let mut glb = Vec::new();
+        glb.append(b"glTF");
+        glb.append(&2u32.to_le_bytes());
+        glb.append(&(12 + 8 + json.len() as u32 + 8 + 8).to_le_bytes());
+        glb.append(&(json.len() as u32).to_le_bytes());
+        glb.append(&0x4E4F534Au32.to_le_bytes());
+        glb.append(json);
+        glb.append(&8u32.to_le_bytes());
+        glb.append(&0x004E4942u32.to_le_bytes());
+        glb.append(&bin);
+
+        let s = Scene::parse_gltf(glb.as_slice(), "").unwrap_or_else(|e| panic!("{}", e.as_str()));
+        match &s.images()[0] {
+            Image::Embedded { mime, data } => {
+                assert_eq!(mime.as_str(), "image/png");
+                assert_eq!(data.as_slice(), &bin[3..]);
+            },
+            Image::File(_) => panic!("embedded image read as a file")
+        }
+    }
+}
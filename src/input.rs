@@ -0,0 +1,329 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_ctypes::*;
+use rs_glfw3::bindings::*;
+use rs_alloc::*;
+use rs_math3d::*;
+
+// carries everything GLFW reports, the viewer reads neither scancodes, modifiers nor drops
+#[allow(dead_code)]
+pub enum Event {
+    /// GLFW key, scancode, action (`GLFW_PRESS`, `GLFW_RELEASE`, `GLFW_REPEAT`) and modifiers
+    Key { key: i32, scancode: i32, action: i32, mods: i32 },
+    /// unicode text input, already composed by the platform
+    Char(char),
+    MouseButton { button: i32, action: i32, mods: i32 },
+    /// cursor position in screen coordinates, origin at the top left of the client area
+    CursorPos(f64, f64),
+    CursorEnter(bool),
+    Scroll(f64, f64),
+    /// paths of the files dropped on the window
+    Drop(Vec<String>),
+    Focus(bool),
+}
+
+const KEY_COUNT     : usize = GLFW_KEY_LAST as usize + 1;
+const BUTTON_COUNT  : usize = GLFW_MOUSE_BUTTON_LAST as usize + 1;
+
+const DOWN      : u8 = 1;
+const PRESSED   : u8 = 2;
+const RELEASED  : u8 = 4;
+
+/// Input state of the current frame and the events that produced it. Events are queued by the
+/// GLFW callbacks while polling and applied once the poll returns, `pressed` and `released` hold for the frame the change happened
+/// in, so a key tapped between two frames reads as both pressed and released but never as down.
+pub struct Input {
+    keys        : [u8; KEY_COUNT],
+    buttons     : [u8; BUTTON_COUNT],
+    mods        : i32,
+    cursor      : Vec2f,
+    frame_cursor: Vec2f,
+    has_cursor  : bool,
+    scroll      : Vec2f,
+    text        : String,
+    focused     : bool,
+    hovered     : bool,
+    events      : Vec<Event>,
+}
+
+fn set(state: &mut u8, action: c_int) {
+    if action == GLFW_PRESS as c_int {
+        *state |= DOWN | PRESSED;
+    } else if action == GLFW_RELEASE as c_int && *state & DOWN != 0 {
+        *state = (*state & !DOWN) | RELEASED;
+    }
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            keys        : [0; KEY_COUNT],
+            buttons     : [0; BUTTON_COUNT],
+            mods        : 0,
+            cursor      : Vec2f::new(0.0, 0.0),
+            frame_cursor: Vec2f::new(0.0, 0.0),
+            has_cursor  : false,
+            scroll      : Vec2f::new(0.0, 0.0),
+            text        : String::new(),
+            focused     : true,
+            hovered     : false,
+            events      : Vec::new(),
+        }
+    }
+
+    /// events received since the last frame, in arrival order
+    // the viewer gets its events through App::event
+    #[allow(dead_code)]
+    pub fn events(&self) -> &[Event] { self.events.as_slice() }
+
+    pub fn is_down(&self, key: i32) -> bool { Self::flag(&self.keys, key, DOWN) }
+    pub fn was_pressed(&self, key: i32) -> bool { Self::flag(&self.keys, key, PRESSED) }
+    // the viewer's bindings only act on presses and held keys
+    #[allow(dead_code)]
+    pub fn was_released(&self, key: i32) -> bool { Self::flag(&self.keys, key, RELEASED) }
+    /// modifier bits (`GLFW_MOD_SHIFT`, ...) of the last key event
+    #[allow(dead_code)]
+    pub fn mods(&self) -> i32 { self.mods }
+
+    pub fn is_button_down(&self, button: i32) -> bool { Self::flag(&self.buttons, button, DOWN) }
+    pub fn was_button_pressed(&self, button: i32) -> bool { Self::flag(&self.buttons, button, PRESSED) }
+    #[allow(dead_code)]
+    pub fn was_button_released(&self, button: i32) -> bool { Self::flag(&self.buttons, button, RELEASED) }
+
+    pub fn cursor(&self) -> Vec2f { self.cursor }
+    /// cursor movement during the frame, in screen coordinates
+    pub fn cursor_delta(&self) -> Vec2f { self.cursor - self.frame_cursor }
+    /// scroll offset accumulated during the frame, y is the usual mouse wheel
+    pub fn scroll(&self) -> Vec2f { self.scroll }
+    /// text typed during the frame
+    // the viewer has no text fields and keeps running out of focus
+    #[allow(dead_code)]
+    pub fn text(&self) -> &str { self.text.as_str() }
+    #[allow(dead_code)]
+    pub fn is_focused(&self) -> bool { self.focused }
+    #[allow(dead_code)]
+    pub fn is_hovered(&self) -> bool { self.hovered }
+
+    fn flag(states: &[u8], i: i32, f: u8) -> bool {
+        i >= 0 && (i as usize) < states.len() && states[i as usize] & f != 0
+    }
+
+    /// forgets the per frame state and applies the events polled for the new frame
+    pub fn begin_frame(&mut self, events: Vec<Event>) {
+        for k in self.keys.iter_mut() {
+            *k &= DOWN;
+        }
+        for b in self.buttons.iter_mut() {
+            *b &= DOWN;
+        }
+        self.frame_cursor = self.cursor;
+        self.scroll = Vec2f::new(0.0, 0.0);
+        self.text = String::new();
+        for e in events.iter() {
+            self.apply(e);
+        }
+        self.events = events;
+    }
+
+    /// hands the queued events over, the state they produced stays
+    pub fn take_events(&mut self) -> Vec<Event> {
+        core::mem::replace(&mut self.events, Vec::new())
+    }
+
+    pub fn restore_events(&mut self, events: Vec<Event>) {
+        self.events = events;
+    }
+
+    fn apply(&mut self, e: &Event) {
+        match e {
+            Event::Key { key, action, mods, .. } => {
+                if *key >= 0 && (*key as usize) < KEY_COUNT {
+                    set(&mut self.keys[*key as usize], *action as c_int);
+                }
+                self.mods = *mods;
+            },
+            Event::MouseButton { button, action, .. } => {
+                if *button >= 0 && (*button as usize) < BUTTON_COUNT {
+                    set(&mut self.buttons[*button as usize], *action as c_int);
+                }
+            },
+            Event::Char(c) => {
+                let mut buf = [0u8; 4];
+                self.text.push_str(c.encode_utf8(&mut buf));
+            },
+            Event::CursorPos(x, y) => {
+                self.cursor = Vec2f::new(*x as f32, *y as f32);
+                // no delta for the first position we hear of
+                if !self.has_cursor {
+                    self.frame_cursor = self.cursor;
+                    self.has_cursor = true;
+                }
+            },
+            Event::CursorEnter(e) => self.hovered = *e,
+            Event::Scroll(x, y) => self.scroll = self.scroll + Vec2f::new(*x as f32, *y as f32),
+            Event::Drop(_) => (),
+            Event::Focus(f) => {
+                self.focused = *f;
+                // the releases happen in another window, don't leave keys stuck down
+                if !*f {
+                    for s in self.keys.iter_mut().chain(self.buttons.iter_mut()) {
+                        if *s & DOWN != 0 {
+                            *s = (*s & !DOWN) | RELEASED;
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// GLFW callbacks
+////////////////////////////////////////////////////////////////////////////////
+
+/// Events the callbacks queue during `glfwPollEvents`. It has its own allocation and is only
+/// reached through the window user pointer, nothing else borrows it while polling.
+pub struct EventQueue {
+    events  : Vec<Event>,
+}
+
+unsafe fn push(win: *mut GLFWwindow, e: Event) {
+    let queue = glfwGetWindowUserPointer(win) as *mut EventQueue;
+    if !queue.is_null() {
+        (*queue).events.push(e);
+    }
+}
+
+unsafe extern "C" fn key_callback(win: *mut GLFWwindow, key: c_int, scancode: c_int, action: c_int, mods: c_int) {
+    push(win, Event::Key { key: key as i32, scancode: scancode as i32, action: action as i32, mods: mods as i32 });
+}
+
+unsafe extern "C" fn char_callback(win: *mut GLFWwindow, codepoint: c_uint) {
+    if let Some(c) = core::char::from_u32(codepoint as u32) {
+        push(win, Event::Char(c));
+    }
+}
+
+unsafe extern "C" fn mouse_button_callback(win: *mut GLFWwindow, button: c_int, action: c_int, mods: c_int) {
+    push(win, Event::MouseButton { button: button as i32, action: action as i32, mods: mods as i32 });
+}
+
+unsafe extern "C" fn cursor_pos_callback(win: *mut GLFWwindow, x: f64, y: f64) {
+    push(win, Event::CursorPos(x, y));
+}
+
+unsafe extern "C" fn cursor_enter_callback(win: *mut GLFWwindow, entered: c_int) {
+    push(win, Event::CursorEnter(entered != 0));
+}
+
+unsafe extern "C" fn scroll_callback(win: *mut GLFWwindow, x: f64, y: f64) {
+    push(win, Event::Scroll(x, y));
+}
+
+unsafe extern "C" fn drop_callback(win: *mut GLFWwindow, count: c_int, paths: *mut *const c_char) {
+    let mut v = Vec::with_capacity(count as usize);
+    for i in 0..count as usize {
+        let p = *paths.add(i) as *const u8;
+        let bytes = core::slice::from_raw_parts(p, libc::strlen(p as *const libc::c_char));
+        match core::str::from_utf8(bytes) {
+            Ok(s) => v.push(String::from(s)),
+            Err(_) => (),
+        }
+    }
+    push(win, Event::Drop(v));
+}
+
+unsafe extern "C" fn focus_callback(win: *mut GLFWwindow, focused: c_int) {
+    push(win, Event::Focus(focused != 0));
+}
+
+/// Routes the window's input callbacks to a new queue in its user pointer. The queue is freed
+/// with `free_queue` once the window is gone.
+pub fn install_callbacks(win: *mut GLFWwindow) -> *mut EventQueue {
+    let queue = Box::into_raw(Box::new(EventQueue { events: Vec::new() }));
+    unsafe {
+        glfwSetWindowUserPointer(win, queue as *mut c_void);
+        glfwSetKeyCallback(win, Some(key_callback));
+        glfwSetCharCallback(win, Some(char_callback));
+        glfwSetMouseButtonCallback(win, Some(mouse_button_callback));
+        glfwSetCursorPosCallback(win, Some(cursor_pos_callback));
+        glfwSetCursorEnterCallback(win, Some(cursor_enter_callback));
+        glfwSetScrollCallback(win, Some(scroll_callback));
+        glfwSetDropCallback(win, Some(drop_callback));
+        glfwSetWindowFocusCallback(win, Some(focus_callback));
+    }
+    queue
+}
+
+/// Processes the pending window events and returns what the callbacks queued
+pub unsafe fn poll_events(queue: *mut EventQueue) -> Vec<Event> {
+    glfwPollEvents();
+    core::mem::replace(&mut (*queue).events, Vec::new())
+}
+
+pub unsafe fn free_queue(queue: *mut EventQueue) {
+    drop(Box::from_raw(queue));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // key events for (key, action) pairs
+    fn keys(list: &[(u32, u32)]) -> Vec<Event> {
+        list.iter().map(|(k, a)| Event::Key { key: *k as i32, scancode: 0, action: *a as i32, mods: 0 }).collect()
+    }
+
+    #[test]
+    fn tap_between_frames() {
+        let a = GLFW_KEY_A as i32;
+        let mut input = Input::new();
+        input.begin_frame(keys(&[(GLFW_KEY_A, GLFW_PRESS), (GLFW_KEY_A, GLFW_RELEASE)]));
+        assert!(input.was_pressed(a) && input.was_released(a) && !input.is_down(a));
+        assert_eq!(input.events().len(), 2);
+
+        // pressed and released only hold for the frame they happened in
+        input.begin_frame(keys(&[(GLFW_KEY_A, GLFW_PRESS)]));
+        assert!(input.was_pressed(a) && !input.was_released(a) && input.is_down(a));
+        input.begin_frame(Vec::new());
+        assert!(!input.was_pressed(a) && input.is_down(a));
+        assert_eq!(input.events().len(), 0);
+    }
+
+    #[test]
+    fn focus_loss_releases() {
+        let a = GLFW_KEY_A as i32;
+        let mut input = Input::new();
+        input.begin_frame(keys(&[(GLFW_KEY_A, GLFW_PRESS)]));
+        let mut events = Vec::new();
+        events.push(Event::Focus(false));
+        input.begin_frame(events);
+        assert!(!input.is_down(a) && input.was_released(a) && !input.is_focused());
+    }
+}
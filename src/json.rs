@@ -0,0 +1,343 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use core::result::Result;
+use rs_alloc::*;
+
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k.as_str() == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(n) if *n >= 0.0 => Some(*n as usize),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s.as_str()),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            // an empty rs_alloc Vec's slice has a null pointer, which would read back as None
+            Value::Array(a) if a.len() == 0 => Some(&[]),
+            Value::Array(a) => Some(a.as_slice()),
+            _ => None
+        }
+    }
+}
+
+// deeper documents are refused rather than overflowing the stack, glTF needs a handful
+const MAX_DEPTH : usize = 128;
+
+struct Parser<'a> {
+    data    : &'a [u8],
+    pos     : usize,
+    depth   : usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> String {
+        format!("json: {} at byte {}", msg, self.pos)
+    }
+
+    fn skip_ws(&mut self) {
+        while self.pos < self.data.len() && (self.data[self.pos] == b' ' || self.data[self.pos] == b'\t' || self.data[self.pos] == b'\n' || self.data[self.pos] == b'\r') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        if self.pos < self.data.len() { Some(self.data[self.pos]) } else { None }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn literal(&mut self, lit: &str, v: Value) -> Result<Value, String> {
+        let end = self.pos + lit.len();
+        if end <= self.data.len() && &self.data[self.pos..end] == lit.as_bytes() {
+            self.pos = end;
+            Ok(v)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn nested(&mut self, array: bool) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nesting too deep"))
+        }
+
+        self.depth += 1;
+        let v = if array { self.array() } else { self.object() };
+        self.depth -= 1;
+        v
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        match self.peek() {
+            Some(b'{') => self.nested(false),
+            Some(b'[') => self.nested(true),
+            Some(b'"') => Ok(Value::Str(self.string()?)),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(_) => self.number(),
+            None => Err(self.error("unexpected end of input"))
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members))
+        }
+
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expecting a key"))
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            let v = self.value()?;
+            members.push((key, v));
+
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => { self.pos += 1; return Ok(Value::Object(members)) },
+                _ => return Err(self.error("expecting , or }"))
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(items))
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => { self.pos += 1; return Ok(Value::Array(items)) },
+                _ => return Err(self.error("expecting , or ]"))
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        if self.pos + 4 > self.data.len() {
+            return Err(self.error("truncated escape"))
+        }
+
+        let mut v = 0;
+        for _ in 0..4 {
+            let d =
+                match (self.data[self.pos] as char).to_digit(16) {
+                    Some(d) => d,
+                    None => return Err(self.error("invalid escape"))
+                };
+            v = v * 16 + d;
+            self.pos += 1;
+        }
+        Ok(v)
+    }
+
+    fn push_char(s: &mut String, c: char) {
+        let mut buf = [0u8; 4];
+        for b in c.encode_utf8(&mut buf).bytes() {
+            s.push(b);
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => { self.pos += 1; return Ok(s) },
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = match self.peek() { Some(c) => c, None => return Err(self.error("unterminated string")) };
+                    self.pos += 1;
+                    match c {
+                        b'"' | b'\\' | b'/' => s.push(c),
+                        b'b' => s.push(8),
+                        b'f' => s.push(12),
+                        b'n' => s.push(b'\n'),
+                        b'r' => s.push(b'\r'),
+                        b't' => s.push(b'\t'),
+                        b'u' => {
+                            let mut cp = self.hex4()?;
+                            // utf-16 surrogate pair
+                            if cp >= 0xD800 && cp < 0xDC00 && self.data[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let lo = self.hex4()?;
+                                cp = 0x10000 + ((cp - 0xD800) << 10) + (lo.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            Self::push_char(&mut s, core::char::from_u32(cp).unwrap_or('\u{FFFD}'));
+                        },
+                        _ => return Err(self.error("invalid escape"))
+                    }
+                },
+                Some(c) => { s.push(c); self.pos += 1 }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            match c {
+                b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' => self.pos += 1,
+                _ => break
+            }
+        }
+
+        let text = unsafe { core::str::from_utf8_unchecked(&self.data[start..self.pos]) };
+        match text.parse::<f64>() {
+            Ok(n) if start != self.pos => Ok(Value::Number(n)),
+            _ => Err(self.error("invalid number"))
+        }
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<Value, String> {
+    // strings are copied byte by byte, escapes aside, so the whole text has to be utf-8
+    if let Err(err) = core::str::from_utf8(data) {
+        return Err(format!("json: invalid utf-8 at byte {}", err.valid_up_to()))
+    }
+
+    let mut p = Parser { data: data, pos: 0, depth: 0 };
+    let v = p.value()?;
+    p.skip_ws();
+    if p.pos != data.len() {
+        return Err(p.error("trailing characters"))
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut v = Vec::new();
+        for _ in 0..depth {
+            v.push(b'[');
+        }
+        for _ in 0..depth {
+            v.push(b']');
+        }
+        v
+    }
+
+    #[test]
+    fn values() {
+        let v = parse(b"{ \"a\": [1, 2.5e1, -3], \"b\": \"x\\u00e9\", \"c\": null, \"d\": true }").ok().unwrap();
+        let a = v.get("a").and_then(|a| a.as_array()).unwrap();
+        assert_eq!(a.len(), 3);
+        assert_eq!(a[1].as_f64(), Some(25.0));
+        assert_eq!(v.get("b").and_then(|b| b.as_str()), Some("x\u{e9}"));
+        assert_eq!(v.get("d").and_then(|d| d.as_bool()), Some(true));
+    }
+
+    #[test]
+    fn depth() {
+        assert!(parse(nested(MAX_DEPTH).as_slice()).is_ok());
+        assert!(parse(nested(MAX_DEPTH + 1).as_slice()).is_err());
+        assert!(parse(nested(100000).as_slice()).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        match parse(b"{\"\xff\":1}") {
+            Ok(_) => panic!("parsed invalid utf-8"),
+            Err(err) => assert_eq!(err.as_str(), "json: invalid utf-8 at byte 2")
+        }
+        assert!(parse(b"[\"\xc3\"]").is_err());
+    }
+
+    #[test]
+    fn empty_array() {
+        let v = parse(b"{ \"a\": [] }").unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(v.get("a").and_then(|a| a.as_array()).map(|a| a.len()), Some(0));
+    }
+}
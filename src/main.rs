@@ -27,12 +27,12 @@
 // POSSIBILITY OF SUCH DAMAGE.
 //
 #![no_std]
-#![no_main]
+// tests run under libtest's main
+#![cfg_attr(not(test), no_main)]
 
 #[link(name="m")]
 extern "C" {}
 
-use rs_ctypes::*;
 use rs_glfw3::bindings::*;
 use rs_gles2::bindings::*;
 use rs_streams::*;
@@ -43,15 +43,45 @@ use rs_math3d::*;
 mod renderer;
 mod objloader;
 mod gles2_renderer;
+mod meshopt;
+mod tangents;
+mod plyloader;
+mod stlloader;
+mod json;
+mod gltfloader;
+mod objwriter;
+mod meshcache;
+mod meshstats;
+mod meshops;
+mod simplify;
+mod primitives;
+mod app;
+mod input;
+mod actions;
+mod camera;
+mod timing;
+mod window;
+mod args;
+mod screenshot;
+mod trace;
 
 use objloader::*;
 use renderer::*;
 use gles2_renderer::*;
+use app::*;
+use actions::*;
+use camera::*;
+use window::*;
+use args::*;
+use gltfloader::Scene;
+use simplify::LodTarget;
+use primitives::Primitive;
 
 #[cfg(not(test))]
 #[panic_handler]
-fn alt_std_panic(_info: &core::panic::PanicInfo) -> ! {
-    unsafe { libc::exit(1) }
+fn alt_std_panic(info: &core::panic::PanicInfo) -> ! {
+    trace::report_panic(info);
+    unsafe { libc::exit(trace::PANIC_EXIT_CODE) }
 }
 
 
@@ -75,22 +105,28 @@ void main() {
     gl_FragColor = vec4(vNormal.xyz, 1.0);
 }\0";
 
-#[cfg(target_arch = "wasm32")]
-type EmArgCallbackFunc = extern "C" fn(*mut c_void);
-
-
-#[cfg(target_arch = "wasm32")]
-extern "C" {
-    fn emscripten_set_main_loop_arg(func: EmArgCallbackFunc, arg: *mut c_void, fps: c_int, simulate_infinite_loop: c_int);
-}
-
 pub struct State {
     program : Option<Box<dyn Program>>,
 
     monkey_vb   : StaticVertexBuffer,
     monkey_ib   : StaticIndexBuffer,
+    monkey_bounds   : meshstats::Bounds,
+
+    camera      : Camera,
+    mode        : CameraMode,
+    orbit       : OrbitController,
+    fly         : FlyController,
+    pan_zoom    : PanZoomController,
 
-    angle   : f32,
+    title_second    : u64,
+    frames_rendered : u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CameraMode {
+    Orbit,
+    Fly,
+    PanZoom,
 }
 
 struct Uniforms {
@@ -106,102 +142,383 @@ impl UniformBlock for Uniforms {
     }
 }
 
-extern "C"
-fn main_loop(win_: *mut c_void) {
-    unsafe {
-        let win = win_ as *mut GLFWwindow;
-        let state = glfwGetWindowUserPointer(win) as *mut State;
-
-        let mut width = 0;
-        let mut height = 0;
-        glfwGetWindowSize(win, &mut width, &mut height);
-        glViewport(0, 0, width, height);
-        glScissor(0, 0, width, height);
-        glClearColor(0.0, 0.0, 0.0, 1.0);
-        glClear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT | GL_STENCIL_BUFFER_BIT);
-        glEnable(GL_DEPTH_TEST);
-        let proj = rs_math3d::perspective(3.141516/4.0, width as f32 / height as f32, 1.0, 100.0);
-        let view = rs_math3d::lookat(&Vec3f::new(0.0, 1.0, 5.0), &Vec3f::new(0.0, 0.0, 0.0), &Vec3f::new(0.0, 1.0, 0.0));
-        let model = Quatf::of_axis_angle(&Vec3f::new(0.0, 1.0, 0.0), (*state).angle);
-        (*state).angle += 0.01;
-        let u = Uniforms { pvm: proj * view * model.mat4() };
-
-        match &(*state).program {
-            Some(p) => {
-                glEnable(GL_CULL_FACE);
-                draw_indexed(p, &(*state).monkey_vb, &(*state).monkey_ib, &u);
-            },
-            None => ()
-        }
+struct Viewer {
+    options : Options,
+    state   : Option<State>,
+}
 
-        glfwSwapBuffers(win);
-        glfwPollEvents();
+fn extension(path: &str) -> String {
+    let name = &path[path.rfind('/').map(|p| p + 1).unwrap_or(0)..];
+    let mut ext = String::new();
+    if let Some(p) = name.rfind('.') {
+        for c in name[p + 1..].bytes() {
+            ext.push(c.to_ascii_lowercase());
+        }
     }
+    ext
 }
 
-#[cfg(target_arch = "wasm32")]
-fn run_main_loop(win: *mut GLFWwindow) {
-    unsafe { emscripten_set_main_loop_arg(main_loop, win as *mut c_void, 0, 1) };
+fn read_mesh(path: &str) -> Result<Mesh, String> {
+    match extension(path).as_str() {
+        "ply" => Mesh::read_ply(path),
+        "stl" => Mesh::read_stl(path),
+        "gltf" | "glb" => Ok(Scene::read_gltf(path)?.flatten()),
+        _     => Mesh::read_obj(path),
+    }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn run_main_loop(win: *mut GLFWwindow) {
-    unsafe {
-        while glfwWindowShouldClose(win) == GLFW_FALSE as c_int && glfwGetKey(win, GLFW_KEY_ESCAPE as c_int) == 0 {
-            main_loop(win as *mut c_void);
-        }
+// --export: converts the model without opening a window
+fn export_model(path: &str, lod: Option<LodTarget>, out: &str) -> Result<(), String> {
+    let m = read_mesh(path)?;
+    let lods = match lod { Some(t) => m.lod_chain(&[t]), None => Vec::new() };
+    let m = lods.as_slice().first().map(|l| l.mesh()).unwrap_or(&m);
+    match extension(out).as_str() {
+        "obj" => m.write_obj(out, 6),
+        "stl" => m.write_stl(out),
+        _     => Err(format!("{}: only obj and stl files can be exported", out))
     }
 }
 
-
-#[link(name="c")]
-#[no_mangle]
-pub extern "C"
-fn main(_argc: isize, _argv: *const *const u8) -> isize  {
-    unsafe {
-        if glfwInit() == GLFW_FALSE as c_int {
-            return 1;
+fn load_model(path: &str, lod: Option<LodTarget>) -> Result<GPUMesh, String> {
+    let build = || -> Result<GPUMesh, String> {
+        let m = read_mesh(path)?;
+        println!("verts     : {}\nuvws      : {}\nnormals   : {}\ntris      : {}\nquads     : {}", m.verts().len(), m.uvws().len(), m.normals().len(), m.tris().len(), m.quads().len());
+        let report = m.validate();
+        println!("warnings  : {}", report.warning_count());
+        if !report.is_valid() {
+            let mut err = format!("{}: {} errors", path, report.error_count());
+            for issue in report.issues().iter().filter(|i| i.is_error()).take(10) {
+                err.push_str(format!("\n  {}", issue).as_str());
+            }
+            if report.error_count() > 10 {
+                err.push_str("\n  ...");
+            }
+            return Err(err)
         }
 
-        glfwWindowHint(GLFW_CONTEXT_CREATION_API as c_int, GLFW_EGL_CONTEXT_API as c_int);
-        glfwWindowHint(GLFW_CLIENT_API as c_int, GLFW_OPENGL_ES_API as c_int);
-        glfwWindowHint(GLFW_CONTEXT_VERSION_MAJOR as c_int, 2);
-        glfwWindowHint(GLFW_CONTEXT_VERSION_MINOR as c_int, 0);
-        glfwWindowHint(GLFW_SAMPLES as c_int, 8);
-        glfwWindowHint(GLFW_ALPHA_BITS as c_int, 0);
+        let lods = match lod { Some(t) => m.lod_chain(&[t]), None => Vec::new() };
+        let source =
+            match lods.as_slice().first() {
+                Some(l) => {
+                    println!("lod       : {} triangles, error {}", l.triangle_count(), l.error());
+                    l.mesh()
+                },
+                None => &m
+            };
+
+        let mut m = GPUMesh::from(source)?;
+        let stats = m.optimize(Some(1.05))?;
+        println!("acmr      : {} -> {}", stats.acmr_before(), stats.acmr_after());
+        Ok(m)
+    };
+
+    // the cache sits next to the model, "suzane.obj" -> "suzane.mesh", or "suzane.lod0.5.mesh",
+    // "suzane.tris500.mesh" and "suzane.err0.01.mesh" for the other targets
+    let ext = extension(path);
+    let stem = if ext.len() > 0 { &path[..path.len() - ext.len() - 1] } else { path };
+    let cache =
+        match lod {
+            Some(LodTarget::Ratio(r))     => format!("{}.lod{}.mesh", stem, r),
+            Some(LodTarget::Triangles(n)) => format!("{}.tris{}.mesh", stem, n),
+            Some(LodTarget::Error(e))     => format!("{}.err{}.mesh", stem, e),
+            None                          => format!("{}.mesh", stem),
+        };
+    meshcache::load_cached(path, cache.as_str(), build)
+}
+
+// a shader source from a file, null terminated for load_program
+fn read_shader(path: &str) -> Result<String, String> {
+    let data = read_bytes(path).map_err(|err| format!("{}: {}", path, err))?;
+    let text = core::str::from_utf8(data.as_slice()).map_err(|_| format!("{}: not utf-8", path))?;
+    let mut src = String::from(text);
+    src.push(0);
+    Ok(src)
+}
 
-        let win = glfwCreateWindow(1024, 900,
-            "App\0".as_bytes().as_ptr() as *const u8 as *const i8,
-            core::ptr::null::<GLFWmonitor>() as *mut GLFWmonitor,
-            core::ptr::null::<GLFWwindow>() as *mut GLFWwindow);
-        glfwMakeContextCurrent(win);
+// used when bindings.cfg is missing
+fn default_bindings() -> ActionMap {
+    let mut map = ActionMap::new();
+    map.bind_action("quit", Button::Key(GLFW_KEY_ESCAPE as i32));
+    map.bind_action("orbit", Button::Mouse(GLFW_MOUSE_BUTTON_LEFT as i32));
+    map.bind_action("pan", Button::Mouse(GLFW_MOUSE_BUTTON_MIDDLE as i32));
+    map.bind_action("look", Button::Mouse(GLFW_MOUSE_BUTTON_RIGHT as i32));
+    map.bind_action("frame", Button::Key(GLFW_KEY_F as i32));
+    map.bind_action("fullscreen", Button::Key(GLFW_KEY_F11 as i32));
+    map.bind_axis("pointer_x", AxisBinding::new(AxisSource::MouseX));
+    map.bind_axis("pointer_y", AxisBinding::new(AxisSource::MouseY));
+    map.bind_axis("zoom", AxisBinding::new(AxisSource::ScrollY));
+    map
+}
 
+impl App for Viewer {
+    fn init(&mut self, ctx: &mut Context) -> Result<(), String> {
+        let bindings =
+            match ActionMap::load("bindings.cfg") {
+                Ok(map) => map,
+                Err(err) => {
+                    println!("bindings  : {}, using the defaults", err);
+                    default_bindings()
+                }
+            };
+        ctx.actions_mut().merge(&bindings);
 
         let attribs = [
             VertexAttributeDesc::new(String::from("aPosition"), VertexFormat::Float3, 0),
             VertexAttributeDesc::new(String::from("aNormal"), VertexFormat::Float3, 12),
             ];
         let uniforms = [ UniformDesc::new(String::from("uPVM"), UniformDataType::Float4x4, 0) ];
-        let program = GLProgram::load_program(&VERTEX_SHADER, &FRAGMENT_SHADER, &attribs, &uniforms);
+        let overridden = self.options.vertex_shader().is_some() || self.options.fragment_shader().is_some();
+        let vs = match self.options.vertex_shader() { Some(p) => read_shader(p)?, None => String::from(VERTEX_SHADER) };
+        let fs = match self.options.fragment_shader() { Some(p) => read_shader(p)?, None => String::from(FRAGMENT_SHADER) };
+        let program = GLProgram::load_program(vs.as_str(), fs.as_str(), &attribs, &uniforms);
+        if overridden && program.is_none() {
+            return Err(String::from("unable to build the shader program"))
+        }
 
+        // without a model argument and without suzane.obj, a sphere stands in
         let m =
-            match Mesh::read_obj("suzane.obj") {
-                Ok(m) => {
-                    println!("verts     : {}\nuvws      : {}\ntris      : {}\nquads     : {}", m.verts().len(), m.uvws().len(), m.tris().len(), m.quads().len());
-                    GPUMesh::from(&m)
-                },
-                _ => panic!("Error reading file")
+            if !self.options.has_model() && File::open(self.options.model()).is_err() {
+                println!("model     : {} not found, showing a sphere", self.options.model());
+                GPUMesh::primitive(&Primitive::Icosphere { radius: 1.0, subdivisions: 3 })?
+            } else {
+                println!("model     : {}", self.options.model());
+                load_model(self.options.model(), self.options.lod())?
             };
+        let stats = m.stats();
+        let b = stats.bounds().aabb();
+        println!("bounds    : ({}, {}, {}) - ({}, {}, {})", b.min.x, b.min.y, b.min.z, b.max.x, b.max.y, b.max.z);
+        println!("radius    : {}", stats.bounds().sphere().radius);
+        println!("area      : {}", stats.area());
+        match stats.volume() {
+            Some(v) => println!("volume    : {}", v),
+            None => println!("volume    : open mesh")
+        }
+        println!("triangles : {} in {} vertices", stats.triangle_count(), stats.vertex_count());
+        for s in stats.submeshes().iter() {
+            let size = s.bounds().size();
+            println!("submesh   : {}, {} triangles, size ({}, {}, {})", s.name(), s.triangle_count(), size.x, size.y, size.z);
+        }
 
         let monkey_vb = StaticVertexBuffer::new(m.verts());
-        let monkey_ib = StaticIndexBuffer::new(m.tris());
+        let monkey_ib =
+            match m.tris() {
+                GPUIndices::U16(i) => StaticIndexBuffer::new(i.as_slice()),
+                GPUIndices::U32(i) => StaticIndexBuffer::new(i.as_slice()),
+            };
 
-        let state = Box::new(State { program : program, monkey_vb: monkey_vb, monkey_ib: monkey_ib, angle: 0.0 });
-        glfwSetWindowUserPointer(win, state.as_ref() as *const State as *mut ::core::ffi::c_void);
-        run_main_loop(win);
+        let (width, height) = ctx.size();
+        let aspect = width as f32 / height.max(1) as f32;
+        let sphere = *stats.bounds().sphere();
+        let mut camera = Camera::perspective(3.141516 / 4.0, 0.1, 100.0);
+        let mut orbit = OrbitController::new(sphere.center, 1.0);
+        orbit.frame(&sphere, &camera, aspect);
+        orbit.snap();
+        orbit.update(0.0, &mut camera);
+        camera.clip_to(&sphere);
+
+        let fly = FlyController::new(camera.position(), sphere.radius);
+        let pan_zoom = PanZoomController::new(Vec2f::new(sphere.center.x, sphere.center.y), 2.0 * sphere.radius, 4.0 * sphere.radius);
+
+        self.state = Some(State { program : program, monkey_vb: monkey_vb, monkey_ib: monkey_ib, monkey_bounds: *stats.bounds(),
+                                  camera: camera, mode: CameraMode::Orbit, orbit: orbit, fly: fly, pan_zoom: pan_zoom,
+                                  title_second: 0, frames_rendered: 0 });
+        Ok(())
+    }
+
+    fn update(&mut self, ctx: &mut Context, dt: f32) {
+        if ctx.actions().was_triggered("quit") {
+            ctx.quit();
+        }
+
+        let state =
+            match &mut self.state {
+                Some(s) => s,
+                None => return
+            };
+
+        if ctx.actions().was_triggered("fullscreen") {
+            ctx.window_mut().toggle_fullscreen();
+        }
+        if ctx.actions().was_triggered("vsync") {
+            let vsync = !ctx.window().vsync();
+            ctx.window_mut().set_vsync(vsync);
+        }
+
+        // frame statistics in the title, once a second
+        if (ctx.time() as u64) != state.title_second {
+            state.title_second = ctx.time() as u64;
+            let t = ctx.timer();
+            let title = format!("{} - {:.1} fps, {:.2} ms (max {:.2} ms){}", self.options.model(), t.fps(), t.frame_time() * 1000.0, t.frame_time_max() * 1000.0,
+                                if ctx.window().vsync() { "" } else { ", no vsync" });
+            ctx.window_mut().set_title(title.as_str());
+        }
 
-        glfwDestroyWindow(win);
-        glfwTerminate();
+        // pointer deltas are in screen coordinates, the aspect ratio is the framebuffer's
+        let viewport = ctx.window().size();
+        let (width, height) = ctx.size();
+        let aspect = width as f32 / height.max(1) as f32;
+        let sphere = *state.monkey_bounds.sphere();
+        let actions = ctx.actions();
+
+        let mode =
+            if actions.was_triggered("camera_orbit") {
+                CameraMode::Orbit
+            } else if actions.was_triggered("camera_fly") {
+                CameraMode::Fly
+            } else if actions.was_triggered("camera_pan") {
+                CameraMode::PanZoom
+            } else {
+                state.mode
+            };
+
+        if mode != state.mode {
+            match mode {
+                CameraMode::Orbit => {
+                    state.camera.set_projection(Projection::Perspective { fov_y: 3.141516 / 4.0 });
+                },
+                CameraMode::Fly => {
+                    state.camera.set_projection(Projection::Perspective { fov_y: 3.141516 / 4.0 });
+                    state.fly.from_camera(&state.camera);
+                },
+                CameraMode::PanZoom => {
+                    state.pan_zoom.frame(&sphere, aspect);
+                    state.pan_zoom.snap();
+                },
+            }
+            state.mode = mode;
+        }
+
+        if actions.was_triggered("arcball") {
+            let m = if state.orbit.mode() == OrbitMode::Arcball { OrbitMode::Turntable } else { OrbitMode::Arcball };
+            state.orbit.set_mode(m);
+        }
+
+        let frame = actions.was_triggered("frame");
+        match state.mode {
+            CameraMode::Orbit => {
+                if frame {
+                    state.orbit.frame(&sphere, &state.camera, aspect);
+                }
+                state.orbit.apply_actions(actions, &state.camera, viewport, dt);
+                state.orbit.update(dt, &mut state.camera);
+                state.camera.clip_to(&sphere);
+            },
+            CameraMode::Fly => {
+                if frame {
+                    state.camera.frame(&sphere, aspect);
+                    state.fly.from_camera(&state.camera);
+                }
+                state.fly.apply_actions(actions, viewport);
+                state.fly.update(dt, &mut state.camera);
+                state.camera.clip_to(&sphere);
+            },
+            CameraMode::PanZoom => {
+                if frame {
+                    state.pan_zoom.frame(&sphere, aspect);
+                }
+                state.pan_zoom.apply_actions(actions, ctx.input().cursor(), viewport, dt);
+                state.pan_zoom.update(dt, &mut state.camera);
+            },
+        }
     }
-    0
-}
\ No newline at end of file
+
+    fn render(&mut self, ctx: &mut Context) {
+        let state =
+            match &mut self.state {
+                Some(s) => s,
+                None => return
+            };
+
+        let (width, height) = ctx.size();
+        unsafe {
+            glViewport(0, 0, width, height);
+            glScissor(0, 0, width, height);
+            glClearColor(0.0, 0.0, 0.0, 1.0);
+            glClear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT | GL_STENCIL_BUFFER_BIT);
+            glEnable(GL_DEPTH_TEST);
+        }
+
+        let u = Uniforms { pvm: state.camera.view_projection(width as f32 / height.max(1) as f32) };
+
+        match &state.program {
+            Some(p) => {
+                unsafe { glEnable(GL_CULL_FACE) };
+                draw_indexed(p, &state.monkey_vb, &state.monkey_ib, &u);
+            },
+            None => ()
+        }
+
+        // the screenshot is the last frame with --frames, the first one otherwise
+        state.frames_rendered += 1;
+        let last = self.options.frames().unwrap_or(1);
+        if state.frames_rendered == last {
+            if let Some(path) = self.options.screenshot() {
+                match screenshot::save(path, width, height) {
+                    Ok(()) => println!("screenshot: {}", path),
+                    Err(err) => println!("screenshot: {}: {}", path, err)
+                }
+            }
+            if self.options.frames().is_some() {
+                ctx.quit();
+            }
+        }
+    }
+}
+
+#[cfg(not(test))]
+#[link(name="c")]
+#[no_mangle]
+pub extern "C"
+fn main(argc: isize, argv: *const *const u8) -> isize  {
+    let args =
+        match unsafe { args::args(argc, argv) } {
+            Ok(a) => a,
+            Err(err) => {
+                println!("error     : {}", err);
+                return 2
+            }
+        };
+    let strs : Vec<&str> = args.iter().skip(1).map(|a| a.as_str()).collect();
+    let options =
+        match Options::parse(strs.as_slice()) {
+            Ok(o) => o,
+            Err(err) => {
+                println!("error     : {}\n{}", err, USAGE);
+                return 2
+            }
+        };
+    if options.help() {
+        println!("{}", USAGE);
+        return 0
+    }
+
+    if let Some(out) = options.export() {
+        return match export_model(options.model(), options.lod(), out) {
+            Ok(()) => {
+                println!("exported  : {}", out);
+                0
+            },
+            Err(err) => {
+                println!("error     : {}", err);
+                1
+            }
+        }
+    }
+
+    // headless runs render as fast as they can in a window nobody sees
+    let headless = options.frames().is_some();
+    let (width, height) = options.size();
+    let window = WindowConfig::new("App").with_size(width, height).with_samples(options.samples())
+        .with_mode(if options.fullscreen() { WindowMode::Borderless } else { WindowMode::Windowed })
+        .with_visible(!headless)
+        .with_vsync(!headless);
+
+    let config = AppConfig::new(window);
+    match app::run(&config, Viewer { options: options, state: None }) {
+        Ok(()) => 0,
+        Err(err) => {
+            println!("error     : {}", err);
+            1
+        }
+    }
+}
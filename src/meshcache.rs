@@ -0,0 +1,515 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+// Binary GPUMesh cache, every value is little endian:
+//   0      magic "RSMC", version, flags
+//   12     vertex count, vertex stride, attribute count
+//   24     index size (2 or 4), index count, submesh count
+//   36     bounds min xyz, max xyz
+//   60     vertex blob offset, index blob offset, submesh table offset
+//   72     attributes: semantic u16, f32 component count u16, offset u32
+//          vertex blob (16 byte aligned), index blob
+//          submeshes: first, count, length prefixed name and material padded to 4
+//   end-4  FNV-1a of every preceding byte
+use core::result::Result;
+use rs_alloc::*;
+use rs_streams::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+const MAGIC         : &[u8; 4] = b"RSMC";
+pub const VERSION   : u32 = 1;
+const HEADER_SIZE   : usize = 72;
+const NO_MATERIAL   : u32 = u32::MAX;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AttributeSemantic {
+    Position,
+    Normal,
+    TexCoord,
+}
+
+impl AttributeSemantic {
+    fn from_u16(v: u16) -> Option<Self> {
+        match v {
+            0 => Some(AttributeSemantic::Position),
+            1 => Some(AttributeSemantic::Normal),
+            2 => Some(AttributeSemantic::TexCoord),
+            _ => None
+        }
+    }
+
+    fn to_u16(&self) -> u16 {
+        match self {
+            AttributeSemantic::Position => 0,
+            AttributeSemantic::Normal => 1,
+            AttributeSemantic::TexCoord => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CacheAttribute {
+    semantic    : AttributeSemantic,
+    components  : u32,
+    offset      : u32,
+}
+
+// for uploading the vertex blob as it is, the viewer copies it back into a GPUMesh instead
+#[allow(dead_code)]
+impl CacheAttribute {
+    pub fn semantic(&self) -> AttributeSemantic { self.semantic }
+    /// number of f32 components
+    pub fn components(&self) -> u32 { self.components }
+    pub fn offset(&self) -> u32 { self.offset }
+}
+
+// the layout of `GPUVertex`
+const GPU_VERTEX_LAYOUT : [CacheAttribute; 3] = [
+    CacheAttribute { semantic: AttributeSemantic::Position, components: 3, offset: 0 },
+    CacheAttribute { semantic: AttributeSemantic::Normal, components: 3, offset: 12 },
+    CacheAttribute { semantic: AttributeSemantic::TexCoord, components: 2, offset: 24 },
+];
+const GPU_VERTEX_STRIDE : u32 = 32;
+
+fn fnv1a(data: &[u8]) -> u32 {
+    let mut h = 0x811c9dc5u32;
+    for b in data.iter() {
+        h ^= *b as u32;
+        h = h.wrapping_mul(0x01000193);
+    }
+    h
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 { u16::from_le_bytes([data[pos], data[pos + 1]]) }
+fn u32_at(data: &[u8], pos: usize) -> u32 { u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) }
+fn f32_at(data: &[u8], pos: usize) -> f32 { f32::from_bits(u32_at(data, pos)) }
+
+fn put_u32(out: &mut Vec<u8>, v: u32) { out.append(&v.to_le_bytes()); }
+fn put_f32(out: &mut Vec<u8>, v: f32) { out.append(&v.to_le_bytes()); }
+
+fn set_u32(out: &mut Vec<u8>, pos: usize, v: u32) {
+    out.as_mut_slice()[pos..pos + 4].copy_from_slice(&v.to_le_bytes());
+}
+
+fn pad(out: &mut Vec<u8>, align: usize) {
+    while out.len() % align != 0 {
+        out.push(0);
+    }
+}
+
+fn align_up(v: usize, align: usize) -> usize { (v + align - 1) / align * align }
+
+// offset + count * size, None when it does not fit in a usize
+fn blob_end(offset: usize, count: usize, size: usize) -> Option<usize> {
+    count.checked_mul(size).and_then(|n| n.checked_add(offset))
+}
+
+/// Borrows a cache file's bytes, the vertex and index blobs can be uploaded as they are
+pub struct MeshCacheView<'a> {
+    data            : &'a [u8],
+    vertex_count    : usize,
+    vertex_stride   : usize,
+    attributes      : Vec<CacheAttribute>,
+    index_size      : usize,
+    index_count     : usize,
+    submesh_count   : usize,
+    vertex_offset   : usize,
+    index_offset    : usize,
+    submesh_offset  : usize,
+}
+
+impl<'a> MeshCacheView<'a> {
+    /// Validates the header, the blob ranges and the checksum
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE + 4 || &data[0..4] != MAGIC {
+            return Err(String::from("not a mesh cache"))
+        }
+
+        if u32_at(data, 4) != VERSION {
+            return Err(format!("unsupported mesh cache version {}", u32_at(data, 4)))
+        }
+
+        let end = data.len() - 4;
+        if fnv1a(&data[..end]) != u32_at(data, end) {
+            return Err(String::from("mesh cache checksum mismatch"))
+        }
+
+        let vertex_count = u32_at(data, 12) as usize;
+        let vertex_stride = u32_at(data, 16) as usize;
+        let attribute_count = u32_at(data, 20) as usize;
+        let index_size = u32_at(data, 24) as usize;
+        let index_count = u32_at(data, 28) as usize;
+        let submesh_count = u32_at(data, 32) as usize;
+        let vertex_offset = u32_at(data, 60) as usize;
+        let index_offset = u32_at(data, 64) as usize;
+        let submesh_offset = u32_at(data, 68) as usize;
+
+        if index_size != 2 && index_size != 4 {
+            return Err(String::from("invalid index size"))
+        }
+
+        // the counts come from the file, a wrapped sum would pass the range checks
+        let in_order = |e: Option<usize>, next: usize| match e { Some(e) => e <= next, None => false };
+        if !in_order(blob_end(HEADER_SIZE, attribute_count, 8), vertex_offset)
+            || !in_order(blob_end(vertex_offset, vertex_count, vertex_stride), index_offset)
+            || !in_order(blob_end(index_offset, index_count, index_size), submesh_offset)
+            || submesh_offset > end {
+            return Err(String::from("mesh cache blobs out of range"))
+        }
+
+        let mut attributes = Vec::with_capacity(attribute_count);
+        for i in 0..attribute_count {
+            let pos = HEADER_SIZE + i * 8;
+            let semantic = match AttributeSemantic::from_u16(u16_at(data, pos)) { Some(s) => s, None => return Err(String::from("unknown vertex attribute")) };
+            attributes.push(CacheAttribute { semantic: semantic, components: u16_at(data, pos + 2) as u32, offset: u32_at(data, pos + 4) });
+        }
+
+        Ok(Self {
+            data            : data,
+            vertex_count    : vertex_count,
+            vertex_stride   : vertex_stride,
+            attributes      : attributes,
+            index_size      : index_size,
+            index_count     : index_count,
+            submesh_count   : submesh_count,
+            vertex_offset   : vertex_offset,
+            index_offset    : index_offset,
+            submesh_offset  : submesh_offset,
+        })
+    }
+
+    pub fn vertex_count(&self) -> usize { self.vertex_count }
+    pub fn vertex_stride(&self) -> usize { self.vertex_stride }
+    pub fn attributes(&self) -> &[CacheAttribute] { self.attributes.as_slice() }
+    pub fn vertex_data(&self) -> &'a [u8] { &self.data[self.vertex_offset..self.vertex_offset + self.vertex_count * self.vertex_stride] }
+
+    /// 2 or 4 bytes
+    pub fn index_size(&self) -> usize { self.index_size }
+    pub fn index_count(&self) -> usize { self.index_count }
+    pub fn index_data(&self) -> &'a [u8] { &self.data[self.index_offset..self.index_offset + self.index_count * self.index_size] }
+
+    // lets a caller frame the mesh before uploading it, the viewer measures the GPUMesh it built
+    #[allow(dead_code)]
+    pub fn bounds(&self) -> Box3f {
+        let d = self.data;
+        Box3f::new(&Vec3f::new(f32_at(d, 36), f32_at(d, 40), f32_at(d, 44)), &Vec3f::new(f32_at(d, 48), f32_at(d, 52), f32_at(d, 56)))
+    }
+
+    fn read_name(&self, pos: &mut usize) -> Result<Option<String>, String> {
+        let end = self.data.len() - 4;
+        if *pos + 4 > end {
+            return Err(String::from("truncated submesh table"))
+        }
+
+        let len = u32_at(self.data, *pos);
+        *pos += 4;
+        if len == NO_MATERIAL {
+            return Ok(None)
+        }
+
+        let len = len as usize;
+        if blob_end(*pos, len, 1).map_or(true, |e| e > end) {
+            return Err(String::from("truncated submesh table"))
+        }
+
+        let name = match core::str::from_utf8(&self.data[*pos..*pos + len]) { Ok(s) => String::from(s), Err(_) => return Err(String::from("invalid submesh name")) };
+        *pos += align_up(len, 4);
+        Ok(Some(name))
+    }
+
+    pub fn submeshes(&self) -> Result<Vec<SubMesh>, String> {
+        let mut pos = self.submesh_offset;
+        let mut submeshes = Vec::with_capacity(self.submesh_count);
+        for _ in 0..self.submesh_count {
+            if blob_end(pos, 8, 1).map_or(true, |e| e > self.data.len() - 4) {
+                return Err(String::from("truncated submesh table"))
+            }
+
+            let first = u32_at(self.data, pos) as usize;
+            let count = u32_at(self.data, pos + 4) as usize;
+            pos += 8;
+            if blob_end(first, count, 1).map_or(true, |e| e > self.index_count) {
+                return Err(String::from("submesh out of the index range"))
+            }
+
+            let name = self.read_name(&mut pos)?.unwrap_or(String::new());
+            let material = self.read_name(&mut pos)?;
+            submeshes.push(SubMesh::new(name, material, first, count));
+        }
+        Ok(submeshes)
+    }
+}
+
+impl GPUMesh {
+    /// Serializes the mesh in the cache format
+    pub fn to_cache(&self) -> Vec<u8> {
+        let verts = self.verts();
+        let mut bounds = Box3f::new(&Vec3f::new(0.0, 0.0, 0.0), &Vec3f::new(0.0, 0.0, 0.0));
+        for (i, v) in verts.iter().enumerate() {
+            bounds = if i == 0 { Box3f::new(&v.pos, &v.pos) } else { bounds.add(&v.pos) };
+        }
+
+        let (index_size, index_count) =
+            match self.tris() {
+                GPUIndices::U16(i) => (2, i.len()),
+                GPUIndices::U32(i) => (4, i.len()),
+            };
+
+        let mut out = Vec::new();
+        out.append(MAGIC);
+        put_u32(&mut out, VERSION);
+        put_u32(&mut out, 0);
+        put_u32(&mut out, verts.len() as u32);
+        put_u32(&mut out, GPU_VERTEX_STRIDE);
+        put_u32(&mut out, GPU_VERTEX_LAYOUT.len() as u32);
+        put_u32(&mut out, index_size);
+        put_u32(&mut out, index_count as u32);
+        put_u32(&mut out, self.submeshes().len() as u32);
+        for v in [bounds.min, bounds.max].iter() {
+            put_f32(&mut out, v.x);
+            put_f32(&mut out, v.y);
+            put_f32(&mut out, v.z);
+        }
+        // blob offsets, patched once known
+        for _ in 0..3 {
+            put_u32(&mut out, 0);
+        }
+
+        for a in GPU_VERTEX_LAYOUT.iter() {
+            out.append(&a.semantic.to_u16().to_le_bytes());
+            out.append(&(a.components as u16).to_le_bytes());
+            put_u32(&mut out, a.offset);
+        }
+
+        pad(&mut out, 16);
+        let vertex_offset = out.len();
+        for v in verts.iter() {
+            for f in [v.pos.x, v.pos.y, v.pos.z, v.normal.x, v.normal.y, v.normal.z, v.uv.x, v.uv.y].iter() {
+                put_f32(&mut out, *f);
+            }
+        }
+
+        let index_offset = out.len();
+        match self.tris() {
+            GPUIndices::U16(i) => for v in i.iter() { out.append(&v.to_le_bytes()); },
+            GPUIndices::U32(i) => for v in i.iter() { out.append(&v.to_le_bytes()); },
+        }
+        pad(&mut out, 4);
+
+        let submesh_offset = out.len();
+        for s in self.submeshes().iter() {
+            put_u32(&mut out, s.first() as u32);
+            put_u32(&mut out, s.count() as u32);
+            for name in [Some(s.name()), s.material()].iter() {
+                match name {
+                    Some(n) => {
+                        put_u32(&mut out, n.len() as u32);
+                        out.append(n.as_bytes());
+                        pad(&mut out, 4);
+                    },
+                    None => put_u32(&mut out, NO_MATERIAL)
+                }
+            }
+        }
+
+        set_u32(&mut out, 60, vertex_offset as u32);
+        set_u32(&mut out, 64, index_offset as u32);
+        set_u32(&mut out, 68, submesh_offset as u32);
+
+        let checksum = fnv1a(out.as_slice());
+        put_u32(&mut out, checksum);
+        out
+    }
+
+    /// Copies a cache back into a GPUMesh, the cache must have the `GPUVertex` layout
+    pub fn from_cache(view: &MeshCacheView) -> Result<GPUMesh, String> {
+        let layout_matches = view.vertex_stride() == GPU_VERTEX_STRIDE as usize
+            && view.attributes().len() == GPU_VERTEX_LAYOUT.len()
+            && view.attributes().iter().zip(GPU_VERTEX_LAYOUT.iter()).all(|(a, b)| a.semantic == b.semantic && a.components == b.components && a.offset == b.offset);
+        if !layout_matches {
+            return Err(String::from("mesh cache vertex layout differs from GPUVertex"))
+        }
+
+        let vd = view.vertex_data();
+        let mut verts = Vec::with_capacity(view.vertex_count());
+        for i in 0..view.vertex_count() {
+            let f = |k: usize| f32_at(vd, i * GPU_VERTEX_STRIDE as usize + k * 4);
+            verts.push(GPUVertex { pos: Vec3f::new(f(0), f(1), f(2)), normal: Vec3f::new(f(3), f(4), f(5)), uv: Vec2f::new(f(6), f(7)) });
+        }
+
+        let id = view.index_data();
+        let tris =
+            if view.index_size() == 2 {
+                let mut v = Vec::with_capacity(view.index_count());
+                for i in 0..view.index_count() { v.push(u16_at(id, i * 2)); }
+                GPUIndices::U16(v)
+            } else {
+                let mut v = Vec::with_capacity(view.index_count());
+                for i in 0..view.index_count() { v.push(u32_at(id, i * 4)); }
+                GPUIndices::U32(v)
+            };
+
+        for i in 0..tris.len() {
+            if tris.get(i) as usize >= verts.len() {
+                return Err(String::from("mesh cache index out of range"))
+            }
+        }
+
+        Ok(GPUMesh::new(verts, tris).with_submeshes(view.submeshes()?))
+    }
+
+    pub fn write_cache(&self, path: &str) -> Result<(), String> {
+        let mut f =
+            match File::create(path) {
+                Ok(f) => f,
+                Err(_) => return Err(String::from("Could not create file"))
+            };
+
+        let data = self.to_cache();
+        match f.write(data.as_slice()) {
+            Ok(n) if n == data.len() => Ok(()),
+            _ => Err(String::from("Could not write file"))
+        }
+    }
+
+    pub fn read_cache(path: &str) -> Result<GPUMesh, String> {
+        let data = read_bytes(path)?;
+        GPUMesh::from_cache(&MeshCacheView::parse(data.as_slice())?)
+    }
+}
+
+// seconds and nanoseconds, whole seconds would let an edit in the second the cache was written go unseen
+fn modified_time(path: &str) -> Option<(i64, i64)> {
+    let mut p = String::from(path);
+    p.push(0);
+    unsafe {
+        let mut st : libc::stat = core::mem::zeroed();
+        if libc::stat(p.as_bytes().as_ptr() as *const libc::c_char, &mut st) == 0 {
+            Some((st.st_mtime as i64, st.st_mtime_nsec as i64))
+        } else {
+            None
+        }
+    }
+}
+
+/// Loads `cache` unless `source` is newer or the cache is unreadable, in which case the mesh
+/// is rebuilt with `build` and the cache rewritten. Equal times count as stale, on file systems
+/// with coarse timestamps that costs a rebuild rather than showing an outdated mesh. Failing to
+/// write the cache is not an error.
+pub fn load_cached<F: FnOnce() -> Result<GPUMesh, String>>(source: &str, cache: &str, build: F) -> Result<GPUMesh, String> {
+    let fresh =
+        match (modified_time(source), modified_time(cache)) {
+            (Some(s), Some(c)) => c > s,
+            (None, Some(_)) => true,
+            _ => false
+        };
+
+    if fresh {
+        match GPUMesh::read_cache(cache) {
+            Ok(m) => return Ok(m),
+            Err(_) => ()
+        }
+    }
+
+    let mesh = build()?;
+    let _ = mesh.write_cache(cache);
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad() -> GPUMesh {
+        let mut verts = Vec::new();
+        for (x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 2.0), (0.0, 2.0)].iter() {
+            verts.push(GPUVertex { pos: Vec3f::new(*x, *y, -1.0), normal: Vec3f::new(0.0, 0.0, 1.0), uv: Vec2f::new(*x, *y * 0.5) });
+        }
+        let mut indices = Vec::new();
+        for i in [0u32, 1, 2, 2, 3, 0].iter() {
+            indices.push(*i);
+        }
+        let mut subs = Vec::new();
+        subs.push(SubMesh::new(String::from("front"), Some(String::from("paint")), 0, 3));
+        subs.push(SubMesh::new(String::from("back"), None, 3, 3));
+        GPUMesh::new(verts, GPUIndices::from_u32(indices, 4)).with_submeshes(subs)
+    }
+
+    // recomputes the checksum after patching a header field
+    fn patched(data: &[u8], pos: usize, v: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.append(&data[..data.len() - 4]);
+        set_u32(&mut out, pos, v);
+        let checksum = fnv1a(out.as_slice());
+        put_u32(&mut out, checksum);
+        out
+    }
+
+    #[test]
+    fn write_parse_round_trip() {
+        let m = quad();
+        let data = m.to_cache();
+        let view = MeshCacheView::parse(data.as_slice()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(view.vertex_count(), 4);
+        assert_eq!((view.index_size(), view.index_count()), (2, 6));
+        assert_eq!((view.bounds().max.y, view.bounds().min.z), (2.0, -1.0));
+
+        let r = GPUMesh::from_cache(&view).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        for (a, b) in m.verts().iter().zip(r.verts().iter()) {
+            assert_eq!((a.pos.x, a.pos.y, a.pos.z, a.uv.y), (b.pos.x, b.pos.y, b.pos.z, b.uv.y));
+        }
+        assert_eq!(r.tris().to_u32().as_slice(), m.tris().to_u32().as_slice());
+        assert_eq!(r.submeshes().len(), 2);
+        assert_eq!((r.submeshes()[0].name(), r.submeshes()[0].material()), ("front", Some("paint")));
+        assert_eq!((r.submeshes()[1].first(), r.submeshes()[1].material()), (3, None));
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut data = quad().to_cache();
+        data.as_mut_slice()[HEADER_SIZE + 10] ^= 1;
+        match MeshCacheView::parse(data.as_slice()) {
+            Ok(_) => panic!("corrupted cache parsed"),
+            Err(err) => assert_eq!(err.as_str(), "mesh cache checksum mismatch")
+        }
+    }
+
+    #[test]
+    fn truncated_header() {
+        let data = quad().to_cache();
+        assert!(MeshCacheView::parse(&data.as_slice()[..HEADER_SIZE]).is_err());
+
+        // counts that only fit by wrapping around
+        assert!(MeshCacheView::parse(patched(data.as_slice(), 12, u32::MAX).as_slice()).is_err());
+        assert!(MeshCacheView::parse(patched(data.as_slice(), 28, u32::MAX).as_slice()).is_err());
+        assert!(MeshCacheView::parse(patched(data.as_slice(), 68, u32::MAX).as_slice()).is_err());
+        assert!(MeshCacheView::parse(patched(data.as_slice(), 32, 3).as_slice()).map(|v| v.submeshes().is_err()).unwrap_or(true));
+    }
+}
@@ -0,0 +1,582 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+use rs_math3d::*;
+use rs_math3d::matrix::Matrix4Extension;
+
+use crate::objloader::*;
+use crate::meshstats::*;
+
+fn transform_point(m: &Mat4f, p: &Vec3f) -> Vec3f {
+    let v = Mat4f::mul_matrix_vector(m, &Vec4f::new(p.x, p.y, p.z, 1.0));
+    if v.w != 0.0 && v.w != 1.0 {
+        Vec3f::new(v.x / v.w, v.y / v.w, v.z / v.w)
+    } else {
+        Vec3f::new(v.x, v.y, v.z)
+    }
+}
+
+/// Inverse transpose of the upper 3x3, keeps normals perpendicular under non uniform scale
+pub fn normal_matrix(m: &Mat4f) -> Mat3f {
+    m.mat3().inverse().transpose()
+}
+
+fn transform_normal(nm: &Mat3f, n: &Vec3f) -> Vec3f {
+    let t = *nm * *n;
+    let l = Vec3f::length(&t);
+    if l > 0.0 { t * (1.0 / l) } else { t }
+}
+
+// recentering on the box center then scaling the largest side to 1
+fn unit_transform(b: &Bounds) -> Mat4f {
+    let s = b.size();
+    let extent = s.x.max(s.y).max(s.z);
+    let scale = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+    rs_math3d::scale(Vec3f::new(scale, scale, scale)) * rs_math3d::translate(-b.center())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Connectivity
+////////////////////////////////////////////////////////////////////////////////
+
+struct DisjointSet {
+    parent  : Vec<u32>,
+}
+
+impl DisjointSet {
+    fn new(count: usize) -> Self { Self { parent: (0..count as u32).collect() } }
+
+    fn find(&mut self, i: u32) -> u32 {
+        let mut root = i;
+        while self.parent[root as usize] != root {
+            root = self.parent[root as usize];
+        }
+
+        // path compression
+        let mut i = i;
+        while self.parent[i as usize] != root {
+            let next = self.parent[i as usize];
+            self.parent[i as usize] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: u32, b: u32) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra != rb {
+            self.parent[ra as usize] = rb;
+        }
+    }
+}
+
+// assigns consecutive component ids in order of first appearance
+fn label_components(set: &mut DisjointSet, first_vertex: &[u32]) -> (Vec<u32>, usize) {
+    let mut root_label = Vec::with_capacity(set.parent.len());
+    for _ in 0..set.parent.len() {
+        root_label.push(NO_INDEX);
+    }
+
+    let mut labels = Vec::with_capacity(first_vertex.len());
+    let mut count = 0;
+    for v in first_vertex.iter() {
+        let r = set.find(*v) as usize;
+        if root_label[r] == NO_INDEX {
+            root_label[r] = count as u32;
+            count += 1;
+        }
+        labels.push(root_label[r]);
+    }
+    (labels, count)
+}
+
+// maps source indices to indices local to the component being extracted, reset in O(touched)
+struct Remap {
+    map     : Vec<u32>,
+    touched : Vec<u32>,
+}
+
+impl Remap {
+    fn new(count: usize) -> Self {
+        let mut map = Vec::with_capacity(count);
+        for _ in 0..count {
+            map.push(NO_INDEX);
+        }
+        Self { map: map, touched: Vec::new() }
+    }
+
+    fn get<T: Copy>(&mut self, idx: u32, src: &[T], dst: &mut Vec<T>) -> u32 {
+        if idx == NO_INDEX {
+            return NO_INDEX
+        }
+
+        let i = idx as usize;
+        if self.map[i] == NO_INDEX {
+            self.map[i] = dst.len() as u32;
+            self.touched.push(idx);
+            dst.push(src[i]);
+        }
+        self.map[i]
+    }
+
+    fn reset(&mut self) {
+        while let Some(i) = self.touched.pop() {
+            self.map[i as usize] = NO_INDEX;
+        }
+    }
+}
+
+fn in_range(idx: &[u32], count: usize) -> bool {
+    idx.iter().all(|i| *i == NO_INDEX || (*i as usize) < count)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Mesh
+////////////////////////////////////////////////////////////////////////////////
+
+impl Mesh {
+    /// Transforms positions by `m` and normals by its normal matrix. Mirroring transforms also
+    /// flip the winding so faces keep pointing outwards.
+    pub fn transform(&mut self, m: &Mat4f) {
+        for p in self.verts_mut().iter_mut() {
+            *p = transform_point(m, p);
+        }
+
+        let nm = normal_matrix(m);
+        for n in self.normals_mut().iter_mut() {
+            *n = transform_normal(&nm, n);
+        }
+
+        if m.mat3().determinant() < 0.0 {
+            self.flip_winding();
+        }
+    }
+
+    /// Moves the bounding box center to the origin and returns the previous center
+    // this and the rest the viewer does not call are for tools built on the loaders
+    #[allow(dead_code)]
+    pub fn recenter(&mut self) -> Vec3f {
+        let c = self.bounds().center();
+        self.transform(&rs_math3d::translate(-c));
+        c
+    }
+
+    /// Recenters and scales uniformly so the largest side of the bounding box is 1,
+    /// returns the transform that was applied
+    #[allow(dead_code)]
+    pub fn normalize(&mut self) -> Mat4f {
+        let m = unit_transform(&self.bounds());
+        self.transform(&m);
+        m
+    }
+
+    /// Reverses the vertex order of every face, normals are left untouched
+    pub fn flip_winding(&mut self) {
+        for t in self.tris_mut().iter_mut() {
+            let (v, uv, n) = (t.verts(), t.uvs(), t.norms());
+            *t = IdTri::new([v[0], v[2], v[1]], [uv[0], uv[2], uv[1]], [n[0], n[2], n[1]]);
+        }
+
+        for q in self.quads_mut().iter_mut() {
+            let (v, uv, n) = (q.verts(), q.uvs(), q.norms());
+            *q = IdQuad::new([v[0], v[3], v[2], v[1]], [uv[0], uv[3], uv[2], uv[1]], [n[0], n[3], n[2], n[1]]);
+        }
+    }
+
+    /// Concatenates meshes, groups keep their faces and materials with the same name are merged.
+    /// When only some meshes have colors the others are white.
+    pub fn merge(meshes: &[&Mesh]) -> Mesh {
+        let mut verts = Vec::new();
+        let mut uvws = Vec::new();
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+        let mut tris = Vec::new();
+        let mut quads = Vec::new();
+        let mut groups = Vec::new();
+        let mut materials = Vec::<Material>::new();
+
+        let any_colors = meshes.iter().any(|m| m.colors().len() != 0);
+        let any_groups = meshes.iter().any(|m| m.groups().len() != 0);
+        let offset = |i: u32, base: usize| if i == NO_INDEX { NO_INDEX } else { i + base as u32 };
+
+        for m in meshes.iter() {
+            let (vb, uvb, nb) = (verts.len(), uvws.len(), normals.len());
+            let (tb, qb) = (tris.len(), quads.len());
+
+            verts.append(m.verts().as_slice());
+            uvws.append(m.uvws().as_slice());
+            normals.append(m.normals().as_slice());
+            if any_colors {
+                for i in 0..m.verts().len() {
+                    colors.push(if m.colors().len() == m.verts().len() { m.colors()[i] } else { Vec4f::new(1.0, 1.0, 1.0, 1.0) });
+                }
+            }
+
+            for t in m.tris().iter() {
+                let (v, uv, n) = (t.verts(), t.uvs(), t.norms());
+                tris.push(IdTri::new([offset(v[0], vb), offset(v[1], vb), offset(v[2], vb)],
+                                     [offset(uv[0], uvb), offset(uv[1], uvb), offset(uv[2], uvb)],
+                                     [offset(n[0], nb), offset(n[1], nb), offset(n[2], nb)]));
+            }
+
+            for q in m.quads().iter() {
+                let (v, uv, n) = (q.verts(), q.uvs(), q.norms());
+                quads.push(IdQuad::new([offset(v[0], vb), offset(v[1], vb), offset(v[2], vb), offset(v[3], vb)],
+                                       [offset(uv[0], uvb), offset(uv[1], uvb), offset(uv[2], uvb), offset(uv[3], uvb)],
+                                       [offset(n[0], nb), offset(n[1], nb), offset(n[2], nb), offset(n[3], nb)]));
+            }
+
+            if any_groups {
+                if m.groups().len() == 0 {
+                    groups.push(Group::new(String::new(), None, tb..tris.len(), qb..quads.len()));
+                }
+                for g in m.groups().iter() {
+                    groups.push(Group::new(String::from(g.name()), g.material().map(|s| String::from(s)),
+                                           g.tris().start + tb..g.tris().end + tb, g.quads().start + qb..g.quads().end + qb));
+                }
+            }
+
+            for mat in m.materials().iter() {
+                if !materials.iter().any(|x| x.name == mat.name) {
+                    materials.push(mat.clone());
+                }
+            }
+        }
+
+        Mesh::from(verts, uvws, tris, quads).with_normals(normals).with_colors(colors).with_groups(groups).with_materials(materials)
+    }
+
+    /// Splits into meshes of faces connected through shared vertices, in order of first face.
+    /// Faces with out of range indices are dropped.
+    #[allow(dead_code)]
+    pub fn split_by_connectivity(&self) -> Vec<Mesh> {
+        let (nv, nuv, nn) = (self.verts().len(), self.uvws().len(), self.normals().len());
+        let valid = |v: &[u32], uv: &[u32], n: &[u32]| v.iter().all(|i| (*i as usize) < nv) && in_range(uv, nuv) && in_range(n, nn);
+        let tris = self.tris().as_slice();
+        let quads = self.quads().as_slice();
+
+        if nv == 0 {
+            return Vec::new()
+        }
+
+        let mut set = DisjointSet::new(nv);
+        let mut first_vertex = Vec::new();
+        for t in tris.iter() {
+            let v = t.verts();
+            if valid(v, t.uvs(), t.norms()) {
+                set.union(v[0], v[1]);
+                set.union(v[0], v[2]);
+            }
+            first_vertex.push(v[0].min(nv as u32 - 1));
+        }
+        for q in quads.iter() {
+            let v = q.verts();
+            if valid(v, q.uvs(), q.norms()) {
+                set.union(v[0], v[1]);
+                set.union(v[0], v[2]);
+                set.union(v[0], v[3]);
+            }
+            first_vertex.push(v[0].min(nv as u32 - 1));
+        }
+
+        let (labels, count) = label_components(&mut set, first_vertex.as_slice());
+        let tri_label = &labels.as_slice()[..tris.len()];
+        let quad_label = &labels.as_slice()[tris.len()..];
+
+        // faces of each component, in file order
+        let mut comp_tris : Vec<Vec<u32>> = (0..count).map(|_| Vec::new()).collect();
+        let mut comp_quads : Vec<Vec<u32>> = (0..count).map(|_| Vec::new()).collect();
+        for (i, t) in tris.iter().enumerate() {
+            if valid(t.verts(), t.uvs(), t.norms()) {
+                comp_tris[tri_label[i] as usize].push(i as u32);
+            }
+        }
+        for (i, q) in quads.iter().enumerate() {
+            if valid(q.verts(), q.uvs(), q.norms()) {
+                comp_quads[quad_label[i] as usize].push(i as u32);
+            }
+        }
+
+        let has_colors = self.colors().len() == nv;
+        let whole = [Group::new(String::new(), None, 0..tris.len(), 0..quads.len())];
+        let groups = if self.groups().len() == 0 { &whole[..] } else { self.groups() };
+
+        let mut vmap = Remap::new(nv);
+        let mut uvmap = Remap::new(nuv);
+        let mut nmap = Remap::new(nn);
+        let mut cmap = Remap::new(if has_colors { nv } else { 0 });
+
+        let mut out = Vec::new();
+        for c in 0..count {
+            let (ct, cq) = (comp_tris[c].as_slice(), comp_quads[c].as_slice());
+            if ct.len() == 0 && cq.len() == 0 {
+                continue
+            }
+
+            let mut verts = Vec::new();
+            let mut uvws = Vec::new();
+            let mut normals = Vec::new();
+            let mut colors = Vec::new();
+            let mut new_tris = Vec::new();
+            let mut new_quads = Vec::new();
+            let mut new_groups = Vec::new();
+            let (mut ti, mut qi) = (0, 0);
+
+            for g in groups.iter() {
+                let (tb, qb) = (new_tris.len(), new_quads.len());
+                while ti < ct.len() && (ct[ti] as usize) < g.tris().end {
+                    let t = &tris[ct[ti] as usize];
+                    if (ct[ti] as usize) >= g.tris().start {
+                        let (v, uv, n) = (t.verts(), t.uvs(), t.norms());
+                        let mut nv = [0; 3];
+                        for k in 0..3 {
+                            nv[k] = vmap.get(v[k], self.verts().as_slice(), &mut verts);
+                            if has_colors { cmap.get(v[k], self.colors().as_slice(), &mut colors); }
+                        }
+                        new_tris.push(IdTri::new(nv,
+                            [uvmap.get(uv[0], self.uvws().as_slice(), &mut uvws), uvmap.get(uv[1], self.uvws().as_slice(), &mut uvws), uvmap.get(uv[2], self.uvws().as_slice(), &mut uvws)],
+                            [nmap.get(n[0], self.normals().as_slice(), &mut normals), nmap.get(n[1], self.normals().as_slice(), &mut normals), nmap.get(n[2], self.normals().as_slice(), &mut normals)]));
+                    }
+                    ti += 1;
+                }
+
+                while qi < cq.len() && (cq[qi] as usize) < g.quads().end {
+                    let q = &quads[cq[qi] as usize];
+                    if (cq[qi] as usize) >= g.quads().start {
+                        let (v, uv, n) = (q.verts(), q.uvs(), q.norms());
+                        let mut nv = [0; 4];
+                        let mut nuv = [0; 4];
+                        let mut nn = [0; 4];
+                        for k in 0..4 {
+                            nv[k] = vmap.get(v[k], self.verts().as_slice(), &mut verts);
+                            if has_colors { cmap.get(v[k], self.colors().as_slice(), &mut colors); }
+                            nuv[k] = uvmap.get(uv[k], self.uvws().as_slice(), &mut uvws);
+                            nn[k] = nmap.get(n[k], self.normals().as_slice(), &mut normals);
+                        }
+                        new_quads.push(IdQuad::new(nv, nuv, nn));
+                    }
+                    qi += 1;
+                }
+
+                if self.groups().len() != 0 && (new_tris.len() != tb || new_quads.len() != qb) {
+                    new_groups.push(Group::new(String::from(g.name()), g.material().map(|s| String::from(s)), tb..new_tris.len(), qb..new_quads.len()));
+                }
+            }
+
+            vmap.reset();
+            uvmap.reset();
+            nmap.reset();
+            cmap.reset();
+
+            let materials = self.materials().iter().cloned().collect();
+            out.push(Mesh::from(verts, uvws, new_tris, new_quads).with_normals(normals).with_colors(colors).with_groups(new_groups).with_materials(materials));
+        }
+        out
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// GPUMesh
+////////////////////////////////////////////////////////////////////////////////
+
+// the viewer only transforms the source Mesh, these are for tools working on GPU meshes
+#[allow(dead_code)]
+impl GPUMesh {
+    /// See `Mesh::transform`
+    pub fn transform(&mut self, m: &Mat4f) {
+        let nm = normal_matrix(m);
+        for v in self.verts_mut().iter_mut() {
+            v.pos = transform_point(m, &v.pos);
+            v.normal = transform_normal(&nm, &v.normal);
+        }
+
+        if m.mat3().determinant() < 0.0 {
+            self.flip_winding();
+        }
+    }
+
+    pub fn recenter(&mut self) -> Vec3f {
+        let c = self.bounds().center();
+        self.transform(&rs_math3d::translate(-c));
+        c
+    }
+
+    pub fn normalize(&mut self) -> Mat4f {
+        let m = unit_transform(&self.bounds());
+        self.transform(&m);
+        m
+    }
+
+    pub fn flip_winding(&mut self) {
+        match self.tris_mut() {
+            GPUIndices::U16(i) => for t in i.as_mut_slice().chunks_exact_mut(3) { t.swap(1, 2); },
+            GPUIndices::U32(i) => for t in i.as_mut_slice().chunks_exact_mut(3) { t.swap(1, 2); },
+        }
+    }
+
+    /// Concatenates meshes, meshes without submeshes become an unnamed one when others have some
+    pub fn merge(meshes: &[&GPUMesh]) -> GPUMesh {
+        let mut verts = Vec::new();
+        let mut indices = Vec::new();
+        let mut submeshes = Vec::new();
+        let any_submeshes = meshes.iter().any(|m| m.submeshes().len() != 0);
+
+        for m in meshes.iter() {
+            let (vb, ib) = (verts.len() as u32, indices.len());
+            verts.append(m.verts());
+            for i in 0..m.tris().len() {
+                indices.push(m.tris().get(i) + vb);
+            }
+
+            if any_submeshes {
+                if m.submeshes().len() == 0 {
+                    submeshes.push(SubMesh::new(String::new(), None, ib, m.tris().len()));
+                }
+                for s in m.submeshes().iter() {
+                    submeshes.push(SubMesh::new(String::from(s.name()), s.material().map(|m| String::from(m)), s.first() + ib, s.count()));
+                }
+            }
+        }
+
+        let count = verts.len();
+        GPUMesh::new(verts, GPUIndices::from_u32(indices, count)).with_submeshes(submeshes)
+    }
+
+    /// Splits into meshes of triangles connected through shared positions, uv and normal seams
+    /// don't separate pieces
+    pub fn split_by_connectivity(&self) -> Vec<GPUMesh> {
+        let verts = self.verts();
+        if verts.len() == 0 {
+            return Vec::new()
+        }
+
+        let positions : Vec<Vec3f> = verts.iter().map(|v| v.pos).collect();
+        let ids = position_ids(positions.as_slice());
+        let indices = self.tris().to_u32();
+        let tri_count = indices.len() / 3;
+
+        let mut set = DisjointSet::new(verts.len());
+        let mut first_vertex = Vec::with_capacity(tri_count);
+        for t in 0..tri_count {
+            let v = [ids[indices[t * 3] as usize], ids[indices[t * 3 + 1] as usize], ids[indices[t * 3 + 2] as usize]];
+            set.union(v[0], v[1]);
+            set.union(v[0], v[2]);
+            first_vertex.push(v[0]);
+        }
+
+        let (labels, count) = label_components(&mut set, first_vertex.as_slice());
+        let mut comp_tris : Vec<Vec<u32>> = (0..count).map(|_| Vec::new()).collect();
+        for t in 0..tri_count {
+            comp_tris[labels[t] as usize].push(t as u32);
+        }
+
+        let whole = [SubMesh::new(String::new(), None, 0, indices.len())];
+        let submeshes = if self.submeshes().len() == 0 { &whole[..] } else { self.submeshes() };
+        let mut vmap = Remap::new(verts.len());
+
+        let mut out = Vec::new();
+        for c in 0..count {
+            let ct = comp_tris[c].as_slice();
+            let mut new_verts = Vec::new();
+            let mut new_indices = Vec::new();
+            let mut new_submeshes = Vec::new();
+            let mut ti = 0;
+
+            for s in submeshes.iter() {
+                let r = match s.range(indices.len()) { Some(r) => r, None => continue };
+                let first = new_indices.len();
+                while ti < ct.len() && (ct[ti] as usize * 3) < r.end {
+                    let t = ct[ti] as usize;
+                    if t * 3 >= r.start {
+                        for k in 0..3 {
+                            new_indices.push(vmap.get(indices[t * 3 + k], verts, &mut new_verts));
+                        }
+                    }
+                    ti += 1;
+                }
+
+                if self.submeshes().len() != 0 && new_indices.len() != first {
+                    new_submeshes.push(SubMesh::new(String::from(s.name()), s.material().map(|m| String::from(m)), first, new_indices.len() - first));
+                }
+            }
+            vmap.reset();
+
+            let vc = new_verts.len();
+            out.push(GPUMesh::new(new_verts, GPUIndices::from_u32(new_indices, vc)).with_submeshes(new_submeshes));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two triangles sharing no vertex, one per group
+    static TWO_PIECES : &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 5 0 0\nv 6 0 0\nv 5 1 0\ng a\nf 1 2 3\ng b\nf 4 5 6\n";
+
+    #[test]
+    fn split_and_merge() {
+        let m = Mesh::parse_obj(TWO_PIECES).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let parts = m.split_by_connectivity();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].verts().len(), 3);
+        assert_eq!(parts[1].verts()[0].x, 5.0);
+        assert_eq!(parts[1].groups()[0].name(), "b");
+
+        let merged = Mesh::merge(&[&parts[1], &parts[0]]);
+        assert_eq!(merged.verts().len(), 6);
+        assert_eq!(merged.tris()[1].verts(), &[3, 4, 5]);
+        assert_eq!(merged.groups()[1].name(), "a");
+        assert_eq!(merged.groups()[1].tris(), 1..2);
+    }
+
+    #[test]
+    fn mirror_flips_winding() {
+        let mut m = Mesh::parse_obj(TWO_PIECES).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        m.transform(&rs_math3d::scale(Vec3f::new(-1.0, 1.0, 1.0)));
+        assert_eq!(m.verts()[1].x, -1.0);
+        assert_eq!(m.tris()[0].verts(), &[0, 2, 1]);
+    }
+
+    #[test]
+    fn gpu_split_and_merge() {
+        let m = Mesh::parse_obj(TWO_PIECES).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let g = GPUMesh::from(&m).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let parts = g.split_by_connectivity();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].submeshes().len(), 1);
+        assert_eq!(parts[0].submeshes()[0].name(), "a");
+
+        let merged = GPUMesh::merge(&[&parts[0], &parts[1]]);
+        assert_eq!(merged.tris().len(), 6);
+        assert_eq!(merged.submeshes()[1].first(), 3);
+        assert_eq!(merged.submeshes()[1].name(), "b");
+    }
+}
@@ -0,0 +1,440 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+const CACHE_SIZE        : usize = 32;
+const CACHE_DECAY_POWER : f32   = 1.5;
+const LAST_TRI_SCORE    : f32   = 0.75;
+const VALENCE_BOOST_SCALE : f32 = 2.0;
+const VALENCE_BOOST_POWER : f32 = 0.5;
+
+/// FIFO size used when reporting ACMR, matching common GLES2 hardware
+pub const ACMR_CACHE_SIZE : usize = 16;
+
+fn filled<T: Copy>(count: usize, v: T) -> Vec<T> {
+    let mut r = Vec::with_capacity(count);
+    for _ in 0..count {
+        r.push(v);
+    }
+    r
+}
+
+// the optimisers index their per vertex tables with these, so they have to be checked up front
+fn check_indices(indices: &[u32], vert_count: usize) -> Result<(), String> {
+    match indices.iter().position(|i| *i as usize >= vert_count) {
+        Some(p) => Err(format!("index {}: vertex {} out of range ({} vertices)", p, indices[p], vert_count)),
+        None => Ok(())
+    }
+}
+
+extern "C" {
+    fn powf(b: f32, e: f32) -> f32;
+}
+
+/// average cache miss ratio: transformed vertices per triangle for a FIFO post-transform cache,
+/// out of range indices count as misses
+pub fn acmr(indices: &[u32], vert_count: usize, cache_size: usize) -> f32 {
+    let tri_count = indices.len() / 3;
+    if tri_count == 0 {
+        return 0.0
+    }
+
+    // timestamp of the insertion into the FIFO
+    let mut stamps = filled(vert_count, 0usize);
+    let mut time = cache_size + 1;
+    let mut misses = 0;
+    for i in indices {
+        let v = *i as usize;
+        if v >= vert_count {
+            misses += 1;
+        } else if time - stamps[v] > cache_size {
+            stamps[v] = time;
+            time += 1;
+            misses += 1;
+        }
+    }
+    misses as f32 / tri_count as f32
+}
+
+fn vertex_score(cache_pos: isize, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0
+    }
+
+    let cache_score =
+        if cache_pos < 0 {
+            0.0
+        } else if cache_pos < 3 {
+            LAST_TRI_SCORE
+        } else {
+            let s = 1.0 - (cache_pos - 3) as f32 / (CACHE_SIZE - 3) as f32;
+            unsafe { powf(s, CACHE_DECAY_POWER) }
+        };
+
+    cache_score + VALENCE_BOOST_SCALE * unsafe { powf(remaining as f32, -VALENCE_BOOST_POWER) }
+}
+
+/// Tom Forsyth's linear-speed vertex cache optimisation, returns the reordered indices.
+/// Trailing indices that do not make a whole triangle are dropped.
+pub fn optimize_vertex_cache(indices: &[u32], vert_count: usize) -> Result<Vec<u32>, String> {
+    let tri_count = indices.len() / 3;
+    let indices = &indices[..tri_count * 3];
+    check_indices(indices, vert_count)?;
+
+    // vertex -> triangles adjacency, packed
+    let mut valence = filled(vert_count, 0u32);
+    for i in indices {
+        valence[*i as usize] += 1;
+    }
+
+    let mut offsets = filled(vert_count + 1, 0usize);
+    for v in 0..vert_count {
+        offsets[v + 1] = offsets[v] + valence[v] as usize;
+    }
+
+    let mut fill = filled(vert_count, 0usize);
+    let mut adjacency = filled(indices.len(), 0u32);
+    for t in 0..tri_count {
+        for k in 0..3 {
+            let v = indices[t * 3 + k] as usize;
+            adjacency[offsets[v] + fill[v]] = t as u32;
+            fill[v] += 1;
+        }
+    }
+
+    let mut remaining = valence;
+    let mut cache_pos = filled(vert_count, -1isize);
+    let mut vscore = filled(vert_count, 0.0f32);
+    for v in 0..vert_count {
+        vscore[v] = vertex_score(-1, remaining[v]);
+    }
+
+    let mut emitted = filled(tri_count, false);
+    let mut tscore = filled(tri_count, 0.0f32);
+    for t in 0..tri_count {
+        tscore[t] = vscore[indices[t * 3] as usize] + vscore[indices[t * 3 + 1] as usize] + vscore[indices[t * 3 + 2] as usize];
+    }
+
+    let mut cache = Vec::<u32>::with_capacity(CACHE_SIZE + 3);
+    let mut next_cache = Vec::<u32>::with_capacity(CACHE_SIZE + 3);
+    let mut result = Vec::with_capacity(indices.len());
+    let mut scan = 0;
+
+    let mut best = -1isize;
+    let mut best_score = -1.0f32;
+    for t in 0..tri_count {
+        if tscore[t] > best_score {
+            best_score = tscore[t];
+            best = t as isize;
+        }
+    }
+
+    while best >= 0 {
+        let t = best as usize;
+        emitted[t] = true;
+
+        // the new triangle goes in front, the rest of the cache is shifted back
+        while next_cache.pop().is_some() {}
+        for k in 0..3 {
+            let v = indices[t * 3 + k];
+            result.push(v);
+            next_cache.push(v);
+
+            let vu = v as usize;
+            remaining[vu] -= 1;
+            let (start, end) = (offsets[vu], offsets[vu] + remaining[vu] as usize + 1);
+            for a in start..end {
+                if adjacency[a] == t as u32 {
+                    adjacency[a] = adjacency[end - 1];
+                    break
+                }
+            }
+        }
+
+        for v in cache.iter() {
+            if *v != next_cache[0] && *v != next_cache[1] && *v != next_cache[2] {
+                next_cache.push(*v);
+            }
+        }
+
+        for (p, v) in next_cache.iter().enumerate() {
+            let vu = *v as usize;
+            cache_pos[vu] = if p < CACHE_SIZE { p as isize } else { -1 };
+            vscore[vu] = vertex_score(cache_pos[vu], remaining[vu]);
+        }
+
+        // pick the best triangle touching the cache, rescoring as we go
+        best = -1;
+        best_score = -1.0;
+        for v in next_cache.iter() {
+            let vu = *v as usize;
+            for a in offsets[vu]..offsets[vu] + remaining[vu] as usize {
+                let at = adjacency[a] as usize;
+                tscore[at] = vscore[indices[at * 3] as usize] + vscore[indices[at * 3 + 1] as usize] + vscore[indices[at * 3 + 2] as usize];
+                if tscore[at] > best_score {
+                    best_score = tscore[at];
+                    best = at as isize;
+                }
+            }
+        }
+
+        core::mem::swap(&mut cache, &mut next_cache);
+        while cache.len() > CACHE_SIZE {
+            cache.pop();
+        }
+
+        if best < 0 {
+            while scan < tri_count && emitted[scan] {
+                scan += 1;
+            }
+            if scan < tri_count {
+                best = scan as isize;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// reorders vertices by first use and remaps the indices accordingly, unused vertices are dropped
+pub fn optimize_vertex_fetch(verts: &[GPUVertex], indices: &mut [u32]) -> Result<Vec<GPUVertex>, String> {
+    check_indices(indices, verts.len())?;
+    let mut remap = filled(verts.len(), u32::MAX);
+    let mut result = Vec::with_capacity(verts.len());
+    for i in indices.iter_mut() {
+        let v = *i as usize;
+        if remap[v] == u32::MAX {
+            remap[v] = result.len() as u32;
+            result.push(verts[v]);
+        }
+        *i = remap[v];
+    }
+    Ok(result)
+}
+
+struct Cluster {
+    start   : usize,
+    end     : usize,
+    key     : f32,
+}
+
+/// Sorts triangle clusters front to back from the outside of the mesh (Sander et al. 2007).
+/// Clusters are split at cache flushes and wherever the local ACMR stays within `threshold`
+/// times the whole mesh's, so the vertex cache efficiency degrades by at most that factor.
+/// Trailing indices that do not make a whole triangle are dropped.
+pub fn optimize_overdraw(verts: &[GPUVertex], indices: &[u32], threshold: f32) -> Result<Vec<u32>, String> {
+    let tri_count = indices.len() / 3;
+    let indices = &indices[..tri_count * 3];
+    check_indices(indices, verts.len())?;
+    if tri_count == 0 {
+        return Ok(Vec::new())
+    }
+
+    let global = acmr(indices, verts.len(), ACMR_CACHE_SIZE);
+
+    // hard boundaries: every vertex of the triangle misses the cache
+    let mut stamps = filled(verts.len(), 0usize);
+    let mut time = ACMR_CACHE_SIZE + 1;
+    let mut clusters = Vec::new();
+    let mut start = 0;
+    let mut misses = 0;
+    for t in 0..tri_count {
+        let mut tri_misses = 0;
+        for k in 0..3 {
+            let v = indices[t * 3 + k] as usize;
+            if time - stamps[v] > ACMR_CACHE_SIZE {
+                stamps[v] = time;
+                time += 1;
+                tri_misses += 1;
+            }
+        }
+
+        // soft boundary: the cluster so far is as cache friendly as we need it to be
+        let local = misses as f32 / (t - start).max(1) as f32;
+        if t > start && (tri_misses == 3 || local <= global * threshold) {
+            clusters.push(Cluster { start: start, end: t, key: 0.0 });
+            start = t;
+            misses = 0;
+        }
+        misses += tri_misses;
+    }
+    clusters.push(Cluster { start: start, end: tri_count, key: 0.0 });
+
+    let mut center = Vec3f::zero();
+    let mut total_area = 0.0;
+    for t in 0..tri_count {
+        let p0 = verts[indices[t * 3] as usize].pos;
+        let p1 = verts[indices[t * 3 + 1] as usize].pos;
+        let p2 = verts[indices[t * 3 + 2] as usize].pos;
+        let area = Vec3f::cross(&(p1 - p0), &(p2 - p0)).length();
+        center = center + (p0 + p1 + p2) * (area / 3.0);
+        total_area += area;
+    }
+    if total_area > 0.0 {
+        center = center / total_area;
+    }
+
+    // outward facing clusters far from the center occlude the rest, draw them first
+    for c in clusters.iter_mut() {
+        let mut c_center = Vec3f::zero();
+        let mut c_normal = Vec3f::zero();
+        let mut c_area = 0.0;
+        for t in c.start..c.end {
+            let p0 = verts[indices[t * 3] as usize].pos;
+            let p1 = verts[indices[t * 3 + 1] as usize].pos;
+            let p2 = verts[indices[t * 3 + 2] as usize].pos;
+            let n = Vec3f::cross(&(p1 - p0), &(p2 - p0));
+            let area = n.length();
+            c_center = c_center + (p0 + p1 + p2) * (area / 3.0);
+            c_normal = c_normal + n;
+            c_area += area;
+        }
+
+        if c_area > 0.0 {
+            c_center = c_center / c_area;
+        }
+        let nl = c_normal.length();
+        if nl > 0.0 {
+            c_normal = c_normal / nl;
+        }
+        c.key = Vec3f::dot(&(c_center - center), &c_normal);
+    }
+
+    clusters.as_mut_slice().sort_unstable_by(|a, b| b.key.partial_cmp(&a.key).unwrap_or(core::cmp::Ordering::Equal));
+
+    let mut result = Vec::with_capacity(indices.len());
+    for c in clusters.iter() {
+        for i in c.start * 3..c.end * 3 {
+            result.push(indices[i]);
+        }
+    }
+    Ok(result)
+}
+
+pub struct OptimizeStats {
+    acmr_before : f32,
+    acmr_after  : f32,
+}
+
+impl OptimizeStats {
+    pub fn acmr_before(&self) -> f32 { self.acmr_before }
+    pub fn acmr_after(&self)  -> f32 { self.acmr_after }
+}
+
+impl GPUMesh {
+    /// Reorders triangles for the post-transform cache, optionally sorts them to reduce overdraw
+    /// (`overdraw_threshold` ~1.05) and finally reorders the vertices for fetch locality.
+    /// Triangles never move across submeshes, submeshes that are out of range or not made of whole
+    /// triangles are left as they are.
+    pub fn optimize(&mut self, overdraw_threshold: Option<f32>) -> Result<OptimizeStats, String> {
+        let vert_count = self.verts().len();
+        let mut indices = self.tris().to_u32();
+        let acmr_before = acmr(indices.as_slice(), vert_count, ACMR_CACHE_SIZE);
+
+        let mut ranges = Vec::new();
+        for s in self.submeshes().iter() {
+            match s.range(indices.len()) {
+                Some(r) => ranges.push(r),
+                None => ()
+            }
+        }
+        if self.submeshes().len() == 0 {
+            ranges.push(0..indices.len() / 3 * 3);
+        }
+
+        for r in ranges.iter() {
+            let range = &mut indices.as_mut_slice()[r.clone()];
+            let mut sorted = optimize_vertex_cache(range, vert_count)?;
+            match overdraw_threshold {
+                Some(t) => sorted = optimize_overdraw(self.verts(), sorted.as_slice(), t)?,
+                None => ()
+            }
+            range.copy_from_slice(sorted.as_slice());
+        }
+
+        let verts = optimize_vertex_fetch(self.verts(), indices.as_mut_slice())?;
+        let acmr_after = acmr(indices.as_slice(), verts.len(), ACMR_CACHE_SIZE);
+        let count = verts.len();
+        let submeshes = self.submeshes().iter().map(|s| SubMesh::new(String::from(s.name()), s.material().map(|m| String::from(m)), s.first(), s.count())).collect();
+        *self = GPUMesh::new(verts, GPUIndices::from_u32(indices, count)).with_submeshes(submeshes);
+
+        Ok(OptimizeStats { acmr_before: acmr_before, acmr_after: acmr_after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(n: usize) -> (Vec<GPUVertex>, Vec<u32>) {
+        let mut verts = Vec::new();
+        for i in 0..n + 2 {
+            verts.push(GPUVertex { pos: Vec3f::new((i / 2) as f32, (i % 2) as f32, 0.0), normal: Vec3f::new(0.0, 0.0, 1.0), uv: Vec2f::new(0.0, 0.0) });
+        }
+        let mut indices = Vec::new();
+        for t in 0..n as u32 {
+            indices.push(t);
+            indices.push(t + 1 + t % 2);
+            indices.push(t + 2 - t % 2);
+        }
+        (verts, indices)
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        let (verts, mut indices) = strip(2);
+        indices.push(0);
+        indices.push(1);
+        indices.push(9);
+        assert!(optimize_vertex_cache(indices.as_slice(), verts.len()).is_err());
+        assert!(optimize_overdraw(verts.as_slice(), indices.as_slice(), 1.05).is_err());
+        assert!(optimize_vertex_fetch(verts.as_slice(), indices.as_mut_slice()).is_err());
+        assert_eq!(acmr(indices.as_slice(), verts.len(), ACMR_CACHE_SIZE), 5.0 / 3.0);
+    }
+
+    #[test]
+    fn partial_triangles() {
+        let (verts, mut indices) = strip(4);
+        indices.push(0);
+        assert_eq!(optimize_vertex_cache(indices.as_slice(), verts.len()).unwrap_or_else(|e| panic!("{}", e.as_str())).len(), 12);
+
+        // a submesh that is not whole triangles stays as it is, the rest is still optimised
+        let count = verts.len();
+        let mut subs = Vec::new();
+        subs.push(SubMesh::new(String::from("strip"), None, 0, 6));
+        subs.push(SubMesh::new(String::from("partial"), None, 6, 7));
+        let mut m = GPUMesh::new(verts, GPUIndices::from_u32(indices, count)).with_submeshes(subs);
+        m.optimize(Some(1.05)).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(m.tris().len(), 13);
+        assert_eq!(m.verts().len(), 6);
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Bounding volumes
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy)]
+pub struct Bounds {
+    aabb    : Box3f,
+    sphere  : Sphere3<f32>,
+}
+
+impl Bounds {
+    /// empty point sets give a zero sized box and sphere at the origin
+    pub fn from_points<I: Iterator<Item = Vec3f> + Clone>(points: I) -> Self {
+        let zero = Vec3f::new(0.0, 0.0, 0.0);
+        let mut aabb = None;
+        for p in points.clone() {
+            aabb = match aabb { None => Some(Box3f::new(&p, &p)), Some(b) => Some(b.add(&p)) };
+        }
+
+        match aabb {
+            None => Self { aabb: Box3f::new(&zero, &zero), sphere: Sphere3::new(zero, 0.0) },
+            Some(aabb) => Self { aabb: aabb, sphere: bounding_sphere(points, &aabb) }
+        }
+    }
+
+    pub fn aabb(&self) -> &Box3f { &self.aabb }
+    pub fn sphere(&self) -> &Sphere3<f32> { &self.sphere }
+    pub fn center(&self) -> Vec3f { self.aabb.center() }
+    pub fn size(&self) -> Vec3f { self.aabb.max - self.aabb.min }
+}
+
+fn farthest<I: Iterator<Item = Vec3f>>(points: I, from: &Vec3f) -> Vec3f {
+    let mut best = *from;
+    let mut best_d = 0.0;
+    for p in points {
+        let d = Vec3f::dot(&(p - *from), &(p - *from));
+        if d > best_d {
+            best_d = d;
+            best = p;
+        }
+    }
+    best
+}
+
+// Ritter's sphere, or the box centered one when that is tighter
+fn bounding_sphere<I: Iterator<Item = Vec3f> + Clone>(points: I, aabb: &Box3f) -> Sphere3<f32> {
+    let first = match points.clone().next() { Some(p) => p, None => return Sphere3::new(aabb.center(), 0.0) };
+    let p1 = farthest(points.clone(), &first);
+    let p2 = farthest(points.clone(), &p1);
+
+    let mut center = (p1 + p2) * 0.5;
+    let mut radius = Vec3f::length(&(p2 - p1)) * 0.5;
+    for p in points.clone() {
+        let d = Vec3f::length(&(p - center));
+        if d > radius {
+            // grow just enough to include p, keeping the far side in place
+            let new_radius = (radius + d) * 0.5;
+            center = center + (p - center) * ((new_radius - radius) / d);
+            radius = new_radius;
+        }
+    }
+
+    let box_center = aabb.center();
+    let mut box_radius = 0.0f32;
+    for p in points {
+        box_radius = box_radius.max(Vec3f::length(&(p - box_center)));
+    }
+
+    if box_radius < radius {
+        Sphere3::new(box_center, box_radius)
+    } else {
+        Sphere3::new(center, radius)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Statistics
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct SubMeshStats {
+    name            : String,
+    bounds          : Bounds,
+    triangle_count  : usize,
+}
+
+impl SubMeshStats {
+    pub fn name(&self) -> &str { self.name.as_str() }
+    pub fn bounds(&self) -> &Bounds { &self.bounds }
+    pub fn triangle_count(&self) -> usize { self.triangle_count }
+}
+
+pub struct MeshStats {
+    bounds          : Bounds,
+    area            : f32,
+    volume          : Option<f32>,
+    vertex_count    : usize,
+    triangle_count  : usize,
+    submeshes       : Vec<SubMeshStats>,
+}
+
+impl MeshStats {
+    pub fn bounds(&self) -> &Bounds { &self.bounds }
+    pub fn area(&self) -> f32 { self.area }
+    /// only closed meshes have a volume, negative when the faces point inwards
+    pub fn volume(&self) -> Option<f32> { self.volume }
+    pub fn vertex_count(&self) -> usize { self.vertex_count }
+    pub fn triangle_count(&self) -> usize { self.triangle_count }
+    pub fn submeshes(&self) -> &[SubMeshStats] { self.submeshes.as_slice() }
+}
+
+fn position_bits(p: &Vec3f) -> [u32; 3] {
+    // -0.0 and 0.0 are the same position
+    let b = |f: f32| if f == 0.0 { 0 } else { f.to_bits() };
+    [b(p.x), b(p.y), b(p.z)]
+}
+
+/// Maps every vertex to the first vertex sharing its position, so that seams split by uvs or
+/// normals don't look like open edges
+pub fn position_ids(positions: &[Vec3f]) -> Vec<u32> {
+    let mut order : Vec<u32> = (0..positions.len() as u32).collect();
+    order.as_mut_slice().sort_unstable_by_key(|i| position_bits(&positions[*i as usize]));
+
+    let mut ids : Vec<u32> = (0..positions.len() as u32).collect();
+    let mut first = 0;
+    for i in 1..order.len() {
+        let (a, b) = (order[first] as usize, order[i] as usize);
+        if position_bits(&positions[a]) == position_bits(&positions[b]) {
+            ids[b] = order[first];
+        } else {
+            first = i;
+        }
+    }
+    ids
+}
+
+// every edge shared by exactly two triangles
+fn is_closed(positions: &[Vec3f], indices: &[u32]) -> bool {
+    if indices.len() == 0 {
+        return false
+    }
+
+    let ids = position_ids(positions);
+    let mut edges = Vec::with_capacity(indices.len());
+    for t in 0..indices.len() / 3 {
+        for k in 0..3 {
+            let a = ids[indices[t * 3 + k] as usize] as u64;
+            let b = ids[indices[t * 3 + (k + 1) % 3] as usize] as u64;
+            edges.push(if a < b { (a << 32) | b } else { (b << 32) | a });
+        }
+    }
+
+    edges.as_mut_slice().sort_unstable();
+    let e = edges.as_slice();
+    let mut i = 0;
+    while i < e.len() {
+        let mut j = i;
+        while j < e.len() && e[j] == e[i] {
+            j += 1;
+        }
+        if j - i != 2 {
+            return false
+        }
+        i = j;
+    }
+    true
+}
+
+fn triangle_stats(positions: &[Vec3f], indices: &[u32]) -> (f32, Option<f32>) {
+    let mut area = 0.0;
+    let mut volume = 0.0;
+    for t in 0..indices.len() / 3 {
+        let p0 = positions[indices[t * 3] as usize];
+        let p1 = positions[indices[t * 3 + 1] as usize];
+        let p2 = positions[indices[t * 3 + 2] as usize];
+        area += Vec3f::length(&Vec3f::cross(&(p1 - p0), &(p2 - p0))) * 0.5;
+        volume += Vec3f::dot(&p0, &Vec3f::cross(&p1, &p2)) / 6.0;
+    }
+
+    (area, if is_closed(positions, indices) { Some(volume) } else { None })
+}
+
+fn stats(positions: &[Vec3f], indices: &[u32], submeshes: Vec<SubMeshStats>) -> MeshStats {
+    let (area, volume) = triangle_stats(positions, indices);
+    MeshStats {
+        bounds          : Bounds::from_points(positions.iter().cloned()),
+        area            : area,
+        volume          : volume,
+        vertex_count    : positions.len(),
+        triangle_count  : indices.len() / 3,
+        submeshes       : submeshes,
+    }
+}
+
+fn submesh_stats(name: &str, positions: &[Vec3f], indices: &[u32]) -> SubMeshStats {
+    SubMeshStats {
+        name            : String::from(name),
+        bounds          : Bounds::from_points(indices.iter().map(|i| positions[*i as usize])),
+        triangle_count  : indices.len() / 3,
+    }
+}
+
+fn push_quad(indices: &mut Vec<u32>, v: &[u32; 4]) {
+    for i in [0, 1, 2, 2, 3, 0].iter() {
+        indices.push(v[*i]);
+    }
+}
+
+impl Mesh {
+    /// Bounds of every vertex, used or not
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(self.verts().iter().cloned())
+    }
+
+    /// Faces with out of range vertex indices are skipped, see `validate`
+    // the viewer reports on the GPUMesh it draws, this is for tools working on the source mesh
+    #[allow(dead_code)]
+    pub fn stats(&self) -> MeshStats {
+        let positions = self.verts().as_slice();
+        let valid = |v: &[u32]| v.iter().all(|i| (*i as usize) < positions.len());
+        let triangulate = |tris: &[IdTri], quads: &[IdQuad]| {
+            let mut indices = Vec::new();
+            for t in tris.iter().filter(|t| valid(t.verts())) {
+                indices.append(t.verts());
+            }
+            for q in quads.iter().filter(|q| valid(q.verts())) {
+                push_quad(&mut indices, q.verts());
+            }
+            indices
+        };
+
+        let tris = self.tris().as_slice();
+        let quads = self.quads().as_slice();
+        let mut submeshes = Vec::new();
+        for g in self.groups().iter() {
+            // reversed ranges would panic when slicing
+            let (t, q) = (g.tris(), g.quads());
+            if t.start <= t.end && t.end <= tris.len() && q.start <= q.end && q.end <= quads.len() {
+                let indices = triangulate(&tris[t], &quads[q]);
+                submeshes.push(submesh_stats(g.name(), positions, indices.as_slice()));
+            }
+        }
+
+        stats(positions, triangulate(tris, quads).as_slice(), submeshes)
+    }
+}
+
+impl GPUMesh {
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(self.verts().iter().map(|v| v.pos))
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        let positions : Vec<Vec3f> = self.verts().iter().map(|v| v.pos).collect();
+        let indices = self.tris().to_u32();
+
+        let mut submeshes = Vec::new();
+        for s in self.submeshes().iter() {
+            match s.range(indices.len()) {
+                Some(r) => submeshes.push(submesh_stats(s.name(), positions.as_slice(), &indices.as_slice()[r])),
+                None => ()
+            }
+        }
+
+        stats(positions.as_slice(), indices.as_slice(), submeshes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE : &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+                         f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 2 3 7 6\nf 3 4 8 7\nf 4 1 5 8\n";
+
+    fn cube(faces: usize) -> Mesh {
+        let text = CUBE.split('\n').take(8 + faces).fold(String::new(), |mut s, l| { s.push_str(l); s.push_str("\n"); s });
+        Mesh::parse_obj(text.as_str()).unwrap_or_else(|e| panic!("{}", e.as_str()))
+    }
+
+    #[test]
+    fn unit_cube() {
+        let stats = cube(6).stats();
+        let b = stats.bounds().aabb();
+        assert_eq!((b.min.x, b.min.y, b.min.z, b.max.x, b.max.y, b.max.z), (0.0, 0.0, 0.0, 1.0, 1.0, 1.0));
+        assert!((stats.bounds().sphere().radius - 0.75f32.sqrt()).abs() < 1e-5);
+        assert_eq!(stats.area(), 6.0);
+        assert!((stats.volume().unwrap_or(0.0) - 1.0).abs() < 1e-6);
+        assert_eq!((stats.vertex_count(), stats.triangle_count()), (8, 12));
+
+        let g = GPUMesh::from(&cube(6)).unwrap_or_else(|e| panic!("{}", e.as_str())).stats();
+        assert!((g.volume().unwrap_or(0.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn open_cube() {
+        let stats = cube(5).stats();
+        assert_eq!(stats.area(), 5.0);
+        assert!(stats.volume().is_none());
+    }
+
+    #[test]
+    fn group_ranges() {
+        let mut groups = Vec::new();
+        groups.push(Group::new(String::from("top"), None, 0..0, 1..2));
+        groups.push(Group::new(String::from("reversed"), None, 0..0, 2..1));
+        groups.push(Group::new(String::from("past"), None, 0..0, 5..7));
+        let m = cube(6).with_groups(groups);
+        let stats = m.stats();
+        assert_eq!(stats.submeshes().len(), 1);
+        assert_eq!(stats.submeshes()[0].name(), "top");
+        assert_eq!(stats.submeshes()[0].triangle_count(), 2);
+        assert_eq!(stats.submeshes()[0].bounds().aabb().min.z, 1.0);
+    }
+}
@@ -27,31 +27,62 @@
 // POSSIBILITY OF SUCH DAMAGE.
 //
 use core::result::Result;
+use core::ops::Range;
 use rs_alloc::*;
 use rs_streams::*;
 
 use rs_math3d::*;
 
+#[derive(Clone, Copy)]
 pub struct IdTri {
     verts   : [u32; 3],
-    uvs     : [u32; 3]
+    uvs     : [u32; 3],
+    norms   : [u32; 3],
 }
 
 impl IdTri {
-    pub fn new(verts: [u32; 3], uvs: [u32; 3]) -> Self { Self { verts: verts, uvs: uvs } }
+    pub fn new(verts: [u32; 3], uvs: [u32; 3], norms: [u32; 3]) -> Self { Self { verts: verts, uvs: uvs, norms: norms } }
+    pub fn verts(&self) -> &[u32; 3] { &self.verts }
+    pub fn uvs(&self)   -> &[u32; 3] { &self.uvs }
+    pub fn norms(&self) -> &[u32; 3] { &self.norms }
 }
 
 
+#[derive(Clone, Copy)]
 pub struct IdQuad {
     verts   : [u32; 4],
-    uvs     : [u32; 4]
+    uvs     : [u32; 4],
+    norms   : [u32; 4],
 }
 
 impl IdQuad {
-    pub fn new(verts: [u32; 4], uvs: [u32; 4]) -> Self { Self { verts: verts, uvs: uvs } }
+    pub fn new(verts: [u32; 4], uvs: [u32; 4], norms: [u32; 4]) -> Self { Self { verts: verts, uvs: uvs, norms: norms } }
+    pub fn verts(&self) -> &[u32; 4] { &self.verts }
+    pub fn uvs(&self)   -> &[u32; 4] { &self.uvs }
+    pub fn norms(&self) -> &[u32; 4] { &self.norms }
 }
 
 
+/// reads a whole file, for the binary formats
+pub fn read_bytes(path: &str) -> Result<Vec<u8>, String> {
+    let mut f =
+        match File::open(path) {
+            Ok(f) => f,
+            Err(_) => return Err(String::from("Could not open file"))
+        };
+
+    let size = f.size();
+    let mut data = Vec::with_capacity(size);
+    for _ in 0..size {
+        data.push(0u8);
+    }
+
+    match f.read(data.as_mut_slice()) {
+        Ok(n) if n == size => Ok(data),
+        _ => Err(String::from("Could not read file"))
+    }
+}
+
 fn parse_vec3(parts: &[&str], verts: &mut Vec<Vec3f>) -> Result<i32, String> {
     if parts.len() != 3 {
         return Err(String::from("expecting 3 floats"))
@@ -70,36 +101,57 @@ fn parse_vec3(parts: &[&str], verts: &mut Vec<Vec3f>) -> Result<i32, String> {
     }
 }
 
-fn parse_vec2(parts: &[&str], uvws: &mut Vec<Vec3f>) -> Result<i32, String> {
-    if parts.len() != 2 {
-        return Err(String::from("expecting 2 floats"))
+fn parse_uvw(parts: &[&str], uvws: &mut Vec<Vec3f>) -> Result<i32, String> {
+    if parts.len() < 1 || parts.len() > 3 {
+        return Err(String::from("expecting 1 to 3 floats"))
     }
 
-    let f0 : Result<f32, _> = parts[0].parse();
-    let f1 : Result<f32, _> = parts[1].parse();
+    // v and w default to 0 when missing
+    let mut uvw = [0.0; 3];
+    for i in 0..parts.len() {
+        match parts[i].parse::<f32>() {
+            Ok(f) => uvw[i] = f,
+            _ => return Err(String::from("float parse error"))
+        }
+    }
 
-    match (f0, f1) {
-        (Ok(f0), Ok(f1)) => {
-            uvws.push(Vec3f::new(f0, f1, 0.0));
-            Ok(0)
-        },
-        _ => Err(String::from("float parse error"))
+    uvws.push(Vec3f::new(uvw[0], uvw[1], uvw[2]));
+    Ok(0)
+}
+
+/// marks a face corner without a texture coordinate or normal
+pub const NO_INDEX : u32 = u32::MAX;
+
+fn parse_index(s: &str) -> Result<u32, String> {
+    if s == "" {
+        return Ok(NO_INDEX)
+    }
+
+    match s.parse::<u32>() {
+        Ok(i) if i > 0 => Ok(i - 1),
+        _ => Err(String::from("invalid index"))
     }
 }
 
-fn parse_part(part: &str) -> Result<(u32, u32), String> {
-    let parts : Vec<&str> = part.split('/').collect();
-    if parts.len() != 1 && parts.len() != 3 {
-        return Result::Err(String::from("expecting vertex or vertex//uv"))
+fn parse_part(part: &str) -> Result<(u32, u32, u32), String> {
+    let mut parts = [""; 3];
+    let mut count = 0;
+    for p in part.split('/') {
+        if count == 3 {
+            return Result::Err(String::from("expecting v, v/vt, v//vn or v/vt/vn"))
+        }
+        parts[count] = p;
+        count += 1;
     }
 
-    let v : Result<u32, _> = parts[0].parse();
-    let uv : Result<u32, _> = if parts.len() == 1 { Ok(0) } else { parts[2].parse() };
+    let v = parse_index(parts[0])?;
+    let uv = if count > 1 { parse_index(parts[1])? } else { NO_INDEX };
+    let n = if count > 2 { parse_index(parts[2])? } else { NO_INDEX };
 
-    match (v, uv) {
-        (Ok(v), Ok(uv)) => Result::Ok((v, uv)),
-        _ => Result::Err(String::from("expecting vertex//uv or vertex"))
+    if v == NO_INDEX {
+        return Result::Err(String::from("missing vertex index"))
     }
+    Result::Ok((v, uv, n))
 }
 
 fn parse_face(parts: &[&str], tris: &mut Vec<IdTri>, quads: &mut Vec<IdQuad>) -> Result<i32, String> {
@@ -113,161 +165,1047 @@ fn parse_face(parts: &[&str], tris: &mut Vec<IdTri>, quads: &mut Vec<IdQuad>) ->
 
     if parts.len() == 3 {
         match (&p0, &p1, &p2) {
-            (Ok((v0, uv0)), Ok((v1, uv1)), Ok((v2, uv2))) => {
-                tris.push(IdTri::new([*v0 - 1, *v1 - 1, *v2 - 1], [*uv0 - 1, *uv1 - 1, *uv2 - 1]));
+            (Ok((v0, uv0, n0)), Ok((v1, uv1, n1)), Ok((v2, uv2, n2))) => {
+                tris.push(IdTri::new([*v0, *v1, *v2], [*uv0, *uv1, *uv2], [*n0, *n1, *n2]));
                 return Ok(0)
             }
-            _ => { return Err(String::new()) }
+            (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => { return Err(err.clone()) }
         }
     }
 
     let p3 = parse_part(&parts[3]);
     match (&p0, &p1, &p2, &p3) {
-        (Ok((v0, uv0)), Ok((v1, uv1)), Ok((v2, uv2)), Ok((v3, uv3))) => { quads.push(IdQuad::new([*v0 - 1, *v1 - 1, *v2 - 1, *v3 - 1], [*uv0 - 1, *uv1 - 1, *uv2 - 1, *uv3 - 1])); }
-        _ => { return Err(String::new()) }
+        (Ok((v0, uv0, n0)), Ok((v1, uv1, n1)), Ok((v2, uv2, n2)), Ok((v3, uv3, n3))) => { quads.push(IdQuad::new([*v0, *v1, *v2, *v3], [*uv0, *uv1, *uv2, *uv3], [*n0, *n1, *n2, *n3])); }
+        (Err(err), _, _, _) | (_, Err(err), _, _) | (_, _, Err(err), _) | (_, _, _, Err(err)) => { return Err(err.clone()) }
     }
 
     Ok(0)
 }
 
-fn parse_line(line: &str, verts: &mut Vec<Vec3f>, uvws: &mut Vec<Vec3f>, tris: &mut Vec<IdTri>, quads: &mut Vec<IdQuad>) -> Result<i32, String> {
-    if line == "" {
-        return Result::Ok(0)
+const CHUNK_SIZE : usize = 64 * 1024;
+
+// replaces the bytes that are not utf-8 with `?`, so comments and names in another
+// encoding still load
+fn lossy_utf8(bytes: &mut [u8]) -> &str {
+    let mut pos = 0;
+    loop {
+        match core::str::from_utf8(&bytes[pos..]) {
+            Ok(_) => break,
+            Err(err) => {
+                let bad = pos + err.valid_up_to();
+                let end = match err.error_len() { Some(n) => bad + n, None => bytes.len() };
+                for b in bytes[bad..end].iter_mut() {
+                    *b = b'?';
+                }
+                pos = end;
+            }
+        }
     }
+    core::str::from_utf8(bytes).unwrap_or("")
+}
 
-    let parts : Vec<&str> = line.split(|x| x == ' ' || x == '\t').filter(|&x| x != "").collect();
-    if parts.len() == 0 {
-        return Result::Err(String::from("No part!"))
+// Splits a stream into lines with a fixed size read buffer, a line is only copied once
+// into `line`, which grows to the longest line of the file. `\r\n` endings are stripped
+// and a trailing `\` joins the next line.
+struct LineReader<'a, R: StreamReader> {
+    reader  : &'a mut R,
+    chunk   : Vec<u8>,
+    pos     : usize,
+    len     : usize,
+    line    : Vec<u8>,
+    eof     : bool,
+    // physical lines consumed so far
+    number  : usize,
+}
+
+impl<'a, R: StreamReader> LineReader<'a, R> {
+    fn new(reader: &'a mut R) -> Self {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        for _ in 0..CHUNK_SIZE {
+            chunk.push(0u8);
+        }
+        Self { reader: reader, chunk: chunk, pos: 0, len: 0, line: Vec::new(), eof: false, number: 0 }
     }
 
+    fn fill(&mut self) -> Result<(), String> {
+        match self.reader.read(self.chunk.as_mut_slice()) {
+            Ok(n) => {
+                self.pos = 0;
+                self.len = n;
+                self.eof = n == 0;
+                Ok(())
+            },
+            Err(_) => Err(String::from("Could not read file"))
+        }
+    }
+
+    fn put(&mut self, line_len: &mut usize, bytes_from: usize, bytes_to: usize) {
+        let count = bytes_to - bytes_from;
+        while self.line.len() < *line_len + count {
+            self.line.push(0);
+        }
+        self.line.as_mut_slice()[*line_len..*line_len + count].copy_from_slice(&self.chunk.as_slice()[bytes_from..bytes_to]);
+        *line_len += count;
+    }
+
+    /// the next logical line and the physical line it starts on
+    fn next_line(&mut self) -> Result<Option<(usize, &str)>, String> {
+        let first = self.number + 1;
+        let mut line_len = 0;
+        let mut any = false;
+        loop {
+            if self.pos == self.len {
+                if self.eof {
+                    break
+                }
+                self.fill()?;
+                continue
+            }
+
+            any = true;
+            let start = self.pos;
+            let newline = self.chunk.as_slice()[start..self.len].iter().position(|c| *c == b'\n');
+            match newline {
+                None => {
+                    let end = self.len;
+                    self.put(&mut line_len, start, end);
+                    self.pos = end;
+                },
+                Some(n) => {
+                    self.put(&mut line_len, start, start + n);
+                    self.pos = start + n + 1;
+                    self.number += 1;
+
+                    let line = self.line.as_mut_slice();
+                    if line_len > 0 && line[line_len - 1] == b'\r' {
+                        line_len -= 1;
+                    }
+
+                    if line_len > 0 && line[line_len - 1] == b'\\' {
+                        line[line_len - 1] = b' ';
+                        continue
+                    }
+                    break
+                }
+            }
+        }
+
+        if !any {
+            return Ok(None)
+        }
+
+        Ok(Some((first, lossy_utf8(&mut self.line.as_mut_slice()[..line_len]))))
+    }
+}
+
+// lets in memory data go through `LineReader` without the copy a `MemoryStreamReader` makes,
+// the viewer itself only reads files
+#[allow(dead_code)]
+struct SliceReader<'a> {
+    data    : &'a [u8],
+    pos     : usize,
+}
+
+impl<'a> Drop for SliceReader<'a> {
+    fn drop(&mut self) {}
+}
+
+impl<'a> Stream for SliceReader<'a> {
+    fn tell(&self) -> usize { self.pos }
+    fn size(&self) -> usize { self.data.len() }
+}
+
+impl<'a> StreamReader for SliceReader<'a> {
+    fn read(&mut self, buff: &mut [u8]) -> Result<usize, ()> {
+        let count = buff.len().min(self.data.len() - self.pos);
+        buff[..count].copy_from_slice(&self.data[self.pos..self.pos + count]);
+        self.pos += count;
+        Ok(count)
+    }
+
+    fn is_eof(&self) -> bool { self.pos == self.data.len() }
+}
+
+// accumulates everything an OBJ file declares while its lines are parsed
+struct ObjBuilder {
+    verts   : Vec<Vec3f>,
+    uvws    : Vec<Vec3f>,
+    normals : Vec<Vec3f>,
+    tris    : Vec<IdTri>,
+    quads   : Vec<IdQuad>,
+    groups  : Vec<Group>,
+    mtllibs : Vec<String>,
+}
+
+impl ObjBuilder {
+    fn new() -> Self {
+        Self { verts: Vec::new(), uvws: Vec::new(), normals: Vec::new(), tris: Vec::new(), quads: Vec::new(), groups: Vec::new(), mtllibs: Vec::new() }
+    }
+
+    fn close_group(&mut self) {
+        let (tris, quads) = (self.tris.len(), self.quads.len());
+        match self.groups.as_mut_slice().last_mut() {
+            Some(g) => {
+                g.tris.end = tris;
+                g.quads.end = quads;
+            },
+            None => ()
+        }
+    }
+
+    // `g`, `o` and `usemtl` all start a new group, faces before the first one go to an unnamed group
+    fn begin_group(&mut self, name: Option<&str>, material: Option<&str>) {
+        if self.groups.len() == 0 && (self.tris.len() != 0 || self.quads.len() != 0) {
+            self.groups.push(Group::new(String::new(), None, 0..self.tris.len(), 0..self.quads.len()));
+        }
+        self.close_group();
+
+        let (prev_name, prev_material) =
+            match self.groups.last() {
+                Some(g) => (g.name.clone(), g.material.clone()),
+                None => (String::new(), None)
+            };
+
+        match self.groups.last() {
+            Some(g) if g.tris.len() == 0 && g.quads.len() == 0 => { self.groups.pop(); },
+            _ => ()
+        }
+
+        let name = match name { Some(n) => String::from(n), None => prev_name };
+        let material = match material { Some(m) => Some(String::from(m)), None => prev_material };
+        self.groups.push(Group::new(name, material, self.tris.len()..self.tris.len(), self.quads.len()..self.quads.len()));
+    }
+
+    fn build(mut self) -> (Mesh, Vec<String>) {
+        self.close_group();
+        let mesh = Mesh::from(self.verts, self.uvws, self.tris, self.quads).with_normals(self.normals).with_groups(self.groups);
+        (mesh, self.mtllibs)
+    }
+}
+
+// names may contain spaces, they are everything after the keyword
+fn rest_of_line<'a>(line: &'a str, keyword: &str) -> &'a str {
+    line.trim_start()[keyword.len()..].trim()
+}
+
+// the longest statement we parse is a quad face, longer ones only need to be recognized as errors
+const MAX_TOKENS : usize = 6;
+
+fn parse_line(line: &str, obj: &mut ObjBuilder) -> Result<i32, String> {
+    let mut tokens = [""; MAX_TOKENS];
+    let mut count = 0;
+    let mut overflow = false;
+    for t in line.split(|x| x == ' ' || x == '\t' || x == '\r').filter(|&x| x != "") {
+        if count == MAX_TOKENS {
+            overflow = true;
+            break
+        }
+        tokens[count] = t;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Result::Ok(0)
+    }
+
+    let parts = &tokens[..count];
     if parts[0].starts_with('#') {
         return Result::Ok(1)
     }
 
+    // names and unknown statements may be arbitrarily long
+    match (overflow, parts[0]) {
+        (true, "v") | (true, "vt") | (true, "vn") | (true, "f") => return Result::Err(String::from("too many components")),
+        _ => ()
+    }
+
     match parts[0] {
-        "v"     => parse_vec3(&parts[1..], verts),
-        "vt"    => parse_vec2(&parts[1..], uvws),
-        "f"     => parse_face(&parts[1..], tris, quads),
+        "v"     => parse_vec3(&parts[1..], &mut obj.verts),
+        "vt"    => parse_uvw(&parts[1..], &mut obj.uvws),
+        "vn"    => parse_vec3(&parts[1..], &mut obj.normals),
+        "f"     => parse_face(&parts[1..], &mut obj.tris, &mut obj.quads),
+        "g" | "o" => {
+            obj.begin_group(Some(rest_of_line(line, parts[0])), None);
+            Ok(0)
+        },
+        "usemtl" => {
+            obj.begin_group(None, Some(rest_of_line(line, parts[0])));
+            Ok(0)
+        },
+        "mtllib" => {
+            obj.mtllibs.push(String::from(rest_of_line(line, parts[0])));
+            Ok(0)
+        },
         _       => Result::Ok(2)
     }
 }
 
+// the mesh and the material libraries it names
+fn parse_obj_lines<R: StreamReader>(reader: &mut R) -> Result<(Mesh, Vec<String>), String> {
+    let mut lines = LineReader::new(reader);
+    let mut obj = ObjBuilder::new();
+    loop {
+        let (line_number, line) =
+            match lines.next_line()? {
+                Some(l) => l,
+                None => break
+            };
+
+        match parse_line(line, &mut obj) {
+            Ok(_) => (),
+            Err(err) => return Err(format!("line {}: {}", line_number, err))
+        }
+    }
+
+    Ok(obj.build())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Groups & materials
+////////////////////////////////////////////////////////////////////////////////
+
+/// a named run of faces sharing a material
+#[derive(Clone)]
+pub struct Group {
+    name        : String,
+    material    : Option<String>,
+    tris        : Range<usize>,
+    quads       : Range<usize>,
+}
+
+impl Group {
+    pub fn new(name: String, material: Option<String>, tris: Range<usize>, quads: Range<usize>) -> Self {
+        Self { name: name, material: material, tris: tris, quads: quads }
+    }
+
+    pub fn name(&self) -> &str { self.name.as_str() }
+    pub fn material(&self) -> Option<&str> { self.material.as_ref().map(|m| m.as_str()) }
+    pub fn tris(&self) -> Range<usize> { self.tris.clone() }
+    pub fn quads(&self) -> Range<usize> { self.quads.clone() }
+}
+
+/// the subset of MTL we read and write
+#[derive(Clone)]
+pub struct Material {
+    pub name        : String,
+    pub ambient     : Vec3f,
+    pub diffuse     : Vec3f,
+    pub specular    : Vec3f,
+    pub shininess   : f32,
+    pub dissolve    : f32,
+    pub diffuse_map : Option<String>,
+    pub bump_map    : Option<String>,
+}
+
+impl Material {
+    pub fn new(name: String) -> Self {
+        Self {
+            name        : name,
+            ambient     : Vec3f::new(0.0, 0.0, 0.0),
+            diffuse     : Vec3f::new(0.8, 0.8, 0.8),
+            specular    : Vec3f::new(0.0, 0.0, 0.0),
+            shininess   : 0.0,
+            dissolve    : 1.0,
+            diffuse_map : None,
+            bump_map    : None,
+        }
+    }
+}
+
+fn parse_f32(parts: &[&str]) -> Result<f32, String> {
+    match parts.get(0).map(|p| p.parse::<f32>()) {
+        Some(Ok(f)) => Ok(f),
+        _ => Err(String::from("float parse error"))
+    }
+}
+
+// `K* r g b` or `K* r`, None for the spectral and xyz forms we do not support
+fn parse_color(parts: &[&str]) -> Result<Option<Vec3f>, String> {
+    match parts.first() {
+        Some(&"spectral") | Some(&"xyz") => return Ok(None),
+        _ => ()
+    }
+
+    if parts.len() == 1 {
+        let f = parse_f32(parts)?;
+        return Ok(Some(Vec3f::new(f, f, f)))
+    }
+
+    let mut colors = Vec::new();
+    parse_vec3(parts, &mut colors)?;
+    Ok(Some(colors[0]))
+}
+
+/// Parses the text of an MTL file. Statements we do not read, spectral and xyz colors and
+/// properties before the first `newmtl` are skipped, malformed numbers are errors.
+pub fn parse_mtl(text: &str) -> Result<Vec<Material>, String> {
+    let mut materials = Vec::<Material>::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let parts : Vec<&str> = line.split(|x| x == ' ' || x == '\t' || x == '\r').filter(|&x| x != "").collect();
+        if parts.len() == 0 || parts[0].starts_with('#') {
+            continue
+        }
+
+        if parts[0] == "newmtl" {
+            materials.push(Material::new(String::from(rest_of_line(line, "newmtl"))));
+            continue
+        }
+
+        let m = match materials.as_mut_slice().last_mut() { Some(m) => m, None => continue };
+        let res =
+            match parts[0] {
+                "Ka"        => parse_color(&parts[1..]).map(|c| m.ambient = c.unwrap_or(m.ambient)),
+                "Kd"        => parse_color(&parts[1..]).map(|c| m.diffuse = c.unwrap_or(m.diffuse)),
+                "Ks"        => parse_color(&parts[1..]).map(|c| m.specular = c.unwrap_or(m.specular)),
+                "Ns"        => parse_f32(&parts[1..]).map(|f| m.shininess = f),
+                "d"         => parse_f32(&parts[1..]).map(|f| m.dissolve = f),
+                "Tr"        => parse_f32(&parts[1..]).map(|f| m.dissolve = 1.0 - f),
+                // options before the file name are not supported, the name is the last part
+                "map_Kd"    => { m.diffuse_map = parts.last().map(|p| String::from(*p)); Ok(()) },
+                "map_Bump" | "map_bump" | "bump" => { m.bump_map = parts.last().map(|p| String::from(*p)); Ok(()) },
+                _ => Ok(())
+            };
+
+        if let Err(err) = res {
+            return Err(format!("line {}: {}", number + 1, err))
+        }
+    }
+
+    Ok(materials)
+}
+
+pub enum MeshIssue {
+    VertexOutOfRange    { face: usize, index: u32 },
+    UVOutOfRange        { face: usize, index: u32 },
+    NormalOutOfRange    { face: usize, index: u32 },
+    InvalidPosition     { vert: usize },
+    DegenerateFace      { face: usize },
+    UnusedVertices      { first: usize, count: usize },
+    NonManifoldEdge     { v0: u32, v1: u32, faces: usize },
+}
+
+impl MeshIssue {
+    /// errors make the mesh unusable, everything else is a warning
+    pub fn is_error(&self) -> bool {
+        match self {
+            MeshIssue::VertexOutOfRange { .. }  => true,
+            MeshIssue::UVOutOfRange { .. }      => true,
+            MeshIssue::NormalOutOfRange { .. }  => true,
+            MeshIssue::InvalidPosition { .. }   => true,
+            _                                   => false,
+        }
+    }
+}
+
+impl core::fmt::Display for MeshIssue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MeshIssue::VertexOutOfRange { face, index } => write!(f, "face {}: vertex index {} out of range", face, index),
+            MeshIssue::UVOutOfRange { face, index }     => write!(f, "face {}: uv index {} out of range", face, index),
+            MeshIssue::NormalOutOfRange { face, index } => write!(f, "face {}: normal index {} out of range", face, index),
+            MeshIssue::InvalidPosition { vert }         => write!(f, "vertex {}: position is not finite", vert),
+            MeshIssue::DegenerateFace { face }          => write!(f, "face {}: degenerate", face),
+            MeshIssue::UnusedVertices { first, count }  => write!(f, "{} unused vertices, first is {}", count, first),
+            MeshIssue::NonManifoldEdge { v0, v1, faces } => write!(f, "edge {}-{}: shared by {} faces", v0, v1, faces),
+        }
+    }
+}
+
+/// Result of `Mesh::validate`. Faces are numbered tris first, then quads.
+pub struct MeshReport {
+    issues  : Vec<MeshIssue>,
+}
+
+impl MeshReport {
+    pub fn issues(&self) -> &[MeshIssue] { self.issues.as_slice() }
+    pub fn error_count(&self) -> usize { self.issues.iter().filter(|i| i.is_error()).count() }
+    pub fn warning_count(&self) -> usize { self.issues.len() - self.error_count() }
+    pub fn is_valid(&self) -> bool { self.error_count() == 0 }
+}
+
+fn is_degenerate(p: &[Vec3f], idx: &[u32]) -> bool {
+    for i in 0..idx.len() {
+        for j in i + 1..idx.len() {
+            if idx[i] == idx[j] { return true }
+        }
+    }
+
+    // a face is degenerate when all of its fan triangles are slivers
+    let p0 = p[idx[0] as usize];
+    for i in 1..idx.len() - 1 {
+        let e1 = p[idx[i] as usize] - p0;
+        let e2 = p[idx[i + 1] as usize] - p0;
+        let area = Vec3f::cross(&e1, &e2).length();
+        if area > f32::EPSILON * e1.length() * e2.length() {
+            return false
+        }
+    }
+    true
+}
+
+fn edge_key(v0: u32, v1: u32) -> u64 {
+    if v0 < v1 { ((v0 as u64) << 32) | v1 as u64 } else { ((v1 as u64) << 32) | v0 as u64 }
+}
+
 pub struct Mesh {
     verts   : Vec<Vec3f>,
     uvws    : Vec<Vec3f>,
+    normals : Vec<Vec3f>,
+    colors  : Vec<Vec4f>,
     tris    : Vec<IdTri>,
-    quads   : Vec<IdQuad>
+    quads   : Vec<IdQuad>,
+    groups  : Vec<Group>,
+    materials : Vec<Material>,
 }
 
 impl Mesh {
 
     pub fn verts(&self) -> &Vec<Vec3f>  { &self.verts }
     pub fn uvws(&self)  -> &Vec<Vec3f>  { &self.uvws }
+    pub fn normals(&self) -> &Vec<Vec3f> { &self.normals }
+    pub fn colors(&self) -> &Vec<Vec4f> { &self.colors }
     pub fn tris(&self)  -> &Vec<IdTri>  { &self.tris }
     pub fn quads(&self) -> &Vec<IdQuad> { &self.quads }
+    pub fn groups(&self) -> &[Group]    { self.groups.as_slice() }
+    pub fn materials(&self) -> &[Material] { self.materials.as_slice() }
+
+    pub fn verts_mut(&mut self) -> &mut [Vec3f]     { self.verts.as_mut_slice() }
+    pub fn normals_mut(&mut self) -> &mut [Vec3f]   { self.normals.as_mut_slice() }
+    pub fn tris_mut(&mut self)  -> &mut [IdTri]     { self.tris.as_mut_slice() }
+    pub fn quads_mut(&mut self) -> &mut [IdQuad]    { self.quads.as_mut_slice() }
 
 
     pub fn from(verts: Vec<Vec3f>, uvws: Vec<Vec3f>, tris: Vec<IdTri>, quads: Vec<IdQuad>) -> Self {
-        Self { verts: verts, uvws: uvws, tris: tris, quads: quads }
+        Self { verts: verts, uvws: uvws, normals: Vec::new(), colors: Vec::new(), tris: tris, quads: quads, groups: Vec::new(), materials: Vec::new() }
     }
 
+    /// normals referenced by the faces' `norms` indices
+    pub fn with_normals(mut self, normals: Vec<Vec3f>) -> Self {
+        self.normals = normals;
+        self
+    }
+
+    /// per vertex RGBA colors, parallel to `verts`
+    pub fn with_colors(mut self, colors: Vec<Vec4f>) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// face ranges into `tris` and `quads`, no groups means a single unnamed one
+    pub fn with_groups(mut self, groups: Vec<Group>) -> Self {
+        self.groups = groups;
+        self
+    }
 
+    /// materials referenced by name from the groups
+    pub fn with_materials(mut self, materials: Vec<Material>) -> Self {
+        self.materials = materials;
+        self
+    }
+
+    /// OBJ puts the uv origin at the bottom left, flip v for images stored top row first
+    // the viewer does not texture its models yet
+    #[allow(dead_code)]
+    pub fn flip_v(&mut self) {
+        for uv in self.uvws.iter_mut() {
+            uv.y = 1.0 - uv.y;
+        }
+    }
+
+
+    /// Reads an OBJ file and the MTL libraries it references
     pub fn read_obj(path: &str) -> Result<Mesh, String> {
-        let file = File::open(path);
-        match file {
-            Ok(_) => (),
-            Err(_) => return Err(String::from("Could not open file"))
+        let mut file =
+            match File::open(path) {
+                Ok(f) => f,
+                Err(_) => return Err(String::from("Could not open file"))
+            };
+
+        // material libraries are relative to the obj, one that is missing or does not parse
+        // leaves the mesh without its materials and prints a warning
+        let (mesh, mtllibs) = parse_obj_lines(&mut file)?;
+        let dir = match path.rfind('/') { Some(p) => &path[..p + 1], None => "" };
+        let mut materials = Vec::new();
+        for lib in mtllibs.iter() {
+            let mut mtl_path = String::from(dir);
+            mtl_path.push_str(lib.as_str());
+            let parsed = read_bytes(mtl_path.as_str()).and_then(|mut bytes| parse_mtl(lossy_utf8(bytes.as_mut_slice())));
+            match parsed {
+                Ok(mats) => {
+                    for m in mats.iter() {
+                        materials.push(m.clone());
+                    }
+                },
+                Err(err) => println!("warning   : {}: {}, materials skipped", mtl_path.as_str(), err)
+            }
+        }
+
+        Ok(mesh.with_materials(materials))
+    }
+
+    /// Parses an OBJ held in memory (`&[u8]`, `&str`, `include_bytes!`...). `mtllib` statements
+    /// are ignored, attach materials with `with_materials(parse_mtl(..)?)`.
+    #[allow(dead_code)]
+    pub fn parse_obj<D: AsRef<[u8]> + ?Sized>(data: &D) -> Result<Mesh, String> {
+        let mut reader = SliceReader { data: data.as_ref(), pos: 0 };
+        Self::parse_obj_stream(&mut reader)
+    }
+
+    /// Parses an OBJ from any stream, `mtllib` statements are ignored like in `parse_obj`
+    #[allow(dead_code)]
+    pub fn parse_obj_stream<R: StreamReader>(reader: &mut R) -> Result<Mesh, String> {
+        parse_obj_lines(reader).map(|(mesh, _)| mesh)
+    }
+
+
+    pub fn validate(&self) -> MeshReport {
+        let mut issues = Vec::new();
+        let mut used = Vec::with_capacity(self.verts.len());
+        for _ in 0..self.verts.len() {
+            used.push(false);
+        }
+
+        for (i, v) in self.verts.iter().enumerate() {
+            if !v.x.is_finite() || !v.y.is_finite() || !v.z.is_finite() {
+                issues.push(MeshIssue::InvalidPosition { vert: i });
+            }
         }
 
-        let mut f = file.unwrap();
-        let mut lines = String::new();
-        f.read_to_string(&mut lines).unwrap();
+        let mut edges = Vec::<u64>::new();
+        let faces = self.tris.iter().map(|t| (&t.verts[..], &t.uvs[..], &t.norms[..]))
+            .chain(self.quads.iter().map(|q| (&q.verts[..], &q.uvs[..], &q.norms[..])));
 
-        let mut verts = Vec::<Vec3f>::new();
-        let mut uvws = Vec::<Vec3f>::new();
-        let mut tris = Vec::<IdTri>::new();
-        let mut quads = Vec::<IdQuad>::new();
+        for (f, (verts, uvs, norms)) in faces.enumerate() {
+            let mut in_range = true;
+            for v in verts {
+                if *v as usize >= self.verts.len() {
+                    issues.push(MeshIssue::VertexOutOfRange { face: f, index: *v });
+                    in_range = false;
+                }
+            }
+
+            for uv in uvs {
+                if *uv != NO_INDEX && *uv as usize >= self.uvws.len() {
+                    issues.push(MeshIssue::UVOutOfRange { face: f, index: *uv });
+                }
+            }
+
+            for n in norms {
+                if *n != NO_INDEX && *n as usize >= self.normals.len() {
+                    issues.push(MeshIssue::NormalOutOfRange { face: f, index: *n });
+                }
+            }
 
-        for l in lines.lines() {
-            match parse_line(l.as_str(), &mut verts, &mut uvws, &mut tris, &mut quads) {
-                Ok(_) => (),
-                Err(err) => return Err(err)
+            if !in_range {
+                continue
+            }
+
+            for i in 0..verts.len() {
+                used[verts[i] as usize] = true;
+                edges.push(edge_key(verts[i], verts[(i + 1) % verts.len()]));
+            }
+
+            if is_degenerate(self.verts.as_slice(), verts) {
+                issues.push(MeshIssue::DegenerateFace { face: f });
             }
         }
 
-        Ok(Mesh::from(verts, uvws, tris, quads))
+        // one issue for all of them, point clouds would otherwise report every vertex
+        let count = used.iter().filter(|u| !**u).count();
+        if let Some(first) = used.iter().position(|u| !*u) {
+            issues.push(MeshIssue::UnusedVertices { first: first, count: count });
+        }
+
+        let edges = edges.as_mut_slice();
+        edges.sort_unstable();
+        let mut i = 0;
+        while i < edges.len() {
+            let mut j = i + 1;
+            while j < edges.len() && edges[j] == edges[i] {
+                j += 1;
+            }
+
+            if j - i > 2 {
+                issues.push(MeshIssue::NonManifoldEdge { v0: (edges[i] >> 32) as u32, v1: edges[i] as u32, faces: j - i });
+            }
+            i = j;
+        }
+
+        MeshReport { issues: issues }
     }
 }
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct GPUVertex {
     pub pos     : Vec3f,
     pub normal  : Vec3f,
     pub uv      : Vec2f,
 }
 
+pub enum GPUIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl GPUIndices {
+    /// picks 16bit indices whenever every vertex is addressable with them
+    pub fn from_u32(indices: Vec<u32>, vert_count: usize) -> Self {
+        if vert_count > 0x10000 {
+            return GPUIndices::U32(indices)
+        }
+
+        let mut v = Vec::with_capacity(indices.len());
+        for i in indices.iter() {
+            v.push(*i as u16);
+        }
+        GPUIndices::U16(v)
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            GPUIndices::U16(v) => v.len(),
+            GPUIndices::U32(v) => v.len(),
+        }
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            GPUIndices::U16(v) => v[i] as u32,
+            GPUIndices::U32(v) => v[i],
+        }
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        let mut v = Vec::with_capacity(self.len());
+        for i in 0..self.len() {
+            v.push(self.get(i));
+        }
+        v
+    }
+}
+
 pub struct GPUMesh {
     verts   : Vec<GPUVertex>,
-    tris    : Vec<u32>,
+    tris    : GPUIndices,
+    submeshes : Vec<SubMesh>,
 }
 
-impl GPUMesh {
-    pub fn from(mesh: &Mesh) -> Self {
-        let mut gpv = Vec::new();
-        let mut tris = Vec::new();
+fn fetch(arr: &[Vec3f], idx: u32) -> Result<Vec3f, String> {
+    if (idx as usize) < arr.len() {
+        Ok(arr[idx as usize])
+    } else {
+        Err(format!("index {} out of range ({} entries)", idx, arr.len()))
+    }
+}
+
+fn fetch_uv(arr: &[Vec3f], idx: u32) -> Result<Vec2f, String> {
+    if idx == NO_INDEX {
+        return Ok(Vec2f::new(0.0, 0.0))
+    }
+
+    let uvw = fetch(arr, idx)?;
+    Ok(Vec2f::new(uvw.x, uvw.y))
+}
+
+fn fetch_normal(arr: &[Vec3f], idx: u32, face_normal: &Vec3f) -> Result<Vec3f, String> {
+    if idx == NO_INDEX {
+        return Ok(*face_normal)
+    }
 
-        for t in mesh.tris().iter() {
-            let v0 = mesh.verts[t.verts[0] as usize];
-            let v1 = mesh.verts[t.verts[1] as usize];
-            let v2 = mesh.verts[t.verts[2] as usize];
+    let n = fetch(arr, idx)?;
+    Ok(Vec3f::normalize(&n))
+}
 
-            let n = rs_math3d::tri_normal(&v0, &v1, &v2);
+// bit exact key of a GPUVertex, with -0.0 folded into 0.0
+#[derive(Clone, Copy, PartialEq)]
+struct VertexKey([u32; 8]);
 
-            let uv0 = mesh.verts[t.uvs[0] as usize];
-            let uv1 = mesh.verts[t.uvs[1] as usize];
-            let uv2 = mesh.verts[t.uvs[2] as usize];
+impl VertexKey {
+    fn bits(f: f32) -> u32 { if f == 0.0 { 0 } else { f.to_bits() } }
 
-            let idx = gpv.len();
-            gpv.push(GPUVertex { pos: v0, normal: n.clone(), uv: Vec2f::new(uv0.x, uv0.y) });
-            gpv.push(GPUVertex { pos: v1, normal: n.clone(), uv: Vec2f::new(uv1.x, uv1.y) });
-            gpv.push(GPUVertex { pos: v2, normal: n.clone(), uv: Vec2f::new(uv2.x, uv2.y) });
+    fn new(v: &GPUVertex) -> Self {
+        Self([Self::bits(v.pos.x), Self::bits(v.pos.y), Self::bits(v.pos.z),
+              Self::bits(v.normal.x), Self::bits(v.normal.y), Self::bits(v.normal.z),
+              Self::bits(v.uv.x), Self::bits(v.uv.y)])
+    }
+}
 
-            tris.push(idx as u32);
-            tris.push(idx as u32 + 1);
-            tris.push(idx as u32 + 2);
+impl rs_alloc::hash::Hash for VertexKey {
+    fn hash(&self) -> usize {
+        let bytes = unsafe { core::slice::from_raw_parts(self.0.as_ptr() as *const u8, 32) };
+        bytes.hash()
+    }
+}
+
+struct VertexWelder {
+    verts   : Vec<GPUVertex>,
+    indices : Vec<u32>,
+    map     : HashMap<VertexKey, u32>,
+}
+
+impl VertexWelder {
+    fn new() -> Self { Self { verts: Vec::new(), indices: Vec::new(), map: HashMap::new() } }
+
+    fn push(&mut self, v: GPUVertex) {
+        let key = VertexKey::new(&v);
+        let idx =
+            match self.map.get(key) {
+                Some(idx) => *idx,
+                None => {
+                    let idx = self.verts.len() as u32;
+                    self.map.set(key, idx);
+                    self.verts.push(v);
+                    idx
+                }
+            };
+        self.indices.push(idx);
+    }
+
+    fn finish(self) -> GPUMesh {
+        let count = self.verts.len();
+        GPUMesh::new(self.verts, GPUIndices::from_u32(self.indices, count))
+    }
+}
+
+/// a range of `GPUMesh::tris` drawn with one material
+#[derive(Clone)]
+pub struct SubMesh {
+    name        : String,
+    material    : Option<String>,
+    first       : usize,
+    count       : usize,
+}
+
+impl SubMesh {
+    pub fn new(name: String, material: Option<String>, first: usize, count: usize) -> Self {
+        Self { name: name, material: material, first: first, count: count }
+    }
+
+    pub fn name(&self) -> &str { self.name.as_str() }
+    pub fn material(&self) -> Option<&str> { self.material.as_ref().map(|m| m.as_str()) }
+    /// first index and index count, in indices not triangles
+    pub fn first(&self) -> usize { self.first }
+    pub fn count(&self) -> usize { self.count }
+
+    /// the index range, None when it does not fit in `index_count` indices or is not whole triangles
+    pub fn range(&self, index_count: usize) -> Option<Range<usize>> {
+        match self.first.checked_add(self.count) {
+            Some(end) if end <= index_count && self.count % 3 == 0 => Some(self.first..end),
+            _ => None
         }
+    }
+}
+
+fn weld_tri(mesh: &Mesh, t: &IdTri, welder: &mut VertexWelder) -> Result<(), String> {
+    let v0 = fetch(mesh.verts.as_slice(), t.verts[0])?;
+    let v1 = fetch(mesh.verts.as_slice(), t.verts[1])?;
+    let v2 = fetch(mesh.verts.as_slice(), t.verts[2])?;
+
+    let n = rs_math3d::tri_normal(&v0, &v1, &v2);
+    let n0 = fetch_normal(mesh.normals.as_slice(), t.norms[0], &n)?;
+    let n1 = fetch_normal(mesh.normals.as_slice(), t.norms[1], &n)?;
+    let n2 = fetch_normal(mesh.normals.as_slice(), t.norms[2], &n)?;
+
+    let uv0 = fetch_uv(mesh.uvws.as_slice(), t.uvs[0])?;
+    let uv1 = fetch_uv(mesh.uvws.as_slice(), t.uvs[1])?;
+    let uv2 = fetch_uv(mesh.uvws.as_slice(), t.uvs[2])?;
+
+    welder.push(GPUVertex { pos: v0, normal: n0, uv: uv0 });
+    welder.push(GPUVertex { pos: v1, normal: n1, uv: uv1 });
+    welder.push(GPUVertex { pos: v2, normal: n2, uv: uv2 });
+    Ok(())
+}
+
+fn weld_quad(mesh: &Mesh, q: &IdQuad, welder: &mut VertexWelder) -> Result<(), String> {
+    let v0 = fetch(mesh.verts.as_slice(), q.verts[0])?;
+    let v1 = fetch(mesh.verts.as_slice(), q.verts[1])?;
+    let v2 = fetch(mesh.verts.as_slice(), q.verts[2])?;
+    let v3 = fetch(mesh.verts.as_slice(), q.verts[3])?;
+
+    let n = rs_math3d::quad_normal(&v0, &v1, &v2, &v3);
+    let n0 = fetch_normal(mesh.normals.as_slice(), q.norms[0], &n)?;
+    let n1 = fetch_normal(mesh.normals.as_slice(), q.norms[1], &n)?;
+    let n2 = fetch_normal(mesh.normals.as_slice(), q.norms[2], &n)?;
+    let n3 = fetch_normal(mesh.normals.as_slice(), q.norms[3], &n)?;
+
+    let uv0 = fetch_uv(mesh.uvws.as_slice(), q.uvs[0])?;
+    let uv1 = fetch_uv(mesh.uvws.as_slice(), q.uvs[1])?;
+    let uv2 = fetch_uv(mesh.uvws.as_slice(), q.uvs[2])?;
+    let uv3 = fetch_uv(mesh.uvws.as_slice(), q.uvs[3])?;
+
+    let gv0 = GPUVertex { pos: v0, normal: n0, uv: uv0 };
+    let gv1 = GPUVertex { pos: v1, normal: n1, uv: uv1 };
+    let gv2 = GPUVertex { pos: v2, normal: n2, uv: uv2 };
+    let gv3 = GPUVertex { pos: v3, normal: n3, uv: uv3 };
+
+    welder.push(gv0);
+    welder.push(gv1);
+    welder.push(gv2);
 
-        for q in mesh.quads().iter() {
-            let v0 = mesh.verts[q.verts[0] as usize];
-            let v1 = mesh.verts[q.verts[1] as usize];
-            let v2 = mesh.verts[q.verts[2] as usize];
-            let v3 = mesh.verts[q.verts[3] as usize];
+    welder.push(gv2);
+    welder.push(gv3);
+    welder.push(gv0);
+    Ok(())
+}
+
+impl GPUMesh {
+    pub fn new(verts: Vec<GPUVertex>, tris: GPUIndices) -> Self { Self { verts: verts, tris: tris, submeshes: Vec::new() } }
+
+    /// index ranges per material, no submeshes means the whole index buffer is drawn at once
+    /// the ranges are not checked here, users of the submeshes skip the ones that do not fit
+    /// in the indices (see `SubMesh::range`)
+    pub fn with_submeshes(mut self, submeshes: Vec<SubMesh>) -> Self {
+        self.submeshes = submeshes;
+        self
+    }
 
-            let n = rs_math3d::quad_normal(&v0, &v1, &v2, &v3);
+    /// builds indexed geometry, welding identical (position, normal, uv) vertices,
+    /// every group of the mesh becomes a submesh
+    pub fn from(mesh: &Mesh) -> Result<Self, String> {
+        let mut welder = VertexWelder::new();
+        let mut submeshes = Vec::new();
 
-            let uv0 = mesh.verts[q.uvs[0] as usize];
-            let uv1 = mesh.verts[q.uvs[1] as usize];
-            let uv2 = mesh.verts[q.uvs[2] as usize];
-            let uv3 = mesh.verts[q.uvs[3] as usize];
+        if mesh.groups().len() == 0 {
+            for t in mesh.tris().iter() {
+                weld_tri(mesh, t, &mut welder)?;
+            }
 
-            let idx = gpv.len();
-            gpv.push(GPUVertex { pos: v0, normal: n.clone(), uv: Vec2f::new(uv0.x, uv0.y) });
-            gpv.push(GPUVertex { pos: v1, normal: n.clone(), uv: Vec2f::new(uv1.x, uv1.y) });
-            gpv.push(GPUVertex { pos: v2, normal: n.clone(), uv: Vec2f::new(uv2.x, uv2.y) });
-            gpv.push(GPUVertex { pos: v3, normal: n.clone(), uv: Vec2f::new(uv3.x, uv3.y) });
+            for q in mesh.quads().iter() {
+                weld_quad(mesh, q, &mut welder)?;
+            }
+        } else {
+            for g in mesh.groups().iter() {
+                if g.tris().end > mesh.tris.len() || g.quads().end > mesh.quads.len() {
+                    return Err(String::from("group range out of the mesh faces"))
+                }
 
-            tris.push(idx as u32);
-            tris.push(idx as u32 + 1);
-            tris.push(idx as u32 + 2);
+                let first = welder.indices.len();
+                for t in mesh.tris.as_slice()[g.tris()].iter() {
+                    weld_tri(mesh, t, &mut welder)?;
+                }
 
-            tris.push(idx as u32 + 2);
-            tris.push(idx as u32 + 3);
-            tris.push(idx as u32);
+                for q in mesh.quads.as_slice()[g.quads()].iter() {
+                    weld_quad(mesh, q, &mut welder)?;
+                }
+                submeshes.push(SubMesh::new(String::from(g.name()), g.material().map(|m| String::from(m)), first, welder.indices.len() - first));
+            }
         }
 
-        Self { verts: gpv, tris: tris }
+        Ok(welder.finish().with_submeshes(submeshes))
     }
 
     pub fn verts(&self) -> &[GPUVertex] { self.verts.as_slice() }
-    pub fn tris(&self) -> &[u32] { self.tris.as_slice() }
-}
\ No newline at end of file
+    pub fn tris(&self) -> &GPUIndices { &self.tris }
+    pub fn verts_mut(&mut self) -> &mut [GPUVertex] { self.verts.as_mut_slice() }
+    pub fn tris_mut(&mut self) -> &mut GPUIndices { &mut self.tris }
+    pub fn submeshes(&self) -> &[SubMesh] { self.submeshes.as_slice() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cargo runs the tests from the package root
+    const TEXTURED_QUAD : &str = "tests/fixtures/textured_quad.obj";
+
+    // rs_alloc's String is not Debug, unwrap would not show the message
+    fn ok<T>(r: Result<T, String>) -> T {
+        match r {
+            Ok(v) => v,
+            Err(err) => panic!("{}", err.as_str())
+        }
+    }
+
+    fn uv_at(m: &GPUMesh, x: f32, y: f32) -> Vec2f {
+        let v = m.verts().iter().find(|v| v.pos.x == x && v.pos.y == y).unwrap();
+        v.uv
+    }
+
+    #[test]
+    fn uvs_come_from_vt() {
+        let m = ok(Mesh::read_obj(TEXTURED_QUAD));
+        assert_eq!(m.uvws().len(), 4);
+        assert_eq!(m.uvws()[1].z, 0.5);
+        assert_eq!(m.uvws()[3].z, 0.0);
+
+        let g = ok(GPUMesh::from(&m));
+        assert_eq!(g.verts().len(), 4);
+        assert_eq!(g.tris().len(), 6);
+        assert_eq!((uv_at(&g, 0.0, 0.0).x, uv_at(&g, 0.0, 0.0).y), (0.0, 0.0));
+        assert_eq!((uv_at(&g, 1.0, 0.0).x, uv_at(&g, 1.0, 0.0).y), (0.25, 0.0));
+        assert_eq!((uv_at(&g, 1.0, 1.0).x, uv_at(&g, 1.0, 1.0).y), (0.25, 0.75));
+        assert_eq!((uv_at(&g, 0.0, 1.0).x, uv_at(&g, 0.0, 1.0).y), (0.0, 0.75));
+    }
+
+    #[test]
+    fn flip_v() {
+        let mut m = ok(Mesh::read_obj(TEXTURED_QUAD));
+        m.flip_v();
+        let g = ok(GPUMesh::from(&m));
+        assert_eq!((uv_at(&g, 1.0, 0.0).x, uv_at(&g, 1.0, 0.0).y), (0.25, 1.0));
+        assert_eq!((uv_at(&g, 0.0, 1.0).x, uv_at(&g, 0.0, 1.0).y), (0.0, 0.25));
+    }
+
+    #[test]
+    fn mtl_forms() {
+        let mats = ok(parse_mtl("Kd 1 0 0\nnewmtl grey\nKd 0.5\nKa spectral file.rfl\nKs xyz 1 1 1\nillum 2\n"));
+        assert_eq!(mats.len(), 1);
+        assert_eq!((mats[0].diffuse.x, mats[0].diffuse.y, mats[0].diffuse.z), (0.5, 0.5, 0.5));
+        assert_eq!(mats[0].ambient.x, 0.0);
+        assert!(parse_mtl("newmtl bad\nNs shiny\n").is_err());
+    }
+
+    #[test]
+    fn submesh_ranges() {
+        let quad = ok(GPUMesh::from(&ok(Mesh::read_obj(TEXTURED_QUAD))));
+        let mut subs = Vec::new();
+        subs.push(SubMesh::new(String::from("ok"), None, 0, 3));
+        subs.push(SubMesh::new(String::from("past"), None, 3, 6));
+        subs.push(SubMesh::new(String::from("wraps"), None, usize::MAX, 2));
+        let mut m = GPUMesh::new(quad.verts().iter().cloned().collect(), GPUIndices::from_u32(quad.tris().to_u32(), 4)).with_submeshes(subs);
+
+        assert_eq!(m.submeshes()[0].range(6), Some(0..3));
+        assert_eq!(m.submeshes()[1].range(6), None);
+        assert_eq!(m.submeshes()[2].range(6), None);
+        assert_eq!(m.stats().submeshes().len(), 1);
+        ok(m.optimize(Some(1.05)));
+        assert_eq!(m.tris().len(), 6);
+        assert_eq!(m.split_by_connectivity().len(), 1);
+    }
+
+    #[test]
+    fn non_utf8_text() {
+        let m = ok(Mesh::parse_obj(&b"# caf\xe9\ng caf\xe9\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n"[..]));
+        assert_eq!(m.groups()[0].name(), "caf?");
+        assert!(Mesh::parse_obj(&b"v 0 0 0\xe9\n"[..]).is_err());
+    }
+
+    #[test]
+    fn physical_line_numbers() {
+        match Mesh::parse_obj("v 0 \\\n  0 0\r\n\nv 1 \\\n 0\\\n 0\nvt x\n") {
+            Ok(_) => panic!("vt x parsed"),
+            Err(err) => assert_eq!(err.as_str(), "line 7: float parse error")
+        }
+    }
+
+    #[test]
+    fn vt_component_count() {
+        assert!(Mesh::parse_obj("vt 0.5\n").is_ok());
+        assert!(Mesh::parse_obj("vt 0.1 0.2 0.3 0.4\n").is_err());
+        assert!(Mesh::parse_obj("vt\n").is_err());
+    }
+
+    #[test]
+    fn unused_vertices_aggregate() {
+        let m = ok(Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 5 5 5\nv 6 6 6\nv 7 7 7\nf 1 2 3\n"));
+        let report = m.validate();
+        assert!(report.is_valid());
+        assert_eq!(report.issues().len(), 1);
+        match report.issues()[0] {
+            MeshIssue::UnusedVertices { first, count } => assert_eq!((first, count), (3, 3)),
+            _ => panic!("expected unused vertices")
+        }
+    }
+}
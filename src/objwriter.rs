@@ -0,0 +1,231 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use core::result::Result;
+use core::fmt::Write;
+use rs_alloc::*;
+use rs_streams::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+fn write_file(path: &str, text: &String) -> Result<(), String> {
+    let mut f =
+        match File::create(path) {
+            Ok(f) => f,
+            Err(_) => return Err(String::from("Could not create file"))
+        };
+
+    match f.write(text.as_bytes()) {
+        Ok(n) if n == text.len() => Ok(()),
+        _ => Err(String::from("Could not write file"))
+    }
+}
+
+fn write_vec3(out: &mut String, tag: &str, v: &Vec3f, precision: usize) {
+    let _ = writeln!(out, "{} {:.*} {:.*} {:.*}", tag, precision, v.x, precision, v.y, precision, v.z);
+}
+
+// v, v/vt, v//vn or v/vt/vn depending on which indices the corner has
+fn write_corner(out: &mut String, v: u32, uv: u32, n: u32) {
+    let _ = write!(out, " {}", v + 1);
+    if uv != NO_INDEX || n != NO_INDEX {
+        out.push_str("/");
+    }
+    if uv != NO_INDEX {
+        let _ = write!(out, "{}", uv + 1);
+    }
+    if n != NO_INDEX {
+        let _ = write!(out, "/{}", n + 1);
+    }
+}
+
+fn write_faces(out: &mut String, tris: &[IdTri], quads: &[IdQuad]) {
+    for t in tris.iter() {
+        out.push_str("f");
+        for i in 0..3 {
+            write_corner(out, t.verts()[i], t.uvs()[i], t.norms()[i]);
+        }
+        out.push_str("\n");
+    }
+
+    for q in quads.iter() {
+        out.push_str("f");
+        for i in 0..4 {
+            write_corner(out, q.verts()[i], q.uvs()[i], q.norms()[i]);
+        }
+        out.push_str("\n");
+    }
+}
+
+impl Mesh {
+    /// Writes the mesh as OBJ with `precision` decimals, and `<name>.mtl` next to it when the mesh has materials
+    pub fn write_obj(&self, path: &str, precision: usize) -> Result<(), String> {
+        if self.materials().len() == 0 {
+            return write_file(path, &self.to_obj(None, precision)?)
+        }
+
+        let stem = match path.rfind('.') { Some(p) if !path[p..].contains('/') => &path[..p], _ => path };
+        let mut mtl_path = String::from(stem);
+        mtl_path.push_str(".mtl");
+        self.write_mtl(mtl_path.as_str(), precision)?;
+
+        let file_name = match mtl_path.as_str().rfind('/') { Some(p) => &mtl_path.as_str()[p + 1..], None => mtl_path.as_str() };
+        write_file(path, &self.to_obj(Some(file_name), precision)?)
+    }
+
+    /// The OBJ text `write_obj` saves, referencing the `mtllib` material library if given
+    pub fn to_obj(&self, mtllib: Option<&str>, precision: usize) -> Result<String, String> {
+        let mut out = String::new();
+        match mtllib {
+            Some(lib) => { let _ = writeln!(out, "mtllib {}", lib); },
+            None => ()
+        }
+
+        for v in self.verts().iter() {
+            write_vec3(&mut out, "v", v, precision);
+        }
+
+        for uv in self.uvws().iter() {
+            if uv.z == 0.0 {
+                let _ = writeln!(out, "vt {:.*} {:.*}", precision, uv.x, precision, uv.y);
+            } else {
+                write_vec3(&mut out, "vt", uv, precision);
+            }
+        }
+
+        for n in self.normals().iter() {
+            write_vec3(&mut out, "vn", n, precision);
+        }
+
+        let tris = self.tris().as_slice();
+        let quads = self.quads().as_slice();
+        if self.groups().len() == 0 {
+            write_faces(&mut out, tris, quads);
+        } else {
+            let mut material = None;
+            for g in self.groups().iter() {
+                // slices of an empty rs_alloc Vec have a null pointer, so no `get` into an Option here
+                let (gt, gq) = (g.tris(), g.quads());
+                if gt.start > gt.end || gt.end > tris.len() || gq.start > gq.end || gq.end > quads.len() {
+                    return Err(String::from("group range out of the mesh faces"))
+                }
+
+                if g.name() != "" {
+                    let _ = writeln!(out, "g {}", g.name());
+                }
+                if g.material().is_some() && g.material() != material {
+                    let _ = writeln!(out, "usemtl {}", g.material().unwrap());
+                    material = g.material();
+                }
+                write_faces(&mut out, &tris[gt], &quads[gq]);
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Writes only the materials as an MTL file
+    pub fn write_mtl(&self, path: &str, precision: usize) -> Result<(), String> {
+        write_file(path, &self.to_mtl(precision))
+    }
+
+    /// The MTL text `write_mtl` saves
+    pub fn to_mtl(&self, precision: usize) -> String {
+        let mut out = String::new();
+        for m in self.materials().iter() {
+            let _ = writeln!(out, "newmtl {}", m.name);
+            write_vec3(&mut out, "Ka", &m.ambient, precision);
+            write_vec3(&mut out, "Kd", &m.diffuse, precision);
+            write_vec3(&mut out, "Ks", &m.specular, precision);
+            let _ = writeln!(out, "Ns {:.*}", precision, m.shininess);
+            let _ = writeln!(out, "d {:.*}", precision, m.dissolve);
+            match &m.diffuse_map {
+                Some(map) => { let _ = writeln!(out, "map_Kd {}", map); },
+                None => ()
+            }
+            match &m.bump_map {
+                Some(map) => { let _ = writeln!(out, "map_Bump {}", map); },
+                None => ()
+            }
+            out.push_str("\n");
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_parse_round_trip() {
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0 0.5\nvt 1 1\nvn 0 0 1\n\
+                   g left\nusemtl red\nf 1/1/1 2/2/1 3/3/1\ng right\nusemtl blue\nf 1/1/1 2/2/1 3/3/1 4/3/1\n";
+        let mut m = Mesh::parse_obj(src).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let mut red = Material::new(String::from("red"));
+        red.diffuse = Vec3f::new(1.0, 0.0, 0.0);
+        red.diffuse_map = Some(String::from("red.png"));
+        let mut mats = Vec::new();
+        mats.push(red);
+        mats.push(Material::new(String::from("blue")));
+        m = m.with_materials(mats);
+
+        let text = m.to_obj(Some("round.mtl"), 6).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let r = Mesh::parse_obj(text.as_str()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(r.verts().len(), 4);
+        assert_eq!(r.uvws().len(), 3);
+        assert_eq!(r.uvws()[1].z, 0.5);
+        assert_eq!(r.normals().len(), 1);
+        for (a, b) in m.verts().iter().zip(r.verts().iter()) {
+            assert_eq!((a.x, a.y, a.z), (b.x, b.y, b.z));
+        }
+        assert_eq!(r.tris().len(), 1);
+        assert_eq!(r.quads().len(), 1);
+        assert_eq!(r.tris()[0].verts(), m.tris()[0].verts());
+        assert_eq!(r.quads()[0].uvs(), m.quads()[0].uvs());
+        assert_eq!(r.groups().len(), 2);
+        assert_eq!(r.groups()[1].name(), "right");
+        assert_eq!(r.groups()[1].material(), Some("blue"));
+        assert_eq!(r.groups()[1].quads(), 0..1);
+
+        let mats = parse_mtl(m.to_mtl(6).as_str()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(mats.len(), 2);
+        assert_eq!(mats[0].name.as_str(), "red");
+        assert_eq!(mats[0].diffuse.x, 1.0);
+        assert_eq!(mats[0].diffuse_map.as_ref().map(|s| s.as_str()), Some("red.png"));
+    }
+
+    #[test]
+    fn quads_only() {
+        let m = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\ng quad\nf 1 2 3 4\n").unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let text = m.to_obj(None, 1).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert!(text.as_str().ends_with("g quad\nf 1 2 3 4\n"));
+    }
+}
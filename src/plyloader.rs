@@ -0,0 +1,535 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use core::result::Result;
+use rs_alloc::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLE,
+    BinaryBE,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "char"  | "int8"    => Ok(Scalar::I8),
+            "uchar" | "uint8"   => Ok(Scalar::U8),
+            "short" | "int16"   => Ok(Scalar::I16),
+            "ushort"| "uint16"  => Ok(Scalar::U16),
+            "int"   | "int32"   => Ok(Scalar::I32),
+            "uint"  | "uint32"  => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double"| "float64" => Ok(Scalar::F64),
+            _ => Err(format!("unknown property type {}", s))
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8     => 1,
+            Scalar::I16 | Scalar::U16   => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64                 => 8,
+        }
+    }
+
+    // maximum of the integer range, colors are normalized by it
+    fn unit(&self) -> f64 {
+        match self {
+            Scalar::U8  => 255.0,
+            Scalar::U16 => 65535.0,
+            _           => 1.0,
+        }
+    }
+}
+
+enum Property {
+    Scalar  { name: String, ty: Scalar },
+    List    { name: String, count: Scalar, item: Scalar },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } => name.as_str(),
+            Property::List { name, .. } => name.as_str(),
+        }
+    }
+}
+
+struct Element {
+    name    : String,
+    count   : usize,
+    props   : Vec<Property>,
+}
+
+impl Element {
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        for (i, p) in self.props.iter().enumerate() {
+            for n in names {
+                if p.name() == *n {
+                    return Some(i)
+                }
+            }
+        }
+        None
+    }
+}
+
+struct Header {
+    format      : Format,
+    elements    : Vec<Element>,
+    data_start  : usize,
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, String> {
+    if !data.starts_with(b"ply\n") && !data.starts_with(b"ply\r\n") {
+        return Err(String::from("not a ply file"))
+    }
+
+    let mut format = None;
+    let mut elements = Vec::<Element>::new();
+    let mut pos = 0;
+
+    loop {
+        let start = pos;
+        while pos < data.len() && data[pos] != b'\n' {
+            pos += 1;
+        }
+        if pos >= data.len() {
+            return Err(String::from("missing end_header"))
+        }
+        pos += 1;
+
+        let line =
+            match core::str::from_utf8(&data[start..pos - 1]) {
+                Ok(l) => l.trim(),
+                Err(_) => return Err(String::from("invalid header"))
+            };
+
+        let parts : Vec<&str> = line.split(|x| x == ' ' || x == '\t').filter(|&x| x != "").collect();
+        if parts.len() == 0 {
+            continue
+        }
+
+        match parts[0] {
+            "ply" | "comment" | "obj_info" => (),
+            "format" if parts.len() == 3 => {
+                format =
+                    match parts[1] {
+                        "ascii"                 => Some(Format::Ascii),
+                        "binary_little_endian"  => Some(Format::BinaryLE),
+                        "binary_big_endian"     => Some(Format::BinaryBE),
+                        _ => return Err(format!("unknown format {}", parts[1]))
+                    };
+            },
+            "element" if parts.len() == 3 => {
+                let count =
+                    match parts[2].parse::<usize>() {
+                        Ok(c) => c,
+                        Err(_) => return Err(String::from("invalid element count"))
+                    };
+                elements.push(Element { name: String::from(parts[1]), count: count, props: Vec::new() });
+            },
+            "property" if parts.len() == 3 || (parts.len() == 5 && parts[1] == "list") => {
+                let prop =
+                    if parts[1] == "list" {
+                        Property::List { name: String::from(parts[4]), count: Scalar::parse(parts[2])?, item: Scalar::parse(parts[3])? }
+                    } else {
+                        Property::Scalar { name: String::from(parts[2]), ty: Scalar::parse(parts[1])? }
+                    };
+
+                let last = elements.len();
+                if last == 0 {
+                    return Err(String::from("property outside of an element"))
+                }
+                elements[last - 1].props.push(prop);
+            },
+            "end_header" => break,
+            _ => return Err(format!("unexpected header line: {}", line))
+        }
+    }
+
+    match format {
+        Some(f) => Ok(Header { format: f, elements: elements, data_start: pos }),
+        None => Err(String::from("missing format"))
+    }
+}
+
+struct Reader<'a> {
+    data    : &'a [u8],
+    pos     : usize,
+    format  : Format,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        if self.pos + N > self.data.len() {
+            return Err(String::from("unexpected end of file"))
+        }
+
+        let mut b = [0u8; N];
+        b.copy_from_slice(&self.data[self.pos..self.pos + N]);
+        self.pos += N;
+        if self.format == Format::BinaryBE {
+            b.reverse();
+        }
+        Ok(b)
+    }
+
+    fn token(&mut self) -> Result<&'a str, String> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        if start == self.pos {
+            return Err(String::from("unexpected end of file"))
+        }
+
+        match core::str::from_utf8(&self.data[start..self.pos]) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(String::from("invalid ascii value"))
+        }
+    }
+
+    fn scalar(&mut self, ty: Scalar) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            return match self.token()?.parse::<f64>() {
+                Ok(v) => Ok(v),
+                Err(_) => Err(String::from("number parse error"))
+            }
+        }
+
+        // binary data is stored in file order, bytes() already swapped big endian values
+        Ok(match ty {
+            Scalar::I8  => i8::from_le_bytes(self.bytes::<1>()?) as f64,
+            Scalar::U8  => u8::from_le_bytes(self.bytes::<1>()?) as f64,
+            Scalar::I16 => i16::from_le_bytes(self.bytes::<2>()?) as f64,
+            Scalar::U16 => u16::from_le_bytes(self.bytes::<2>()?) as f64,
+            Scalar::I32 => i32::from_le_bytes(self.bytes::<4>()?) as f64,
+            Scalar::U32 => u32::from_le_bytes(self.bytes::<4>()?) as f64,
+            Scalar::F32 => f32::from_le_bytes(self.bytes::<4>()?) as f64,
+            Scalar::F64 => f64::from_le_bytes(self.bytes::<8>()?),
+        })
+    }
+
+    // vertex indices are stored as any scalar type, negative or fractional ones are errors
+    fn index(&mut self, ty: Scalar) -> Result<u32, String> {
+        let v = self.scalar(ty)?;
+        if !(v >= 0.0 && v <= u32::MAX as f64) || (v as u32) as f64 != v {
+            return Err(String::from("invalid vertex index"))
+        }
+        Ok(v as u32)
+    }
+
+    // a list's item count, refusing counts the rest of the file cannot hold
+    fn list_len(&mut self, count: Scalar, item: Scalar) -> Result<usize, String> {
+        let n = self.scalar(count)?;
+        // ascii items take at least a digit and a separator
+        let item_size = if self.format == Format::Ascii { 2 } else { item.size() };
+        let left = (self.data.len() - self.pos + 1) / item_size;
+        if !(n >= 0.0) || n > left as f64 {
+            return Err(String::from("list longer than the file"))
+        }
+        Ok(n as usize)
+    }
+
+    fn skip(&mut self, prop: &Property) -> Result<(), String> {
+        match prop {
+            Property::Scalar { ty, .. } => {
+                if self.format == Format::Ascii {
+                    self.token()?;
+                } else if self.pos + ty.size() <= self.data.len() {
+                    self.pos += ty.size();
+                } else {
+                    return Err(String::from("unexpected end of file"))
+                }
+            },
+            Property::List { count, item, .. } => {
+                let n = self.list_len(*count, *item)?;
+                for _ in 0..n {
+                    self.skip(&Property::Scalar { name: String::new(), ty: *item })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Mesh {
+    /// Reads ascii and binary PLY files. Vertex normals (nx, ny, nz), colors (red, green, blue,
+    /// alpha) and texture coordinates (u/v, s/t or texture_u/texture_v) are kept when present.
+    /// Polygons with more than 4 sides are fan triangulated, files without faces give a point cloud.
+    pub fn read_ply(path: &str) -> Result<Mesh, String> {
+        let data = read_bytes(path)?;
+        Self::parse_ply(data.as_slice())
+    }
+
+    pub fn parse_ply(data: &[u8]) -> Result<Mesh, String> {
+        let header = parse_header(data)?;
+        let mut rd = Reader { data: data, pos: header.data_start, format: header.format };
+
+        let mut verts = Vec::new();
+        let mut uvws = Vec::new();
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+        let mut tris = Vec::new();
+        let mut quads = Vec::new();
+
+        for e in header.elements.iter() {
+            match e.name.as_str() {
+                "vertex" => {
+                    let pos = [e.find(&["x"]), e.find(&["y"]), e.find(&["z"])];
+                    let nrm = [e.find(&["nx"]), e.find(&["ny"]), e.find(&["nz"])];
+                    let uv  = [e.find(&["u", "s", "texture_u", "texture_s"]), e.find(&["v", "t", "texture_v", "texture_t"])];
+                    let col = [e.find(&["red", "r"]), e.find(&["green", "g"]), e.find(&["blue", "b"]), e.find(&["alpha", "a"])];
+
+                    let has_nrm = nrm.iter().all(|p| p.is_some());
+                    let has_uv  = uv.iter().all(|p| p.is_some());
+                    let has_col = col[0..3].iter().all(|p| p.is_some());
+
+                    let mut values = Vec::with_capacity(e.props.len());
+                    for _ in 0..e.props.len() {
+                        values.push(0.0f32);
+                    }
+
+                    for _ in 0..e.count {
+                        for (i, p) in e.props.iter().enumerate() {
+                            match p {
+                                Property::Scalar { ty, .. } => {
+                                    let v = rd.scalar(*ty)?;
+                                    let is_col = col.iter().any(|c| *c == Some(i));
+                                    values[i] = if is_col { (v / ty.unit()) as f32 } else { v as f32 };
+                                },
+                                _ => rd.skip(p)?
+                            }
+                        }
+
+                        let get = |i: Option<usize>, default: f32| match i { Some(i) => values[i], None => default };
+                        verts.push(Vec3f::new(get(pos[0], 0.0), get(pos[1], 0.0), get(pos[2], 0.0)));
+                        if has_nrm {
+                            normals.push(Vec3f::new(get(nrm[0], 0.0), get(nrm[1], 0.0), get(nrm[2], 0.0)));
+                        }
+                        if has_uv {
+                            uvws.push(Vec3f::new(get(uv[0], 0.0), get(uv[1], 0.0), 0.0));
+                        }
+                        if has_col {
+                            colors.push(Vec4f::new(get(col[0], 0.0), get(col[1], 0.0), get(col[2], 0.0), get(col[3], 1.0)));
+                        }
+                    }
+                },
+                "face" => {
+                    let list = e.find(&["vertex_indices", "vertex_index"]);
+                    for _ in 0..e.count {
+                        for (i, p) in e.props.iter().enumerate() {
+                            match p {
+                                Property::List { count, item, .. } if Some(i) == list => {
+                                    let n = rd.list_len(*count, *item)?;
+                                    let mut idx = Vec::with_capacity(n);
+                                    for _ in 0..n {
+                                        idx.push(rd.index(*item)?);
+                                    }
+                                    push_face(idx.as_slice(), &mut tris, &mut quads);
+                                },
+                                _ => rd.skip(p)?
+                            }
+                        }
+                    }
+                },
+                _ => {
+                    for _ in 0..e.count {
+                        for p in e.props.iter() {
+                            rd.skip(p)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        // attributes are per vertex: uv and normal indices follow the vertex indices
+        let has_uv = uvws.len() > 0;
+        let has_nrm = normals.len() > 0;
+        let attr = |v: u32, present: bool| if present { v } else { NO_INDEX };
+        let tris = tris.iter().map(|t: &[u32; 3]| IdTri::new(*t, [attr(t[0], has_uv), attr(t[1], has_uv), attr(t[2], has_uv)], [attr(t[0], has_nrm), attr(t[1], has_nrm), attr(t[2], has_nrm)])).collect();
+        let quads = quads.iter().map(|q: &[u32; 4]| IdQuad::new(*q, [attr(q[0], has_uv), attr(q[1], has_uv), attr(q[2], has_uv), attr(q[3], has_uv)], [attr(q[0], has_nrm), attr(q[1], has_nrm), attr(q[2], has_nrm), attr(q[3], has_nrm)])).collect();
+
+        Ok(Mesh::from(verts, uvws, tris, quads).with_normals(normals).with_colors(colors))
+    }
+}
+
+fn push_face(idx: &[u32], tris: &mut Vec<[u32; 3]>, quads: &mut Vec<[u32; 4]>) {
+    match idx.len() {
+        0 | 1 | 2 => (),
+        3 => tris.push([idx[0], idx[1], idx[2]]),
+        4 => quads.push([idx[0], idx[1], idx[2], idx[3]]),
+        _ => {
+            for i in 1..idx.len() - 1 {
+                tris.push([idx[0], idx[i], idx[i + 1]]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary_ply(face_list: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.append(b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n");
+        data.append(b"element face 1\nproperty list uint int vertex_indices\nend_header\n");
+        for v in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter() {
+            data.append(&v.to_le_bytes());
+        }
+        data.append(face_list);
+        data
+    }
+
+    #[test]
+    fn face_list() {
+        let mut face = Vec::new();
+        face.append(&3u32.to_le_bytes());
+        for i in 0..3i32 {
+            face.append(&i.to_le_bytes());
+        }
+        let m = Mesh::parse_ply(binary_ply(face.as_slice()).as_slice()).ok().unwrap();
+        assert_eq!(m.tris().len(), 1);
+    }
+
+    #[test]
+    fn oversized_face_list() {
+        let data = binary_ply(&u32::MAX.to_le_bytes());
+        assert!(Mesh::parse_ply(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn bad_indices() {
+        for i in [-1.0f32, 0.5].iter() {
+            let mut data = Vec::new();
+            data.append(b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n");
+            data.append(b"element face 1\nproperty list uchar float vertex_indices\nend_header\n");
+            for _ in 0..9 {
+                data.append(&0.0f32.to_le_bytes());
+            }
+            data.push(3);
+            data.append(&0.0f32.to_le_bytes());
+            data.append(&1.0f32.to_le_bytes());
+            data.append(&i.to_le_bytes());
+            assert!(Mesh::parse_ply(data.as_slice()).is_err());
+        }
+    }
+
+    #[test]
+    fn magic_first() {
+        match Mesh::parse_ply(b"format ascii 1.0\nend_header\n") {
+            Ok(_) => panic!("parsed without the magic"),
+            Err(err) => assert_eq!(err.as_str(), "not a ply file")
+        }
+        assert!(Mesh::parse_ply(b"plyx\nformat ascii 1.0\nend_header\n").is_err());
+    }
+
+    #[test]
+    fn ascii_attributes() {
+        let src = "ply\r\nformat ascii 1.0\ncomment per vertex everything\n\
+                   element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                   property float nx\nproperty float ny\nproperty float nz\n\
+                   property uchar red\nproperty uchar green\nproperty uchar blue\nproperty float s\nproperty float t\n\
+                   element face 1\nproperty uchar flags\nproperty list uchar int vertex_index\nend_header\n\
+                   0 0 0 0 0 1 255 0 0 0 0\n1 0 0 0 0 1 0 255 0 1 0\n1 1 0 0 0 1 0 0 255 1 1\n0 1 0 0 0 1 51 51 51 0 1\n\
+                   7 4 0 1 2 3\n";
+        let m = Mesh::parse_ply(src.as_bytes()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(m.verts().len(), 4);
+        assert_eq!(m.quads().len(), 1);
+        assert_eq!(m.quads()[0].verts(), &[0, 1, 2, 3]);
+        assert_eq!(m.quads()[0].uvs(), &[0, 1, 2, 3]);
+        assert_eq!(m.quads()[0].norms(), &[0, 1, 2, 3]);
+        assert_eq!(m.normals().len(), 4);
+        assert_eq!(m.normals()[2].z, 1.0);
+        assert_eq!((m.uvws()[2].x, m.uvws()[2].y), (1.0, 1.0));
+        assert_eq!(m.colors().len(), 4);
+        assert_eq!((m.colors()[1].x, m.colors()[1].y, m.colors()[1].w), (0.0, 1.0, 1.0));
+        assert_eq!(m.colors()[3].z, 0.2);
+    }
+
+    #[test]
+    fn big_endian() {
+        let mut data = Vec::new();
+        data.append(b"ply\nformat binary_big_endian 1.0\nelement vertex 3\nproperty double x\nproperty double y\nproperty double z\n");
+        data.append(b"property ushort red\nproperty ushort green\nproperty ushort blue\n");
+        data.append(b"element face 1\nproperty list uchar ushort vertex_indices\nend_header\n");
+        for (i, v) in [0.0f64, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0].iter().enumerate() {
+            data.append(&v.to_be_bytes());
+            if i % 3 == 2 {
+                for c in [65535u16, 0, 65535].iter() {
+                    data.append(&c.to_be_bytes());
+                }
+            }
+        }
+        data.push(3);
+        for i in [2u16, 1, 0].iter() {
+            data.append(&i.to_be_bytes());
+        }
+
+        let m = Mesh::parse_ply(data.as_slice()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(m.verts()[2].y, 2.0);
+        assert_eq!(m.tris()[0].verts(), &[2, 1, 0]);
+        assert_eq!(m.tris()[0].uvs(), &[NO_INDEX; 3]);
+        assert_eq!((m.colors()[0].x, m.colors()[0].y, m.colors()[0].z), (1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn point_cloud() {
+        let m = Mesh::parse_ply(b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n1 2 3\n4 5 6\n")
+            .unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(m.verts().len(), 2);
+        assert_eq!(m.verts()[1].z, 6.0);
+        assert_eq!(m.tris().len() + m.quads().len(), 0);
+    }
+}
@@ -0,0 +1,618 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+extern "C" {
+    fn sinf(x: f32) -> f32;
+    fn cosf(x: f32) -> f32;
+    fn acosf(x: f32) -> f32;
+    fn atan2f(y: f32, x: f32) -> f32;
+    fn sqrtf(x: f32) -> f32;
+}
+
+const PI : f32 = core::f32::consts::PI;
+
+// 20 * 4^8 triangles and a third as many vertices, one more level and the welded indices of
+// GPUMesh would no longer fit in u32
+const MAX_SUBDIVISIONS : u32 = 8;
+
+/// Procedural shapes, centered on the origin with +y up. Faces wind counter clockwise seen from
+/// outside, uvs have their origin at the bottom left like OBJ files.
+// the viewer itself only builds the icosphere it shows without a model
+#[allow(dead_code)]
+pub enum Primitive {
+    /// xz plane facing +y, uvs span the whole plane
+    Plane { width: f32, depth: f32, cols: u32, rows: u32 },
+    /// xz plane of `cols` x `rows` square cells facing +y, uvs repeat once per cell
+    Grid { cols: u32, rows: u32, cell_size: f32 },
+    /// every face split in `segments` x `segments` quads with its own 0..1 uvs
+    Cube { size: Vec3f, segments: u32 },
+    /// latitude/longitude sphere, `slices` around y and `stacks` from pole to pole
+    UvSphere { radius: f32, slices: u32, stacks: u32 },
+    /// subdivided icosahedron, 20 * 4^subdivisions triangles, subdivisions stop at 8
+    Icosphere { radius: f32, subdivisions: u32 },
+    Cylinder { radius: f32, height: f32, slices: u32, stacks: u32, caps: bool },
+    /// apex at +y, the cap closes the base
+    Cone { radius: f32, height: f32, slices: u32, stacks: u32, cap: bool },
+    /// ring around y, `major_segments` around the ring and `minor_segments` around the tube
+    Torus { major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32 },
+    /// cylinder of `height` capped by two hemispheres of `stacks` each, `rings` along the side
+    Capsule { radius: f32, height: f32, slices: u32, stacks: u32, rings: u32 },
+}
+
+// faces index positions, uvs and normals separately, so seams only duplicate what differs
+struct Builder {
+    verts   : Vec<Vec3f>,
+    uvws    : Vec<Vec3f>,
+    normals : Vec<Vec3f>,
+    tris    : Vec<IdTri>,
+    quads   : Vec<IdQuad>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self { verts: Vec::new(), uvws: Vec::new(), normals: Vec::new(), tris: Vec::new(), quads: Vec::new() }
+    }
+
+    fn pos(&mut self, p: Vec3f) -> u32 {
+        self.verts.push(p);
+        self.verts.len() as u32 - 1
+    }
+
+    fn uv(&mut self, u: f32, v: f32) -> u32 {
+        self.uvws.push(Vec3f::new(u, v, 0.0));
+        self.uvws.len() as u32 - 1
+    }
+
+    fn normal(&mut self, n: Vec3f) -> u32 {
+        self.normals.push(n);
+        self.normals.len() as u32 - 1
+    }
+
+    fn finish(self) -> Mesh {
+        Mesh::from(self.verts, self.uvws, self.tris, self.quads).with_normals(self.normals)
+    }
+
+    // flat rectangle spanned by du and dv, du x dv gives the facing
+    fn patch(&mut self, origin: Vec3f, du: Vec3f, dv: Vec3f, nu: u32, nv: u32, uv_scale: Vec2f) {
+        let n = self.normal(Vec3f::normalize(&Vec3f::cross(&du, &dv)));
+        let first_pos = self.verts.len() as u32;
+        let first_uv = self.uvws.len() as u32;
+
+        for j in 0..=nv {
+            for i in 0..=nu {
+                let (s, t) = (i as f32 / nu as f32, j as f32 / nv as f32);
+                self.pos(origin + du * s + dv * t);
+                self.uv(s * uv_scale.x, t * uv_scale.y);
+            }
+        }
+
+        let stride = nu + 1;
+        for j in 0..nv {
+            for i in 0..nu {
+                let c = [j * stride + i, j * stride + i + 1, (j + 1) * stride + i + 1, (j + 1) * stride + i];
+                self.quads.push(IdQuad::new([first_pos + c[0], first_pos + c[1], first_pos + c[2], first_pos + c[3]],
+                                            [first_uv + c[0], first_uv + c[1], first_uv + c[2], first_uv + c[3]],
+                                            [n; 4]));
+            }
+        }
+    }
+
+    // flat disk at height y facing +y or -y, planar uvs as seen from the side it faces
+    fn disk(&mut self, y: f32, radius: f32, slices: u32, up: bool) {
+        let n = self.normal(Vec3f::new(0.0, if up { 1.0 } else { -1.0 }, 0.0));
+        let center = self.pos(Vec3f::new(0.0, y, 0.0));
+        let center_uv = self.uv(0.5, 0.5);
+        let first_pos = self.verts.len() as u32;
+        let first_uv = self.uvws.len() as u32;
+
+        for i in 0..slices {
+            let a = 2.0 * PI * i as f32 / slices as f32;
+            let (c, s) = unsafe { (cosf(a), sinf(a)) };
+            self.pos(Vec3f::new(radius * c, y, -radius * s));
+            self.uv(0.5 + if up { 0.5 } else { -0.5 } * c, 0.5 + 0.5 * s);
+        }
+
+        for i in 0..slices {
+            let j = (i + 1) % slices;
+            let (a, b) = if up { (i, j) } else { (j, i) };
+            self.tris.push(IdTri::new([center, first_pos + a, first_pos + b],
+                                      [center_uv, first_uv + a, first_uv + b],
+                                      [n; 3]));
+        }
+    }
+
+    // sweeps a profile in the (radius, y) half plane around y, profile points with a zero radius
+    // become poles closed by triangles, a closed profile shares its first and last rings
+    fn revolve(&mut self, profile: &[ProfilePoint], slices: u32, closed: bool) {
+        let mut rings : Vec<Ring> = Vec::with_capacity(profile.len());
+        for (k, p) in profile.iter().enumerate() {
+            let pole = p.radius <= 0.0;
+            let nl = unsafe { sqrtf(p.nr * p.nr + p.ny * p.ny) };
+            let (nr, ny) = (p.nr / nl, p.ny / nl);
+
+            let reuse = closed && k == profile.len() - 1 && k > 0;
+            let pos = if reuse { rings[0].pos } else { self.verts.len() as u32 };
+            let normal = if reuse { rings[0].normal } else { self.normals.len() as u32 };
+            let uv = self.uvws.len() as u32;
+
+            // poles get one normal and uv per slice, taken halfway across the slice
+            let (columns, offset) = if pole { (slices, 0.5) } else { (slices + 1, 0.0) };
+            for i in 0..columns {
+                let t = (i as f32 + offset) / slices as f32;
+                let a = 2.0 * PI * t;
+                let (c, s) = unsafe { (cosf(a), sinf(a)) };
+                if !reuse && i < slices {
+                    if !pole || i == 0 {
+                        self.pos(Vec3f::new(p.radius * c, p.y, -p.radius * s));
+                    }
+                    self.normal(Vec3f::new(nr * c, ny, -nr * s));
+                }
+                self.uv(t, p.v);
+            }
+            rings.push(Ring { pos: pos, normal: normal, uv: uv, pole: pole });
+        }
+
+        for k in 1..rings.len() {
+            let (r0, r1) = (&rings[k - 1], &rings[k]);
+            if r0.pole && r1.pole {
+                continue
+            }
+
+            for i in 0..slices {
+                let a = r0.corner(i, i, slices);
+                let b = r0.corner(i + 1, i, slices);
+                let c = r1.corner(i + 1, i, slices);
+                let d = r1.corner(i, i, slices);
+                if r0.pole {
+                    self.tris.push(IdTri::new([a.0, c.0, d.0], [a.1, c.1, d.1], [a.2, c.2, d.2]));
+                } else if r1.pole {
+                    self.tris.push(IdTri::new([a.0, b.0, c.0], [a.1, b.1, c.1], [a.2, b.2, c.2]));
+                } else {
+                    self.quads.push(IdQuad::new([a.0, b.0, c.0, d.0], [a.1, b.1, c.1, d.1], [a.2, b.2, c.2, d.2]));
+                }
+            }
+        }
+    }
+}
+
+// point of a revolved profile, (nr, ny) is the outward normal in the same half plane
+#[derive(Clone, Copy)]
+struct ProfilePoint {
+    radius  : f32,
+    y       : f32,
+    nr      : f32,
+    ny      : f32,
+    v       : f32,
+}
+
+impl ProfilePoint {
+    fn new(radius: f32, y: f32, nr: f32, ny: f32, v: f32) -> Self { Self { radius: radius, y: y, nr: nr, ny: ny, v: v } }
+}
+
+// first indices of a revolved ring
+struct Ring {
+    pos     : u32,
+    normal  : u32,
+    uv      : u32,
+    pole    : bool,
+}
+
+impl Ring {
+    // (position, uv, normal) of the ring at `column`, poles answer for the slice instead
+    fn corner(&self, column: u32, slice: u32, slices: u32) -> (u32, u32, u32) {
+        if self.pole {
+            (self.pos, self.uv + slice, self.normal + slice)
+        } else {
+            (self.pos + column % slices, self.uv + column, self.normal + column % slices)
+        }
+    }
+}
+
+fn sphere_profile(radius: f32, stacks: u32) -> Vec<ProfilePoint> {
+    (0..=stacks).map(|k| {
+        let t = PI * k as f32 / stacks as f32;
+        let (s, c) = unsafe { (sinf(t), cosf(t)) };
+        // exact zeros so that both ends are detected as poles
+        let s = if k == 0 || k == stacks { 0.0 } else { s };
+        ProfilePoint::new(radius * s, -radius * c, s, -c, k as f32 / stacks as f32)
+    }).collect()
+}
+
+fn capsule_profile(radius: f32, height: f32, stacks: u32, rings: u32) -> Vec<ProfilePoint> {
+    let half = height * 0.5;
+    let quarter = PI * radius * 0.5;
+    let total = 2.0 * quarter + height;
+    let mut profile = Vec::with_capacity((2 * stacks + rings + 1) as usize);
+
+    // bottom hemisphere from the pole to the equator
+    for k in 0..=stacks {
+        let t = -0.5 * PI + 0.5 * PI * k as f32 / stacks as f32;
+        let (s, c) = unsafe { (sinf(t), cosf(t)) };
+        let c = if k == 0 { 0.0 } else { c };
+        profile.push(ProfilePoint::new(radius * c, -half + radius * s, c, s, quarter * k as f32 / stacks as f32 / total));
+    }
+
+    for k in 1..rings {
+        let t = k as f32 / rings as f32;
+        profile.push(ProfilePoint::new(radius, -half + height * t, 1.0, 0.0, (quarter + height * t) / total));
+    }
+
+    for k in 0..=stacks {
+        let t = 0.5 * PI * k as f32 / stacks as f32;
+        let (s, c) = unsafe { (sinf(t), cosf(t)) };
+        let c = if k == stacks { 0.0 } else { c };
+        profile.push(ProfilePoint::new(radius * c, half + radius * s, c, s, (quarter + height + quarter * k as f32 / stacks as f32) / total));
+    }
+    profile
+}
+
+fn spherical_uv(n: &Vec3f) -> Vec2f {
+    let a = unsafe { atan2f(-n.z, n.x) };
+    let u = if a < 0.0 { a / (2.0 * PI) + 1.0 } else { a / (2.0 * PI) };
+    let v = unsafe { acosf((-n.y).max(-1.0).min(1.0)) } / PI;
+    Vec2f::new(u, v)
+}
+
+fn icosphere(b: &mut Builder, radius: f32, subdivisions: u32) {
+    let t = (1.0 + unsafe { sqrtf(5.0) }) * 0.5;
+    let mut points : Vec<Vec3f> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ].iter().map(|p| Vec3f::normalize(&Vec3f::new(p.0, p.1, p.2))).collect();
+
+    let mut faces : Vec<[u32; 3]> = [
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ].iter().map(|f| *f).collect();
+
+    // each level splits every edge once, the sorted edge list gives the midpoint indices
+    for _ in 0..subdivisions.min(MAX_SUBDIVISIONS) {
+        let edge = |a: u32, b: u32| if a < b { (a as u64) << 32 | b as u64 } else { (b as u64) << 32 | a as u64 };
+
+        let mut edges : Vec<u64> = Vec::with_capacity(faces.len() * 3);
+        for f in faces.iter() {
+            for k in 0..3 {
+                edges.push(edge(f[k], f[(k + 1) % 3]));
+            }
+        }
+        edges.as_mut_slice().sort_unstable();
+        let mut unique : Vec<u64> = Vec::with_capacity(edges.len() / 2);
+        for e in edges.iter() {
+            if unique.last() != Some(e) {
+                unique.push(*e);
+            }
+        }
+
+        let base = points.len() as u32;
+        for e in unique.iter() {
+            let (a, b) = (points[(*e >> 32) as usize], points[(*e & 0xFFFF_FFFF) as usize]);
+            points.push(Vec3f::normalize(&(a + b)));
+        }
+
+        let mut next = Vec::with_capacity(faces.len() * 4);
+        for f in faces.iter() {
+            let mut m = [0u32; 3];
+            for k in 0..3 {
+                let e = edge(f[k], f[(k + 1) % 3]);
+                m[k] = base + unique.as_slice().binary_search(&e).unwrap_or(0) as u32;
+            }
+            next.push([f[0], m[0], m[2]]);
+            next.push([f[1], m[1], m[0]]);
+            next.push([f[2], m[2], m[1]]);
+            next.push([m[0], m[1], m[2]]);
+        }
+        faces = next;
+    }
+
+    for p in points.iter() {
+        b.pos(*p * radius);
+        b.normal(*p);
+        let uv = spherical_uv(p);
+        b.uv(uv.x, uv.y);
+    }
+
+    // uvs follow the vertices except across the seam and at the poles, where corners get their own
+    let pole = |p: &Vec3f| p.x * p.x + p.z * p.z < 1.0e-10;
+    for f in faces.iter() {
+        let is_pole = [pole(&points[f[0] as usize]), pole(&points[f[1] as usize]), pole(&points[f[2] as usize])];
+        let mut uv = [b.uvws[f[0] as usize], b.uvws[f[1] as usize], b.uvws[f[2] as usize]];
+        let mut ids = *f;
+
+        let wraps = (0..3).any(|k| !is_pole[k] && uv[k].x > 0.75) && (0..3).any(|k| !is_pole[k] && uv[k].x < 0.25);
+        for k in 0..3 {
+            if wraps && !is_pole[k] && uv[k].x < 0.5 {
+                uv[k].x += 1.0;
+                ids[k] = b.uv(uv[k].x, uv[k].y);
+            }
+        }
+
+        for k in 0..3 {
+            if is_pole[k] {
+                let u = (uv[(k + 1) % 3].x + uv[(k + 2) % 3].x) * 0.5;
+                ids[k] = b.uv(u, uv[k].y);
+            }
+        }
+        b.tris.push(IdTri::new(*f, ids, *f));
+    }
+}
+
+impl Mesh {
+    /// Generates the shape with smooth normals on curved surfaces and hard edges where
+    /// the surface folds (cube edges, cylinder and cone caps)
+    pub fn primitive(shape: &Primitive) -> Mesh {
+        let mut b = Builder::new();
+        match *shape {
+            Primitive::Plane { width, depth, cols, rows } => {
+                b.patch(Vec3f::new(-width * 0.5, 0.0, depth * 0.5), Vec3f::new(width, 0.0, 0.0), Vec3f::new(0.0, 0.0, -depth),
+                        cols.max(1), rows.max(1), Vec2f::new(1.0, 1.0));
+            }
+
+            Primitive::Grid { cols, rows, cell_size } => {
+                let (cols, rows) = (cols.max(1), rows.max(1));
+                let (w, d) = (cols as f32 * cell_size, rows as f32 * cell_size);
+                b.patch(Vec3f::new(-w * 0.5, 0.0, d * 0.5), Vec3f::new(w, 0.0, 0.0), Vec3f::new(0.0, 0.0, -d),
+                        cols, rows, Vec2f::new(cols as f32, rows as f32));
+            }
+
+            Primitive::Cube { size, segments } => {
+                let h = size * 0.5;
+                let n = segments.max(1);
+                let x = Vec3f::new(size.x, 0.0, 0.0);
+                let y = Vec3f::new(0.0, size.y, 0.0);
+                let z = Vec3f::new(0.0, 0.0, size.z);
+                // (face center, du, dv) with du x dv pointing out
+                let faces = [
+                    (Vec3f::new( h.x, 0.0, 0.0), -z,  y),
+                    (Vec3f::new(-h.x, 0.0, 0.0),  z,  y),
+                    (Vec3f::new(0.0,  h.y, 0.0),  x, -z),
+                    (Vec3f::new(0.0, -h.y, 0.0),  x,  z),
+                    (Vec3f::new(0.0, 0.0,  h.z),  x,  y),
+                    (Vec3f::new(0.0, 0.0, -h.z), -x,  y),
+                ];
+                for (c, du, dv) in faces.iter() {
+                    b.patch(*c - *du * 0.5 - *dv * 0.5, *du, *dv, n, n, Vec2f::new(1.0, 1.0));
+                }
+            }
+
+            Primitive::UvSphere { radius, slices, stacks } => {
+                b.revolve(sphere_profile(radius, stacks.max(2)).as_slice(), slices.max(3), false);
+            }
+
+            Primitive::Icosphere { radius, subdivisions } => icosphere(&mut b, radius, subdivisions),
+
+            Primitive::Cylinder { radius, height, slices, stacks, caps } => {
+                let (slices, stacks) = (slices.max(3), stacks.max(1));
+                let profile : Vec<ProfilePoint> = (0..=stacks).map(|k| {
+                    let t = k as f32 / stacks as f32;
+                    ProfilePoint::new(radius, height * (t - 0.5), 1.0, 0.0, t)
+                }).collect();
+                b.revolve(profile.as_slice(), slices, false);
+                if caps {
+                    b.disk(height * 0.5, radius, slices, true);
+                    b.disk(-height * 0.5, radius, slices, false);
+                }
+            }
+
+            Primitive::Cone { radius, height, slices, stacks, cap } => {
+                let (slices, stacks) = (slices.max(3), stacks.max(1));
+                let profile : Vec<ProfilePoint> = (0..=stacks).map(|k| {
+                    let t = k as f32 / stacks as f32;
+                    let r = if k == stacks { 0.0 } else { radius * (1.0 - t) };
+                    ProfilePoint::new(r, height * (t - 0.5), height, radius, t)
+                }).collect();
+                b.revolve(profile.as_slice(), slices, false);
+                if cap {
+                    b.disk(-height * 0.5, radius, slices, false);
+                }
+            }
+
+            Primitive::Torus { major_radius, minor_radius, major_segments, minor_segments } => {
+                let segments = minor_segments.max(3);
+                // starts on the inside of the tube so the uv seam is the least visible
+                let profile : Vec<ProfilePoint> = (0..=segments).map(|k| {
+                    let t = k as f32 / segments as f32;
+                    let a = PI + 2.0 * PI * t;
+                    let (s, c) = unsafe { (sinf(a), cosf(a)) };
+                    ProfilePoint::new(major_radius + minor_radius * c, minor_radius * s, c, s, t)
+                }).collect();
+                b.revolve(profile.as_slice(), major_segments.max(3), true);
+            }
+
+            Primitive::Capsule { radius, height, slices, stacks, rings } => {
+                b.revolve(capsule_profile(radius, height, stacks.max(1), rings.max(1)).as_slice(), slices.max(3), false);
+            }
+        }
+        b.finish()
+    }
+}
+
+impl GPUMesh {
+    /// Welded, indexed version of `Mesh::primitive`
+    pub fn primitive(shape: &Primitive) -> Result<GPUMesh, String> {
+        GPUMesh::from(&Mesh::primitive(shape)).map_err(|err| format!("invalid primitive: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corner_uvs(m: &Mesh, t: &IdTri) -> [Vec3f; 3] {
+        let uv = t.uvs();
+        [m.uvws()[uv[0] as usize], m.uvws()[uv[1] as usize], m.uvws()[uv[2] as usize]]
+    }
+
+    // the mesh has no issues and every corner normal agrees with its face's winding and points
+    // away from `inside`, the closest point of the shape's core
+    fn check(m: &Mesh, inside: &dyn Fn(&Vec3f) -> Vec3f) {
+        assert_eq!(m.validate().issues().len(), 0);
+
+        let faces = m.tris().iter().map(|t| (&t.verts()[..], &t.norms()[..]))
+            .chain(m.quads().iter().map(|q| (&q.verts()[..], &q.norms()[..])));
+        for (v, n) in faces {
+            let p : Vec<Vec3f> = v.iter().map(|i| m.verts()[*i as usize]).collect();
+            let face = if p.len() == 3 { Vec3f::cross(&(p[1] - p[0]), &(p[2] - p[0])) } else { Vec3f::cross(&(p[2] - p[0]), &(p[3] - p[1])) };
+            for k in 0..p.len() {
+                let normal = m.normals()[n[k] as usize];
+                assert!(Vec3f::dot(&normal, &face) > 0.0);
+                assert!(Vec3f::dot(&normal, &(p[k] - inside(&p[k]))) > 0.0);
+            }
+        }
+    }
+
+    fn origin(_: &Vec3f) -> Vec3f { Vec3f::new(0.0, 0.0, 0.0) }
+
+    #[test]
+    fn plane() {
+        let m = Mesh::primitive(&Primitive::Plane { width: 2.0, depth: 1.0, cols: 4, rows: 3 });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (20, 0, 12));
+        check(&m, &|p| Vec3f::new(p.x, -1.0, p.z));
+        assert!(m.stats().volume().is_none());
+
+        let size = m.bounds().size();
+        assert_eq!((size.x, size.y, size.z), (2.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn grid() {
+        let m = Mesh::primitive(&Primitive::Grid { cols: 3, rows: 2, cell_size: 0.5 });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (12, 0, 6));
+        check(&m, &|p| Vec3f::new(p.x, -1.0, p.z));
+        assert!(m.stats().volume().is_none());
+
+        // uvs repeat once per cell
+        let far = m.uvws().iter().fold(Vec3f::new(0.0, 0.0, 0.0), |a, uv| Vec3f::new(a.x.max(uv.x), a.y.max(uv.y), 0.0));
+        assert_eq!((far.x, far.y), (3.0, 2.0));
+    }
+
+    #[test]
+    fn cube() {
+        let m = Mesh::primitive(&Primitive::Cube { size: Vec3f::new(1.0, 2.0, 3.0), segments: 2 });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (54, 0, 24));
+        check(&m, &origin);
+        let v = m.stats().volume().unwrap();
+        assert!((v - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let m = Mesh::primitive(&Primitive::UvSphere { radius: 1.0, slices: 8, stacks: 4 });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (26, 16, 16));
+        check(&m, &origin);
+        let v = m.stats().volume().unwrap();
+        assert!(v > 0.5 * 4.0 / 3.0 * PI && v < 4.0 / 3.0 * PI);
+    }
+
+    #[test]
+    fn cylinder() {
+        let m = Mesh::primitive(&Primitive::Cylinder { radius: 1.0, height: 2.0, slices: 8, stacks: 2, caps: true });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (42, 16, 16));
+        check(&m, &origin);
+        let v = m.stats().volume().unwrap();
+        assert!(v > 0.5 * 2.0 * PI && v < 2.0 * PI);
+
+        let open = Mesh::primitive(&Primitive::Cylinder { radius: 1.0, height: 2.0, slices: 8, stacks: 2, caps: false });
+        assert!(open.stats().volume().is_none());
+    }
+
+    #[test]
+    fn cone() {
+        let m = Mesh::primitive(&Primitive::Cone { radius: 1.0, height: 2.0, slices: 8, stacks: 2, cap: true });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (26, 16, 8));
+        check(&m, &origin);
+        let v = m.stats().volume().unwrap();
+        assert!(v > 0.5 * 2.0 * PI / 3.0 && v < 2.0 * PI / 3.0);
+    }
+
+    #[test]
+    fn torus() {
+        let m = Mesh::primitive(&Primitive::Torus { major_radius: 2.0, minor_radius: 0.5, major_segments: 8, minor_segments: 4 });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (32, 0, 32));
+        // normals point away from the circle running through the middle of the tube
+        check(&m, &|p| { let r = unsafe { sqrtf(p.x * p.x + p.z * p.z) }; Vec3f::new(p.x * 2.0 / r, 0.0, p.z * 2.0 / r) });
+        let v = m.stats().volume().unwrap();
+        assert!(v > 0.25 * 2.0 * PI * PI * 2.0 * 0.25 && v < 2.0 * PI * PI * 2.0 * 0.25);
+    }
+
+    #[test]
+    fn capsule() {
+        let m = Mesh::primitive(&Primitive::Capsule { radius: 0.5, height: 1.0, slices: 8, stacks: 2, rings: 2 });
+        assert_eq!((m.verts().len(), m.tris().len(), m.quads().len()), (42, 16, 32));
+        // normals point away from the segment between the two hemisphere centers
+        check(&m, &|p| Vec3f::new(0.0, p.y.max(-0.5).min(0.5), 0.0));
+        let v = m.stats().volume().unwrap();
+        let exact = PI * 0.25 * 1.0 + 4.0 / 3.0 * PI * 0.125;
+        assert!(v > 0.5 * exact && v < exact);
+    }
+
+    #[test]
+    fn icosphere_seam() {
+        let m = Mesh::primitive(&Primitive::Icosphere { radius: 1.0, subdivisions: 2 });
+        assert_eq!(m.tris().len(), 320);
+
+        // no triangle interpolates across the whole texture where u wraps from 1 to 0
+        let mut wrapped = 0;
+        for t in m.tris().iter() {
+            let uv = corner_uvs(&m, t);
+            let (lo, hi) = (uv.iter().map(|c| c.x).fold(f32::MAX, f32::min), uv.iter().map(|c| c.x).fold(f32::MIN, f32::max));
+            assert!(hi - lo < 0.5);
+            if hi > 1.0 {
+                wrapped += 1;
+            }
+        }
+        assert!(wrapped > 0);
+    }
+
+    #[test]
+    fn icosphere_poles() {
+        let m = Mesh::primitive(&Primitive::Icosphere { radius: 1.0, subdivisions: 1 });
+        let mut poles = 0;
+        for t in m.tris().iter() {
+            let uv = corner_uvs(&m, t);
+            for k in 0..3 {
+                let p = m.verts()[t.verts()[k] as usize];
+                if p.x * p.x + p.z * p.z < 1.0e-10 {
+                    // the pole takes the u between the two other corners and v at the very top or bottom
+                    poles += 1;
+                    assert_eq!(uv[k].x, (uv[(k + 1) % 3].x + uv[(k + 2) % 3].x) * 0.5);
+                    assert!(uv[k].y == 0.0 || uv[k].y == 1.0);
+                }
+            }
+        }
+        // the poles split an edge of the icosahedron, six triangles meet at each
+        assert_eq!(poles, 12);
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_gles2::bindings::*;
+use rs_streams::*;
+use rs_alloc::*;
+
+/// Reads back the color of the bound framebuffer as packed RGB, top row first. Call it
+/// before swapping, the back buffer is undefined afterwards.
+pub fn read_pixels(width: i32, height: i32) -> Vec<u8> {
+    let (w, h) = (width.max(0) as usize, height.max(0) as usize);
+
+    // RGBA bytes is the only combination GLES2 always supports
+    let mut rgba = Vec::with_capacity(w * h * 4);
+    for _ in 0..w * h * 4 {
+        rgba.push(0u8);
+    }
+    unsafe {
+        glPixelStorei(GL_PACK_ALIGNMENT, 4);
+        glReadPixels(0, 0, width, height, GL_RGBA, GL_UNSIGNED_BYTE, rgba.as_mut_slice().as_mut_ptr() as *mut _);
+    }
+
+    // GL rows go bottom up
+    let mut rgb = Vec::with_capacity(w * h * 3);
+    for y in (0..h).rev() {
+        for x in 0..w {
+            let p = (y * w + x) * 4;
+            rgb.append(&rgba[p..p + 3]);
+        }
+    }
+    rgb
+}
+
+/// Writes a binary (P6) portable pixmap
+pub fn write_ppm(path: &str, width: i32, height: i32, rgb: &[u8]) -> Result<(), String> {
+    if width <= 0 || height <= 0 || rgb.len() != width as usize * height as usize * 3 {
+        return Err(String::from("image size does not match its pixels"))
+    }
+
+    let mut f =
+        match File::create(path) {
+            Ok(f) => f,
+            Err(_) => return Err(String::from("Could not create file"))
+        };
+
+    let header = format!("P6\n{} {}\n255\n", width, height);
+    let mut data = Vec::<u8>::with_capacity(header.len() + rgb.len());
+    data.append(header.as_bytes());
+    data.append(rgb);
+
+    match f.write(data.as_slice()) {
+        Ok(n) if n == data.len() => Ok(()),
+        _ => Err(String::from("Could not write file"))
+    }
+}
+
+/// Reads the framebuffer and saves it, see `read_pixels`
+pub fn save(path: &str, width: i32, height: i32) -> Result<(), String> {
+    write_ppm(path, width, height, read_pixels(width, height).as_slice())
+}
@@ -0,0 +1,728 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+
+use crate::objloader::*;
+
+extern "C" {
+    fn sqrt(x: f64) -> f64;
+}
+
+fn filled<T: Copy>(count: usize, v: T) -> Vec<T> {
+    let mut r = Vec::with_capacity(count);
+    for _ in 0..count {
+        r.push(v);
+    }
+    r
+}
+
+// border edges get a plane perpendicular to the face through them, weighted by the squared edge
+// length so that moving a border vertex off its border costs more than flattening the interior
+const BORDER_WEIGHT : f64 = 10.0;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Quadrics
+////////////////////////////////////////////////////////////////////////////////
+
+// symmetric 4x4 plane matrix and the total weight of the planes, errors are weighted means
+// so that they stay comparable to distances however many planes a vertex accumulated
+#[derive(Clone, Copy)]
+struct Quadric([f64; 11]);
+
+impl Quadric {
+    fn zero() -> Self { Quadric([0.0; 11]) }
+
+    // plane a*x + b*y + c*z + d = 0 with a unit normal
+    fn from_plane(a: f64, b: f64, c: f64, d: f64, w: f64) -> Self {
+        Quadric([a * a * w, a * b * w, a * c * w, a * d * w,
+                 b * b * w, b * c * w, b * d * w,
+                 c * c * w, c * d * w,
+                 d * d * w,
+                 w])
+    }
+
+    fn add(&mut self, o: &Quadric) {
+        for i in 0..11 {
+            self.0[i] += o.0[i];
+        }
+    }
+
+    // weighted mean of the squared distances to the planes
+    fn error(&self, p: &[f64; 3]) -> f64 {
+        let q = &self.0;
+        if q[10] <= 0.0 {
+            return 0.0
+        }
+
+        let (x, y, z) = (p[0], p[1], p[2]);
+        let e = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+              + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+              + q[7] * z * z + 2.0 * q[8] * z
+              + q[9];
+        e.max(0.0) / q[10]
+    }
+}
+
+fn sub(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] { [a[0] - b[0], a[1] - b[1], a[2] - b[2]] }
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] { [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]] }
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 { a[0] * b[0] + a[1] * b[1] + a[2] * b[2] }
+fn length(a: &[f64; 3]) -> f64 { unsafe { sqrt(dot(a, a)) } }
+
+fn plane_quadric(n: &[f64; 3], p: &[f64; 3], w: f64) -> Quadric {
+    let l = length(n);
+    if l == 0.0 {
+        return Quadric::zero()
+    }
+    let n = [n[0] / l, n[1] / l, n[2] / l];
+    Quadric::from_plane(n[0], n[1], n[2], -dot(&n, p), w)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Level of detail
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LodTarget {
+    /// stop at this many triangles
+    Triangles(usize),
+    /// stop at this fraction of the source triangles
+    Ratio(f32),
+    /// collapse while the error stays under this fraction of the bounding radius
+    Error(f32),
+}
+
+pub struct Lod {
+    mesh            : Mesh,
+    error           : f32,
+    triangle_count  : usize,
+}
+
+impl Lod {
+    pub fn mesh(&self) -> &Mesh { &self.mesh }
+    /// largest collapse error, as a fraction of the source bounding radius
+    pub fn error(&self) -> f32 { self.error }
+    pub fn triangle_count(&self) -> usize { self.triangle_count }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Manifold,
+    Border,
+    // uv seams, material boundaries, non-manifold and complex border vertices never move
+    Locked,
+}
+
+struct Simplifier<'a> {
+    positions   : Vec<[f64; 3]>,
+    tris        : Vec<[u32; 3]>,
+    uvs         : Vec<[u32; 3]>,
+    norms       : Vec<[u32; 3]>,
+    groups      : Vec<u32>,
+    alive       : Vec<bool>,
+    live_count  : usize,
+    vertex_tris : Vec<Vec<u32>>,
+    kind        : Vec<Kind>,
+    quadrics    : Vec<Quadric>,
+    mesh        : &'a Mesh,
+}
+
+fn edge_key(a: u32, b: u32) -> u64 {
+    if a < b { ((a as u64) << 32) | b as u64 } else { ((b as u64) << 32) | a as u64 }
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a Mesh) -> Self {
+        let nv = mesh.verts().len();
+        let valid = |v: &[u32], uv: &[u32], n: &[u32]|
+            v.iter().all(|i| (*i as usize) < nv)
+            && uv.iter().all(|i| *i == NO_INDEX || (*i as usize) < mesh.uvws().len())
+            && n.iter().all(|i| *i == NO_INDEX || (*i as usize) < mesh.normals().len());
+
+        let mut s = Self {
+            positions   : mesh.verts().iter().map(|p| [p.x as f64, p.y as f64, p.z as f64]).collect(),
+            tris        : Vec::new(),
+            uvs         : Vec::new(),
+            norms       : Vec::new(),
+            groups      : Vec::new(),
+            alive       : Vec::new(),
+            live_count  : 0,
+            vertex_tris : (0..nv).map(|_| Vec::new()).collect(),
+            kind        : filled(nv, Kind::Manifold),
+            quadrics    : filled(nv, Quadric::zero()),
+            mesh        : mesh,
+        };
+
+        // triangles are laid out group by group, quads split like GPUMesh::from does
+        let whole = [Group::new(String::new(), None, 0..mesh.tris().len(), 0..mesh.quads().len())];
+        let groups = if mesh.groups().len() == 0 { &whole[..] } else { mesh.groups() };
+        for (gi, g) in groups.iter().enumerate() {
+            if g.tris().start > g.tris().end || g.tris().end > mesh.tris().len()
+            || g.quads().start > g.quads().end || g.quads().end > mesh.quads().len() {
+                continue
+            }
+
+            for t in mesh.tris().as_slice()[g.tris()].iter() {
+                if valid(t.verts(), t.uvs(), t.norms()) {
+                    s.push_tri(*t.verts(), *t.uvs(), *t.norms(), gi as u32);
+                }
+            }
+
+            for q in mesh.quads().as_slice()[g.quads()].iter() {
+                if valid(q.verts(), q.uvs(), q.norms()) {
+                    let (v, uv, n) = (q.verts(), q.uvs(), q.norms());
+                    s.push_tri([v[0], v[1], v[2]], [uv[0], uv[1], uv[2]], [n[0], n[1], n[2]], gi as u32);
+                    s.push_tri([v[2], v[3], v[0]], [uv[2], uv[3], uv[0]], [n[2], n[3], n[0]], gi as u32);
+                }
+            }
+        }
+
+        s.classify();
+        s.compute_quadrics();
+        s
+    }
+
+    fn push_tri(&mut self, v: [u32; 3], uv: [u32; 3], n: [u32; 3], group: u32) {
+        // degenerate index triangles carry no surface
+        if v[0] == v[1] || v[1] == v[2] || v[2] == v[0] {
+            return
+        }
+
+        let t = self.tris.len() as u32;
+        for k in 0..3 {
+            self.vertex_tris[v[k] as usize].push(t);
+        }
+        self.tris.push(v);
+        self.uvs.push(uv);
+        self.norms.push(n);
+        self.groups.push(group);
+        self.alive.push(true);
+        self.live_count += 1;
+    }
+
+    // sorted (edge, triangle) pairs, edges used once are borders
+    fn edges(&self) -> Vec<(u64, u32)> {
+        let mut edges = Vec::with_capacity(self.tris.len() * 3);
+        for (t, v) in self.tris.iter().enumerate() {
+            if self.alive[t] {
+                for k in 0..3 {
+                    edges.push((edge_key(v[k], v[(k + 1) % 3]), t as u32));
+                }
+            }
+        }
+        edges.as_mut_slice().sort_unstable_by_key(|e| e.0);
+        edges
+    }
+
+    // every live edge once, walked from its lower vertex, no sorting needed
+    fn live_edges(&self, out: &mut Vec<(u32, u32)>, ring: &mut Vec<u32>) {
+        while out.pop().is_some() {}
+        for u in 0..self.positions.len() as u32 {
+            while ring.pop().is_some() {}
+            self.one_ring(u, ring);
+            for w in ring.iter() {
+                if *w > u {
+                    out.push((u, *w));
+                }
+            }
+        }
+    }
+
+    fn classify(&mut self) {
+        let nv = self.positions.len();
+        let mut border_edges = filled(nv, 0u32);
+
+        let edges = self.edges();
+        let e = edges.as_slice();
+        let mut i = 0;
+        while i < e.len() {
+            let mut j = i;
+            while j < e.len() && e[j].0 == e[i].0 {
+                j += 1;
+            }
+
+            let (a, b) = ((e[i].0 >> 32) as usize, (e[i].0 & 0xFFFF_FFFF) as usize);
+            match j - i {
+                1 => {
+                    border_edges[a] += 1;
+                    border_edges[b] += 1;
+                },
+                2 => (),
+                _ => {
+                    self.kind[a] = Kind::Locked;
+                    self.kind[b] = Kind::Locked;
+                }
+            }
+            i = j;
+        }
+
+        for v in 0..nv {
+            if self.kind[v] == Kind::Locked {
+                continue
+            }
+
+            match border_edges[v] {
+                0 => (),
+                2 => self.kind[v] = Kind::Border,
+                _ => self.kind[v] = Kind::Locked,
+            }
+
+            // every corner of the vertex must agree on uv and group, hard normals are recomputed
+            let mut first = None;
+            for t in self.vertex_tris[v].iter() {
+                let t = *t as usize;
+                let k = self.corner(t, v as u32);
+                let attr = (self.uvs[t][k], self.groups[t]);
+                match first {
+                    None => first = Some(attr),
+                    Some(f) if f != attr => { self.kind[v] = Kind::Locked; break },
+                    _ => ()
+                }
+            }
+        }
+    }
+
+    fn compute_quadrics(&mut self) {
+        for t in 0..self.tris.len() {
+            let v = self.tris[t];
+            let p = [self.positions[v[0] as usize], self.positions[v[1] as usize], self.positions[v[2] as usize]];
+            let n = cross(&sub(&p[1], &p[0]), &sub(&p[2], &p[0]));
+            let q = plane_quadric(&n, &p[0], length(&n) * 0.5);
+            for k in 0..3 {
+                self.quadrics[v[k] as usize].add(&q);
+            }
+
+            for k in 0..3 {
+                let (a, b) = (v[k], v[(k + 1) % 3]);
+                if self.shared_tris(a, b) == 1 {
+                    let e = sub(&p[(k + 1) % 3], &p[k]);
+                    let bq = plane_quadric(&cross(&e, &n), &p[k], dot(&e, &e) * BORDER_WEIGHT);
+                    self.quadrics[a as usize].add(&bq);
+                    self.quadrics[b as usize].add(&bq);
+                }
+            }
+        }
+    }
+
+    fn corner(&self, t: usize, v: u32) -> usize {
+        let tv = &self.tris[t];
+        if tv[0] == v { 0 } else if tv[1] == v { 1 } else { 2 }
+    }
+
+    fn contains(&self, t: usize, v: u32) -> bool {
+        let tv = &self.tris[t];
+        tv[0] == v || tv[1] == v || tv[2] == v
+    }
+
+    fn shared_tris(&self, a: u32, b: u32) -> usize {
+        self.vertex_tris[a as usize].iter().filter(|t| self.alive[**t as usize] && self.contains(**t as usize, b)).count()
+    }
+
+    fn collapse_cost(&self, u: u32, v: u32) -> Option<f64> {
+        match self.kind[u as usize] {
+            Kind::Locked => return None,
+            Kind::Border => {
+                if self.kind[v as usize] == Kind::Manifold || self.shared_tris(u, v) != 1 {
+                    return None
+                }
+            },
+            Kind::Manifold => ()
+        }
+
+        let mut q = self.quadrics[u as usize];
+        q.add(&self.quadrics[v as usize]);
+        Some(q.error(&self.positions[v as usize]))
+    }
+
+    // the uv u's corners take: v's along the collapsed edge, both sides must agree
+    fn collapse_uv(&self, u: u32, v: u32) -> Option<u32> {
+        let mut uv = None;
+        for t in self.vertex_tris[u as usize].iter() {
+            let t = *t as usize;
+            if self.alive[t] && self.contains(t, v) {
+                let a = self.uvs[t][self.corner(t, v)];
+                match uv {
+                    None => uv = Some(a),
+                    Some(x) if x != a => return None,
+                    _ => ()
+                }
+            }
+        }
+        uv
+    }
+
+    // v's normal when all its corners share one, a face normal otherwise
+    fn vertex_normal(&self, v: u32) -> u32 {
+        let mut normal = None;
+        for t in self.vertex_tris[v as usize].iter() {
+            let t = *t as usize;
+            if self.alive[t] {
+                let n = self.norms[t][self.corner(t, v)];
+                match normal {
+                    None => normal = Some(n),
+                    Some(x) if x != n => return NO_INDEX,
+                    _ => ()
+                }
+            }
+        }
+        normal.unwrap_or(NO_INDEX)
+    }
+
+    fn one_ring(&self, u: u32, out: &mut Vec<u32>) {
+        for t in self.vertex_tris[u as usize].iter() {
+            let t = *t as usize;
+            if self.alive[t] {
+                for w in self.tris[t].iter() {
+                    if *w != u && !out.iter().any(|x| *x == *w) {
+                        out.push(*w);
+                    }
+                }
+            }
+        }
+    }
+
+    fn can_collapse(&self, u: u32, v: u32, ring_u: &mut Vec<u32>, ring_v: &mut Vec<u32>) -> bool {
+        // link condition: u and v may only share the vertices opposite to their common edge
+        while ring_u.pop().is_some() {}
+        while ring_v.pop().is_some() {}
+        self.one_ring(u, ring_u);
+        self.one_ring(v, ring_v);
+        let common = ring_u.iter().filter(|w| ring_v.iter().any(|x| *x == **w)).count();
+        if common != self.shared_tris(u, v) {
+            return false
+        }
+
+        // no triangle may flip or collapse to a sliver
+        let pv = self.positions[v as usize];
+        for t in self.vertex_tris[u as usize].iter() {
+            let t = *t as usize;
+            if !self.alive[t] || self.contains(t, v) {
+                continue
+            }
+
+            let tv = self.tris[t];
+            let p = [self.positions[tv[0] as usize], self.positions[tv[1] as usize], self.positions[tv[2] as usize]];
+            let n0 = cross(&sub(&p[1], &p[0]), &sub(&p[2], &p[0]));
+            let k = self.corner(t, u);
+            let mut q = p;
+            q[k] = pv;
+            let n1 = cross(&sub(&q[1], &q[0]), &sub(&q[2], &q[0]));
+            if dot(&n0, &n1) <= 0.0 || dot(&n1, &n1) <= dot(&n0, &n0) * 1e-6 {
+                return false
+            }
+        }
+        true
+    }
+
+    fn collapse(&mut self, u: u32, v: u32, uv: u32) {
+        let normal = self.vertex_normal(v);
+        let tris = core::mem::replace(&mut self.vertex_tris[u as usize], Vec::new());
+        for t in tris.iter() {
+            let t = *t as usize;
+            if !self.alive[t] {
+                continue
+            }
+
+            if self.contains(t, v) {
+                self.alive[t] = false;
+                self.live_count -= 1;
+            } else {
+                let k = self.corner(t, u);
+                self.tris[t][k] = v;
+                self.uvs[t][k] = uv;
+
+                // a flat shaded face changed shape, let it get its face normal back
+                let n = self.norms[t];
+                if n[0] == n[1] && n[1] == n[2] {
+                    self.norms[t] = [NO_INDEX; 3];
+                } else {
+                    self.norms[t][k] = normal;
+                }
+                self.vertex_tris[v as usize].push(t as u32);
+            }
+        }
+
+        let q = self.quadrics[u as usize];
+        self.quadrics[v as usize].add(&q);
+    }
+
+    // collapses the cheapest edges pass after pass, returns the largest squared error
+    fn run(&mut self, target: usize, max_error_sq: f64) -> f64 {
+        let mut result = 0.0f64;
+        let mut touched = filled(self.positions.len(), false);
+        let mut ring_u = Vec::new();
+        let mut ring_v = Vec::new();
+        let mut edges = Vec::new();
+        let mut candidates = Vec::new();
+
+        while self.live_count > target {
+            self.live_edges(&mut edges, &mut ring_u);
+            while candidates.pop().is_some() {}
+            for (a, b) in edges.iter() {
+                let (a, b) = (*a, *b);
+                let best =
+                    match (self.collapse_cost(a, b), self.collapse_cost(b, a)) {
+                        (Some(ca), Some(cb)) => if ca <= cb { Some((ca, a, b)) } else { Some((cb, b, a)) },
+                        (Some(ca), None) => Some((ca, a, b)),
+                        (None, Some(cb)) => Some((cb, b, a)),
+                        (None, None) => None
+                    };
+                match best {
+                    Some(c) if c.0 <= max_error_sq => candidates.push(c),
+                    _ => ()
+                }
+            }
+            candidates.as_mut_slice().sort_unstable_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(core::cmp::Ordering::Equal));
+
+            for t in touched.iter_mut() {
+                *t = false;
+            }
+
+            let mut collapsed = 0;
+            for (cost, u, v) in candidates.iter() {
+                if self.live_count <= target {
+                    break
+                }
+
+                let (u, v) = (*u, *v);
+                if touched[u as usize] || touched[v as usize] {
+                    continue
+                }
+
+                // costs were computed at the start of the pass, neighbours may have moved since
+                let uv = match self.collapse_uv(u, v) { Some(uv) => uv, None => continue };
+                if !self.can_collapse(u, v, &mut ring_u, &mut ring_v) {
+                    continue
+                }
+
+                for w in ring_u.iter().chain(ring_v.iter()) {
+                    touched[*w as usize] = true;
+                }
+                touched[u as usize] = true;
+                touched[v as usize] = true;
+
+                self.collapse(u, v, uv);
+                result = result.max(*cost);
+                collapsed += 1;
+            }
+
+            if collapsed == 0 {
+                break
+            }
+        }
+        result
+    }
+
+    fn lod(&self, radius: f64, error_sq: f64) -> Lod {
+        let mesh = self.build();
+        Lod { triangle_count: mesh.tris().len(), mesh: mesh, error: (unsafe { sqrt(error_sq) } / radius) as f32 }
+    }
+
+    fn build(&self) -> Mesh {
+        let mesh = self.mesh;
+        let mut remap = filled(self.positions.len(), NO_INDEX);
+        let mut verts = Vec::new();
+        let mut colors = Vec::new();
+        let has_colors = mesh.colors().len() == mesh.verts().len();
+
+        let mut tris = Vec::new();
+        let mut groups = Vec::new();
+        let mut group_start = 0;
+        for t in 0..self.tris.len() {
+            if !self.alive[t] {
+                continue
+            }
+
+            let tv = self.tris[t];
+            let mut nv = [0; 3];
+            for k in 0..3 {
+                let v = tv[k] as usize;
+                if remap[v] == NO_INDEX {
+                    remap[v] = verts.len() as u32;
+                    verts.push(mesh.verts()[v]);
+                    if has_colors {
+                        colors.push(mesh.colors()[v]);
+                    }
+                }
+                nv[k] = remap[v];
+            }
+
+            // close the previous group's range when the group changes
+            let g = self.groups[t] as usize;
+            while mesh.groups().len() != 0 && groups.len() < g {
+                let src = &mesh.groups()[groups.len()];
+                groups.push(Group::new(String::from(src.name()), src.material().map(|m| String::from(m)), group_start..tris.len(), 0..0));
+                group_start = tris.len();
+            }
+            tris.push(IdTri::new(nv, self.uvs[t], self.norms[t]));
+        }
+
+        while mesh.groups().len() != 0 && groups.len() < mesh.groups().len() {
+            let src = &mesh.groups()[groups.len()];
+            groups.push(Group::new(String::from(src.name()), src.material().map(|m| String::from(m)), group_start..tris.len(), 0..0));
+            group_start = tris.len();
+        }
+
+        let mut uvws = Vec::new();
+        uvws.append(mesh.uvws().as_slice());
+        let mut normals = Vec::new();
+        normals.append(mesh.normals().as_slice());
+        let materials = mesh.materials().iter().cloned().collect();
+
+        Mesh::from(verts, uvws, tris, Vec::new()).with_normals(normals).with_colors(colors).with_groups(groups).with_materials(materials)
+    }
+}
+
+impl Mesh {
+    /// Quadric error metric decimation by edge collapse, one level per target. Vertices on uv
+    /// seams and material boundaries are kept, border vertices only slide along the border and
+    /// the levels only have triangles. Each level carries on from the previous one while the
+    /// targets get coarser, a target finer than the previous level starts over from the source.
+    pub fn lod_chain(&self, targets: &[LodTarget]) -> Vec<Lod> {
+        let radius = self.bounds().sphere().radius.max(f32::MIN_POSITIVE) as f64;
+        let source_tris = self.tris().len() + self.quads().len() * 2;
+
+        let mut s = Simplifier::new(self);
+        let mut error_sq = 0.0f64;
+        let mut lods = Vec::new();
+        for t in targets.iter() {
+            let (target, max_error) =
+                match t {
+                    LodTarget::Triangles(n) => (*n, f32::MAX),
+                    LodTarget::Ratio(r) => ((source_tris as f32 * r) as usize, f32::MAX),
+                    LodTarget::Error(e) => (0, *e),
+                };
+            let limit = max_error as f64 * radius;
+
+            if s.live_count < s.alive.len() && (target > s.live_count || limit * limit < error_sq) {
+                s = Simplifier::new(self);
+                error_sq = 0.0;
+            }
+
+            error_sq = error_sq.max(s.run(target, limit * limit));
+            lods.push(s.lod(radius, error_sq));
+        }
+        lods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs_math3d::*;
+    use crate::primitives::Primitive;
+
+    // a plane costs nothing to collapse, its border vertices slide along the border
+    #[test]
+    fn flat_grid_collapses() {
+        let grid = Mesh::primitive(&Primitive::Plane { width: 2.0, depth: 2.0, cols: 8, rows: 8 });
+        let lod = &grid.lod_chain(&[LodTarget::Triangles(2)])[0];
+        let m = lod.mesh();
+        assert_eq!(lod.triangle_count(), 2);
+        assert!(lod.error() < 1e-4);
+        assert!(m.verts().iter().all(|v| v.y == 0.0));
+        let (a, b) = (*grid.bounds().aabb(), *m.bounds().aabb());
+        assert_eq!((a.min.x, a.min.z, a.max.x, a.max.z), (b.min.x, b.min.z, b.max.x, b.max.z));
+    }
+
+    #[test]
+    fn error_limit() {
+        let sphere = Mesh::primitive(&Primitive::Icosphere { radius: 1.0, subdivisions: 2 });
+        assert_eq!(sphere.lod_chain(&[LodTarget::Error(0.0)])[0].triangle_count(), 320);
+
+        let lods = sphere.lod_chain(&[LodTarget::Ratio(0.5), LodTarget::Error(0.05)]);
+        assert!(lods[0].triangle_count() <= 160);
+        assert!(lods[1].triangle_count() < 320);
+        assert!(lods[1].error() <= 0.05);
+    }
+
+    // coarser levels carry on from the previous one
+    #[test]
+    fn chain_gets_coarser() {
+        let sphere = Mesh::primitive(&Primitive::Icosphere { radius: 1.0, subdivisions: 3 });
+        let lods = sphere.lod_chain(&[LodTarget::Ratio(0.5), LodTarget::Triangles(200), LodTarget::Ratio(0.1)]);
+        assert!(lods[0].triangle_count() <= 640);
+        assert!(lods[1].triangle_count() <= 200 && lods[1].triangle_count() < lods[0].triangle_count());
+        assert!(lods[2].triangle_count() <= 128 && lods[2].triangle_count() < lods[1].triangle_count());
+        assert!(lods[0].error() <= lods[1].error() && lods[1].error() <= lods[2].error());
+    }
+
+    // a flat n x n grid in two groups split along x = 0, with a uv seam along z = 0
+    fn seamed_grid(n: u32) -> Mesh {
+        let side = n + 1;
+        let mut verts = Vec::new();
+        let mut uvws = Vec::new();
+        for j in 0..side {
+            for i in 0..side {
+                let (x, z) = (i as f32 * 2.0 / n as f32 - 1.0, j as f32 * 2.0 / n as f32 - 1.0);
+                verts.push(Vec3f::new(x, 0.0, z));
+                uvws.push(Vec3f::new(x, z, 0.0));
+            }
+        }
+        for k in 0..side * side {
+            let uv = uvws[k as usize];
+            uvws.push(Vec3f::new(uv.x + 10.0, uv.y, 0.0));
+        }
+
+        let mut tris = Vec::new();
+        let mut groups = Vec::new();
+        for (g, cols) in [0..n / 2, n / 2..n].iter().enumerate() {
+            let start = tris.len();
+            for j in 0..n {
+                for i in cols.clone() {
+                    let v = [j * side + i, j * side + i + 1, (j + 1) * side + i + 1, (j + 1) * side + i];
+                    let o = if j < n / 2 { side * side } else { 0 };
+                    tris.push(IdTri::new([v[0], v[2], v[1]], [v[0] + o, v[2] + o, v[1] + o], [NO_INDEX; 3]));
+                    tris.push(IdTri::new([v[0], v[3], v[2]], [v[0] + o, v[3] + o, v[2] + o], [NO_INDEX; 3]));
+                }
+            }
+            groups.push(Group::new(format!("g{}", g), None, start..tris.len(), 0..0));
+        }
+        Mesh::from(verts, uvws, tris, Vec::new()).with_groups(groups)
+    }
+
+    #[test]
+    fn seams_and_groups_stay() {
+        let grid = seamed_grid(8);
+        let lod = &grid.lod_chain(&[LodTarget::Triangles(0)])[0];
+        let m = lod.mesh();
+        assert!(lod.triangle_count() < grid.tris().len());
+        assert_eq!(m.groups().len(), 2);
+
+        // every vertex on the group boundary and on the seam is still there
+        for v in grid.verts().iter().filter(|v| v.x == 0.0 || v.z == 0.0) {
+            assert!(m.verts().iter().any(|w| w.x == v.x && w.y == v.y && w.z == v.z), "lost {} {}", v.x, v.z);
+        }
+
+        // and the seam still splits the uvs
+        for t in m.tris().iter() {
+            let below = t.verts().iter().any(|v| m.verts()[*v as usize].z < 0.0);
+            assert!(t.uvs().iter().all(|uv| (m.uvws()[*uv as usize].x > 5.0) == below));
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use core::result::Result;
+use rs_alloc::*;
+use rs_streams::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+// facets repeat their corners, positions are welded on their exact bits
+#[derive(Clone, Copy, PartialEq)]
+struct PositionKey([u32; 3]);
+
+impl rs_alloc::hash::Hash for PositionKey {
+    fn hash(&self) -> usize {
+        let bytes = unsafe { core::slice::from_raw_parts(self.0.as_ptr() as *const u8, 12) };
+        bytes.hash()
+    }
+}
+
+struct Welder {
+    verts   : Vec<Vec3f>,
+    map     : HashMap<PositionKey, u32>,
+}
+
+impl Welder {
+    fn bits(f: f32) -> u32 { if f == 0.0 { 0 } else { f.to_bits() } }
+
+    fn index(&mut self, p: Vec3f) -> u32 {
+        let key = PositionKey([Self::bits(p.x), Self::bits(p.y), Self::bits(p.z)]);
+        match self.map.get(key) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.verts.len() as u32;
+                self.map.set(key, idx);
+                self.verts.push(p);
+                idx
+            }
+        }
+    }
+}
+
+struct Facets {
+    welder  : Welder,
+    normals : Vec<Vec3f>,
+    tris    : Vec<IdTri>,
+}
+
+impl Facets {
+    fn new() -> Self { Self { welder: Welder { verts: Vec::new(), map: HashMap::new() }, normals: Vec::new(), tris: Vec::new() } }
+
+    fn push(&mut self, n: Vec3f, p: [Vec3f; 3]) {
+        let v = [self.welder.index(p[0]), self.welder.index(p[1]), self.welder.index(p[2])];
+
+        // a zero normal means "compute it yourself"
+        let ni =
+            if n.x == 0.0 && n.y == 0.0 && n.z == 0.0 {
+                NO_INDEX
+            } else {
+                self.normals.push(n);
+                (self.normals.len() - 1) as u32
+            };
+
+        self.tris.push(IdTri::new(v, [NO_INDEX; 3], [ni; 3]));
+    }
+
+    fn finish(self) -> Mesh {
+        Mesh::from(self.welder.verts, Vec::new(), self.tris, Vec::new()).with_normals(self.normals)
+    }
+}
+
+fn f32_at(data: &[u8], pos: usize) -> f32 {
+    f32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn vec3_at(data: &[u8], pos: usize) -> Vec3f {
+    Vec3f::new(f32_at(data, pos), f32_at(data, pos + 4), f32_at(data, pos + 8))
+}
+
+// data is 84 bytes of header and count then 50 bytes per facet, checked by parse_stl
+fn parse_binary(data: &[u8]) -> Mesh {
+    let count = (data.len() - 84) / 50;
+    let mut facets = Facets::new();
+    for i in 0..count {
+        let f = 84 + i * 50;
+        facets.push(vec3_at(data, f), [vec3_at(data, f + 12), vec3_at(data, f + 24), vec3_at(data, f + 36)]);
+    }
+    facets.finish()
+}
+
+fn next_f32<'a, I: Iterator<Item = &'a str>>(tokens: &mut I) -> Result<f32, String> {
+    match tokens.next().map(|t| t.parse::<f32>()) {
+        Some(Ok(f)) => Ok(f),
+        _ => Err(String::from("float parse error"))
+    }
+}
+
+fn parse_ascii(text: &str) -> Result<Mesh, String> {
+    let mut facets = Facets::new();
+    let mut tokens = text.split(|x: char| x.is_ascii_whitespace()).filter(|&x| x != "");
+
+    let mut normal = Vec3f::zero();
+    let mut corners = Vec::<Vec3f>::new();
+    while let Some(t) = tokens.next() {
+        match t {
+            "facet" => {
+                // facet normal nx ny nz
+                tokens.next();
+                normal = Vec3f::new(next_f32(&mut tokens)?, next_f32(&mut tokens)?, next_f32(&mut tokens)?);
+                while corners.pop().is_some() {}
+            },
+            "vertex" => corners.push(Vec3f::new(next_f32(&mut tokens)?, next_f32(&mut tokens)?, next_f32(&mut tokens)?)),
+            "endloop" => {
+                // some exporters write polygons, fan them
+                for i in 1..corners.len().max(2) - 1 {
+                    facets.push(normal, [corners[0], corners[i], corners[i + 1]]);
+                }
+            },
+            _ => ()
+        }
+    }
+
+    Ok(facets.finish())
+}
+
+impl Mesh {
+    /// Reads ascii or binary STL, duplicated facet corners are welded into shared vertices
+    pub fn read_stl(path: &str) -> Result<Mesh, String> {
+        let data = read_bytes(path)?;
+        Self::parse_stl(data.as_slice())
+    }
+
+    pub fn parse_stl(data: &[u8]) -> Result<Mesh, String> {
+        // binary headers may start with "solid" too, trust the size first
+        if data.len() >= 84 {
+            let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+            if count.checked_mul(50).and_then(|n| n.checked_add(84)) == Some(data.len()) {
+                return Ok(parse_binary(data))
+            }
+        }
+
+        if data.len() >= 5 && &data[0..5] == b"solid" {
+            return match core::str::from_utf8(data) {
+                Ok(text) => parse_ascii(text),
+                Err(_) => Err(String::from("invalid ascii stl"))
+            }
+        }
+
+        Err(String::from("not a stl file"))
+    }
+
+    /// Writes a binary STL, quads are split in two triangles
+    pub fn write_stl(&self, path: &str) -> Result<(), String> {
+        let data = self.to_stl()?;
+        let mut f =
+            match File::create(path) {
+                Ok(f) => f,
+                Err(_) => return Err(String::from("Could not create file"))
+            };
+
+        match f.write(data.as_slice()) {
+            Ok(n) if n == data.len() => Ok(()),
+            _ => Err(String::from("Could not write file"))
+        }
+    }
+
+    /// The binary STL `write_stl` writes
+    pub fn to_stl(&self) -> Result<Vec<u8>, String> {
+        let mut tris = Vec::<[u32; 3]>::new();
+        for t in self.tris().iter() {
+            tris.push(*t.verts());
+        }
+        for q in self.quads().iter() {
+            let v = q.verts();
+            tris.push([v[0], v[1], v[2]]);
+            tris.push([v[2], v[3], v[0]]);
+        }
+
+        let mut data = Vec::<u8>::with_capacity(84 + tris.len() * 50);
+        for _ in 0..80 {
+            data.push(0);
+        }
+        data.append(&(tris.len() as u32).to_le_bytes());
+
+        let verts = self.verts();
+        for t in tris.iter() {
+            if t.iter().any(|v| *v as usize >= verts.len()) {
+                return Err(String::from("vertex index out of range"))
+            }
+
+            let p = [verts[t[0] as usize], verts[t[1] as usize], verts[t[2] as usize]];
+            let n = rs_math3d::tri_normal(&p[0], &p[1], &p[2]);
+            for v in [n, p[0], p[1], p[2]].iter() {
+                data.append(&v.x.to_le_bytes());
+                data.append(&v.y.to_le_bytes());
+                data.append(&v.z.to_le_bytes());
+            }
+            data.append(&[0u8, 0u8]);
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary_stl(count: u32, facets: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..80 {
+            data.push(0u8);
+        }
+        data.append(&count.to_le_bytes());
+        for f in 0..facets {
+            for v in [0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, f as f32].iter() {
+                data.append(&v.to_le_bytes());
+            }
+            data.append(&[0u8, 0u8]);
+        }
+        data
+    }
+
+    #[test]
+    fn binary() {
+        let m = Mesh::parse_stl(binary_stl(2, 2).as_slice()).ok().unwrap();
+        assert_eq!(m.tris().len(), 2);
+        assert_eq!(m.verts().len(), 4);
+    }
+
+    #[test]
+    fn count_mismatch() {
+        assert!(Mesh::parse_stl(binary_stl(3, 2).as_slice()).is_err());
+        assert!(Mesh::parse_stl(binary_stl(u32::MAX, 1).as_slice()).is_err());
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let m = Mesh::parse_stl(binary_stl(2, 2).as_slice()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        let data = m.to_stl().unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(data.len(), 84 + 2 * 50);
+
+        let r = Mesh::parse_stl(data.as_slice()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(r.verts().len(), m.verts().len());
+        assert_eq!(r.tris().len(), 2);
+        for (a, b) in m.verts().iter().zip(r.verts().iter()) {
+            assert_eq!((a.x, a.y, a.z), (b.x, b.y, b.z));
+        }
+        for (a, b) in m.tris().iter().zip(r.tris().iter()) {
+            assert_eq!(a.verts(), b.verts());
+        }
+        assert_eq!(r.normals()[0].z, 1.0);
+    }
+
+    #[test]
+    fn ascii() {
+        let src = "solid square\n\
+                   facet normal 0 0 1\n outer loop\n  vertex 0 0 0\n  vertex 1 0 0\n  vertex 1 1 0\n endloop\nendfacet\n\
+                   facet normal 0 0 0\n outer loop\n  vertex 0 0 0\n  vertex 1 1 0\n  vertex 0 1 0\n endloop\nendfacet\n\
+                   endsolid square\n";
+        let m = Mesh::parse_stl(src.as_bytes()).unwrap_or_else(|e| panic!("{}", e.as_str()));
+        assert_eq!(m.verts().len(), 4);
+        assert_eq!(m.tris().len(), 2);
+        assert_eq!(m.tris()[1].verts(), &[0, 2, 3]);
+        assert_eq!(m.normals().len(), 1);
+        assert_eq!(m.tris()[1].norms(), &[NO_INDEX; 3]);
+        assert!(Mesh::parse_stl(b"solid x\nfacet normal 0 0 nope\n").is_err());
+    }
+}
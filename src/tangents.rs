@@ -0,0 +1,235 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_alloc::*;
+use rs_math3d::*;
+
+use crate::objloader::*;
+
+extern "C" {
+    fn acosf(x: f32) -> f32;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GPUTangentVertex {
+    pub pos     : Vec3f,
+    pub normal  : Vec3f,
+    pub uv      : Vec2f,
+    pub tangent : Vec4f,    // w is the bitangent sign: b = w * cross(n, t)
+}
+
+// the viewer's shaders have no normal mapping yet, nothing builds one outside the tests
+#[allow(dead_code)]
+pub struct GPUTangentMesh {
+    verts   : Vec<GPUTangentVertex>,
+    tris    : GPUIndices,
+    submeshes : Vec<SubMesh>,
+}
+
+#[allow(dead_code)]
+impl GPUTangentMesh {
+    pub fn verts(&self) -> &[GPUTangentVertex] { self.verts.as_slice() }
+    pub fn tris(&self) -> &GPUIndices { &self.tris }
+    /// the source mesh's submeshes, splitting vertices leaves the index ranges unchanged
+    pub fn submeshes(&self) -> &[SubMesh] { self.submeshes.as_slice() }
+}
+
+fn is_zero(f: f32) -> bool { f.abs() <= f32::MIN_POSITIVE }
+
+fn project(v: &Vec3f, n: &Vec3f) -> Vec3f { *v - *n * Vec3f::dot(n, v) }
+
+fn normalize_or_zero(v: &Vec3f) -> Vec3f {
+    let l = v.length();
+    if is_zero(l) { Vec3f::zero() } else { *v / l }
+}
+
+// any unit vector perpendicular to n, for vertices no triangle could give a tangent
+fn perpendicular(n: &Vec3f) -> Vec3f {
+    let a = if n.x.abs() < 0.9 { Vec3f::new(1.0, 0.0, 0.0) } else { Vec3f::new(0.0, 1.0, 0.0) };
+    normalize_or_zero(&project(&a, n))
+}
+
+// per vertex accumulated tangents, one per texture space orientation
+#[derive(Clone, Copy)]
+struct Accum {
+    pos     : Vec3f,
+    neg     : Vec3f,
+    has_pos : bool,
+    has_neg : bool,
+}
+
+impl GPUMesh {
+    /// Generates per vertex tangents following the MikkTSpace conventions: face tangents
+    /// are projected onto each corner's normal plane, weighted by the corner angle, and
+    /// vertices shared by faces of opposite texture space orientation are split. Faces without
+    /// uv area have no orientation, they neither contribute nor split.
+    #[allow(dead_code)]
+    pub fn with_tangents(&self) -> GPUTangentMesh {
+        let verts = self.verts();
+        let mut indices = self.tris().to_u32();
+        let tri_count = indices.len() / 3;
+
+        let mut accum = Vec::with_capacity(verts.len());
+        for _ in 0..verts.len() {
+            accum.push(Accum { pos: Vec3f::zero(), neg: Vec3f::zero(), has_pos: false, has_neg: false });
+        }
+
+        let mut orient = Vec::with_capacity(tri_count);
+        for t in 0..tri_count {
+            let i = [indices[t * 3] as usize, indices[t * 3 + 1] as usize, indices[t * 3 + 2] as usize];
+            let (v0, v1, v2) = (&verts[i[0]], &verts[i[1]], &verts[i[2]]);
+
+            let d1 = v1.pos - v0.pos;
+            let d2 = v2.pos - v0.pos;
+            let s1 = v1.uv.x - v0.uv.x;
+            let t1 = v1.uv.y - v0.uv.y;
+            let s2 = v2.uv.x - v0.uv.x;
+            let t2 = v2.uv.y - v0.uv.y;
+
+            let signed_area = s1 * t2 - t1 * s2;
+            if is_zero(signed_area) {
+                orient.push(None);
+                continue
+            }
+            let preserving = signed_area > 0.0;
+            orient.push(Some(preserving));
+
+            // tangent of the face in object space, its orientation follows the uv winding
+            let os = d1 * t2 - d2 * t1;
+            let fos = normalize_or_zero(&os) * if preserving { 1.0 } else { -1.0 };
+
+            for k in 0..3 {
+                let v = &verts[i[k]];
+                let prev = &verts[i[(k + 2) % 3]];
+                let next = &verts[i[(k + 1) % 3]];
+                let n = v.normal;
+
+                let e1 = normalize_or_zero(&project(&(next.pos - v.pos), &n));
+                let e2 = normalize_or_zero(&project(&(prev.pos - v.pos), &n));
+                let cos = Vec3f::dot(&e1, &e2).max(-1.0).min(1.0);
+                let angle = unsafe { acosf(cos) };
+
+                let a = &mut accum[i[k]];
+                let ct = normalize_or_zero(&project(&fos, &n)) * angle;
+                if preserving {
+                    a.pos = a.pos + ct;
+                    a.has_pos = true;
+                } else {
+                    a.neg = a.neg + ct;
+                    a.has_neg = true;
+                }
+            }
+        }
+
+        let mut out = Vec::with_capacity(verts.len());
+        let mut split = Vec::with_capacity(verts.len());
+        for (v, a) in verts.iter().zip(accum.iter()) {
+            let (t, w) = if a.has_pos || !a.has_neg { (a.pos, 1.0) } else { (a.neg, -1.0) };
+            out.push(Self::tangent_vertex(v, &t, w));
+            split.push(u32::MAX);
+        }
+
+        // faces that disagree with the orientation the vertex kept get their own copy
+        for t in 0..tri_count {
+            for k in 0..3 {
+                let vi = indices[t * 3 + k] as usize;
+                let a = &accum[vi];
+                if orient[t] == Some(false) && a.has_pos && a.has_neg {
+                    if split[vi] == u32::MAX {
+                        split[vi] = out.len() as u32;
+                        out.push(Self::tangent_vertex(&verts[vi], &a.neg, -1.0));
+                    }
+                    indices[t * 3 + k] = split[vi];
+                }
+            }
+        }
+
+        let count = out.len();
+        let submeshes = self.submeshes().iter().cloned().collect();
+        GPUTangentMesh { verts: out, tris: GPUIndices::from_u32(indices, count), submeshes: submeshes }
+    }
+
+    fn tangent_vertex(v: &GPUVertex, t: &Vec3f, w: f32) -> GPUTangentVertex {
+        let mut t = normalize_or_zero(&project(t, &v.normal));
+        if is_zero(t.length()) {
+            t = perpendicular(&v.normal);
+        }
+        GPUTangentVertex { pos: v.pos, normal: v.normal, uv: v.uv, tangent: Vec4f::new(t.x, t.y, t.z, w) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tangents(obj: &str) -> GPUTangentMesh {
+        let m = Mesh::parse_obj(obj).ok().unwrap();
+        GPUMesh::from(&m).ok().unwrap().with_tangents()
+    }
+
+    #[test]
+    fn follows_u() {
+        let t = tangents("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1 4/4/1\n");
+        assert_eq!(t.verts().len(), 4);
+        for v in t.verts().iter() {
+            assert!((v.tangent.x - 1.0).abs() < 1e-6 && v.tangent.y.abs() < 1e-6);
+            assert_eq!(v.tangent.w, 1.0);
+        }
+    }
+
+    #[test]
+    fn no_uv_area() {
+        // the second triangle has all its uvs at the same spot
+        let t = tangents("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\nf 1/3/1 3/3/1 4/3/1\n");
+        assert_eq!(t.tris().len(), 6);
+        for v in t.verts().iter() {
+            assert!(v.tangent.x.is_finite() && v.tangent.y.is_finite() && v.tangent.z.is_finite());
+            assert!((v.tangent.x * v.tangent.x + v.tangent.y * v.tangent.y + v.tangent.z * v.tangent.z - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn mirrored_seam() {
+        // the second triangle mirrors the first one's uvs across the shared diagonal
+        let t = tangents("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/2/1\n");
+        assert_eq!(t.tris().len(), 6);
+        assert_eq!(t.verts().len(), 6);
+
+        // the diagonal's vertices keep their first orientation, the mirrored face gets copies
+        let tris = t.tris().to_u32();
+        for k in 0..3 {
+            assert_eq!(t.verts()[tris[k] as usize].tangent.w, 1.0);
+            assert_eq!(t.verts()[tris[3 + k] as usize].tangent.w, -1.0);
+        }
+        for v in t.verts()[4..].iter() {
+            assert_eq!(v.tangent.w, -1.0);
+            assert!(v.pos.x == v.pos.y);
+        }
+    }
+}
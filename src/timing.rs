@@ -0,0 +1,257 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+#[cfg(not(target_arch = "wasm32"))]
+use rs_glfw3::bindings::*;
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn emscripten_get_now() -> f64;
+}
+
+/// frames kept for the frame time statistics
+pub const HISTORY_SIZE : usize = 120;
+
+/// Seconds from an arbitrary origin, monotonic
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 { unsafe { glfwGetTime() } }
+
+/// Seconds from an arbitrary origin, monotonic
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 { unsafe { emscripten_get_now() * 0.001 } }
+
+#[cfg(not(target_arch = "wasm32"))]
+fn sleep(seconds: f64) {
+    let ts = libc::timespec { tv_sec: seconds as libc::time_t, tv_nsec: ((seconds - (seconds as u64) as f64) * 1.0e9) as libc::c_long };
+    unsafe { libc::nanosleep(&ts, core::ptr::null_mut()) };
+}
+
+/// Frame clock: real delta time, a fixed timestep accumulator, frame rate capping and the
+/// recent frame times.
+///
+/// Deltas are clamped to `max_delta` so that a stall (breakpoint, window drag, tab in the
+/// background) does not turn into a burst of fixed steps.
+pub struct FrameTimer {
+    start       : f64,
+    frame_start : f64,
+    frame       : u64,
+
+    fixed_step  : f32,
+    accumulator : f32,
+    max_delta   : f32,
+    max_fps     : f32,
+
+    history     : [f32; HISTORY_SIZE],
+    history_len : usize,
+    history_pos : usize,
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        let t = now();
+        Self {
+            start: t, frame_start: t, frame: 0,
+            fixed_step: 1.0 / 60.0, accumulator: 0.0, max_delta: 0.25, max_fps: 0.0,
+            history: [0.0; HISTORY_SIZE], history_len: 0, history_pos: 0,
+        }
+    }
+
+    /// seconds per fixed update
+    pub fn with_fixed_step(mut self, step: f32) -> Self {
+        self.fixed_step = step.max(1.0e-4);
+        self
+    }
+
+    /// longest delta a frame can report, in seconds
+    // the viewer keeps the default quarter second
+    #[allow(dead_code)]
+    pub fn with_max_delta(mut self, max_delta: f32) -> Self {
+        self.max_delta = max_delta.max(1.0e-4);
+        self
+    }
+
+    /// frames per second not to exceed, 0 leaves the rate to vsync
+    pub fn with_max_fps(mut self, fps: f32) -> Self {
+        self.max_fps = fps.max(0.0);
+        self
+    }
+
+    /// Restarts the frame clock, so that time spent loading does not count as a frame
+    pub fn reset(&mut self) {
+        self.frame_start = now();
+        self.accumulator = 0.0;
+    }
+
+    /// Starts a frame: measures the delta since the previous one and feeds the accumulator
+    pub fn begin_frame(&mut self) -> f32 { self.begin_frame_at(now()) }
+
+    /// `begin_frame` at `t` seconds on the `now()` clock
+    pub fn begin_frame_at(&mut self, t: f64) -> f32 {
+        let real = (t - self.frame_start) as f32;
+        self.frame_start = t;
+        self.frame += 1;
+
+        self.history[self.history_pos] = real;
+        self.history_pos = (self.history_pos + 1) % HISTORY_SIZE;
+        self.history_len = (self.history_len + 1).min(HISTORY_SIZE);
+
+        let dt = real.min(self.max_delta);
+        self.accumulator += dt;
+        dt
+    }
+
+    /// Takes one fixed step out of the accumulator, call until it answers false
+    pub fn next_fixed_step(&mut self) -> bool {
+        if self.accumulator >= self.fixed_step {
+            self.accumulator -= self.fixed_step;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Waits out the rest of the frame budget when a frame cap is set. Sleeps most of it and
+    /// spins the last millisecond, sleeping alone overshoots by the scheduler's granularity.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn end_frame(&self) {
+        if self.max_fps <= 0.0 {
+            return
+        }
+
+        let deadline = self.frame_start + 1.0 / self.max_fps as f64;
+        loop {
+            let left = deadline - now();
+            if left <= 0.0 {
+                break
+            }
+            if left > 0.002 {
+                sleep(left - 0.001);
+            }
+        }
+    }
+
+    /// The browser paces frames, the cap goes to `emscripten_set_main_loop_arg` instead
+    #[cfg(target_arch = "wasm32")]
+    pub fn end_frame(&self) {}
+
+    pub fn fixed_step(&self) -> f32 { self.fixed_step }
+    // only the emscripten loop reads the cap back, natively end_frame applies it
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn max_fps(&self) -> f32 { self.max_fps }
+    /// how far the accumulator got into the next fixed step, to interpolate between the last two states
+    // the viewer has no fixed update to interpolate
+    #[allow(dead_code)]
+    pub fn alpha(&self) -> f32 { self.accumulator / self.fixed_step }
+    /// seconds since the timer was created, at the beginning of the frame
+    pub fn time(&self) -> f64 { self.frame_start - self.start }
+    // nothing shows the frame number since frames stopped being traced
+    #[allow(dead_code)]
+    pub fn frame_count(&self) -> u64 { self.frame }
+
+    /// Unclamped frame times, oldest first
+    pub fn history(&self) -> impl Iterator<Item = f32> + '_ {
+        let first = (self.history_pos + HISTORY_SIZE - self.history_len) % HISTORY_SIZE;
+        (0..self.history_len).map(move |i| self.history[(first + i) % HISTORY_SIZE])
+    }
+
+    /// average frame time over the history, in seconds
+    pub fn frame_time(&self) -> f32 {
+        if self.history_len == 0 {
+            return 0.0
+        }
+        self.history().sum::<f32>() / self.history_len as f32
+    }
+
+    /// longest frame of the history, the hitches an average hides
+    pub fn frame_time_max(&self) -> f32 { self.history().fold(0.0, f32::max) }
+
+    pub fn fps(&self) -> f32 {
+        let ft = self.frame_time();
+        if ft > 0.0 { 1.0 / ft } else { 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_steps() {
+        let mut timer = FrameTimer::new().with_fixed_step(0.01);
+        timer.reset();
+        let t = now();
+
+        let mut steps = 0;
+        assert!((timer.begin_frame_at(t + 0.025) - 0.025).abs() < 1e-4);
+        while timer.next_fixed_step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+        assert!((timer.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timer.frame_count(), 1);
+    }
+
+    #[test]
+    fn stalls_are_clamped() {
+        let mut timer = FrameTimer::new().with_max_delta(0.1).with_fixed_step(0.05);
+        timer.reset();
+        let t = now();
+
+        // the history keeps the real frame time, the delta and the steps stop at the clamp
+        assert_eq!(timer.begin_frame_at(t + 2.0), 0.1);
+        let mut steps = 0;
+        while timer.next_fixed_step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+        assert!((timer.frame_time_max() - 2.0).abs() < 1e-4);
+
+        // nonsense settings fall back to the smallest usable values
+        let mut timer = FrameTimer::new().with_max_delta(-1.0).with_fixed_step(-1.0).with_max_fps(-30.0);
+        timer.reset();
+        assert!(timer.begin_frame_at(now() + 1.0) > 0.0);
+        assert!(timer.fixed_step() > 0.0 && timer.max_fps() == 0.0);
+    }
+
+    #[test]
+    fn history() {
+        let mut timer = FrameTimer::new();
+        timer.reset();
+        let mut t = now();
+        assert_eq!(timer.fps(), 0.0);
+
+        // wraps around, the slow first frames fall out of the history
+        for i in 0..HISTORY_SIZE + 10 {
+            t += if i < 10 { 0.1 } else { 0.02 };
+            timer.begin_frame_at(t);
+        }
+        assert_eq!(timer.history().count(), HISTORY_SIZE);
+        assert!((timer.frame_time() - 0.02).abs() < 1e-4);
+        assert!((timer.fps() - 50.0).abs() < 0.1);
+    }
+}
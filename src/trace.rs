@@ -0,0 +1,172 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use core::fmt::Write;
+
+// how many operations the panic report shows, and how long each line can be
+const RING_SIZE : usize = 16;
+const LINE_SIZE : usize = 80;
+
+/// Exit code of a panicking process, the same as a panicking std program
+pub const PANIC_EXIT_CODE : i32 = 101;
+
+#[cfg(target_arch = "wasm32")]
+const EM_LOG_CONSOLE  : i32 = 1;
+#[cfg(target_arch = "wasm32")]
+const EM_LOG_ERROR    : i32 = 4;
+#[cfg(target_arch = "wasm32")]
+const EM_LOG_JS_STACK : i32 = 16;
+
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn emscripten_log(flags: i32, format: *const u8, ...);
+}
+
+// formats into a fixed buffer, truncating what does not fit: nothing here may allocate,
+// the allocator could be why we panic
+struct FixedWriter<'a> {
+    buf     : &'a mut [u8],
+    len     : usize,
+}
+
+impl<'a> FixedWriter<'a> {
+    fn new(buf: &'a mut [u8]) -> Self { Self { buf: buf, len: 0 } }
+}
+
+impl<'a> Write for FixedWriter<'a> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut n = s.len().min(self.buf.len() - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Line {
+    buf     : [u8; LINE_SIZE],
+    len     : usize,
+}
+
+const EMPTY_LINE : Line = Line { buf: [0; LINE_SIZE], len: 0 };
+
+static mut RING  : [Line; RING_SIZE] = [EMPTY_LINE; RING_SIZE];
+static mut COUNT : usize = 0;
+static mut PANICKING : bool = false;
+
+/// Records a renderer resource being created or deleted, or a failure, the last few are
+/// printed if the program panics: `trace::record(format_args!("delete program {}", id))`.
+/// Nothing per draw or per frame goes here, the formatting is not free.
+pub fn record(args: core::fmt::Arguments) {
+    unsafe {
+        let line = &mut (*core::ptr::addr_of_mut!(RING))[COUNT % RING_SIZE];
+        let mut w = FixedWriter::new(&mut line.buf);
+        let _ = w.write_fmt(args);
+        line.len = w.len;
+        COUNT += 1;
+    }
+}
+
+fn write_operations(w: &mut FixedWriter) {
+    unsafe {
+        let ring = &*core::ptr::addr_of!(RING);
+        if COUNT == 0 {
+            return
+        }
+
+        let _ = write!(w, "\nlast renderer operations, oldest first:");
+        let first = if COUNT > RING_SIZE { COUNT - RING_SIZE } else { 0 };
+        for i in first..COUNT {
+            let line = &ring[i % RING_SIZE];
+            let _ = write!(w, "\n  {:5} {}", i, core::str::from_utf8(&line.buf[..line.len]).unwrap_or("?"));
+        }
+    }
+}
+
+// report is null terminated, for emscripten_log
+#[cfg(not(target_arch = "wasm32"))]
+fn emit(report: &[u8]) {
+    unsafe { libc::write(2, report.as_ptr() as *const libc::c_void, report.len() - 1) };
+}
+
+// stderr ends up in the console too, but emscripten_log adds the javascript stack
+#[cfg(target_arch = "wasm32")]
+fn emit(report: &[u8]) {
+    unsafe { emscripten_log(EM_LOG_CONSOLE | EM_LOG_ERROR | EM_LOG_JS_STACK, "%s\0".as_ptr(), report.as_ptr()) };
+}
+
+/// Prints the panic message, where it happened and the last renderer operations. A panic
+/// while reporting only prints a short notice.
+pub fn report_panic(info: &core::panic::PanicInfo) {
+    unsafe {
+        if PANICKING {
+            emit(b"panic while panicking\n\0");
+            return
+        }
+        PANICKING = true;
+    }
+
+    let mut buf = [0u8; 2048];
+    let len = {
+        // keep room for the newline and the terminating null
+        let size = buf.len() - 2;
+        let mut w = FixedWriter::new(&mut buf[..size]);
+        match info.location() {
+            Some(loc) => { let _ = write!(w, "panic at {}: {}", loc, info.message()); },
+            None => { let _ = write!(w, "panic: {}", info.message()); }
+        }
+        write_operations(&mut w);
+        w.len
+    };
+    buf[len] = b'\n';
+    buf[len + 1] = 0;
+    emit(&buf[..len + 2]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        let mut buf = [0u8; 6];
+        let mut w = FixedWriter::new(&mut buf);
+        let _ = write!(w, "ab{}", "\u{e9}\u{e9}\u{e9}");
+        let len = w.len;
+        assert_eq!(core::str::from_utf8(&buf[..len]), Ok("ab\u{e9}\u{e9}"));
+
+        let mut buf = [0u8; 5];
+        let mut w = FixedWriter::new(&mut buf);
+        let _ = write!(w, "ab{}", "\u{e9}\u{e9}\u{e9}");
+        let len = w.len;
+        assert_eq!(core::str::from_utf8(&buf[..len]), Ok("ab\u{e9}"));
+    }
+}
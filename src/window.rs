@@ -0,0 +1,511 @@
+// Copyright 2020-Present (c) Raja Lehtihet & Wael El Oraiby
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice,
+// this list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+// this list of conditions and the following disclaimer in the documentation
+// and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors
+// may be used to endorse or promote products derived from this software without
+// specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE
+// ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE
+// LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR
+// CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF
+// SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
+// INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
+// CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE)
+// ARISING IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE
+// POSSIBILITY OF SUCH DAMAGE.
+//
+use rs_ctypes::*;
+use rs_glfw3::bindings::*;
+use rs_streams::{errorln, fprintln};
+use rs_alloc::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    /// exclusive full screen, switching the monitor to the closest mode to the window size
+    Fullscreen,
+    /// full screen at the monitor's current mode, no mode switch
+    Borderless,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ContextApi {
+    Egl,
+    /// GLX, WGL or NSGL, whatever the platform has
+    Native,
+}
+
+/// One attempt at creating the GLES context
+#[derive(Clone, Copy)]
+pub struct ContextConfig {
+    pub api     : ContextApi,
+    pub major   : i32,
+    pub minor   : i32,
+    pub samples : i32,
+}
+
+impl ContextConfig {
+    pub fn new(api: ContextApi, major: i32, minor: i32, samples: i32) -> Self {
+        Self { api: api, major: major, minor: minor, samples: samples }
+    }
+
+    /// Everything worth trying, best first: GLES 2.0 then 3.0 (which runs GLES2 code as is),
+    /// EGL then the native API, and the requested MSAA down to 4x and none
+    pub fn fallbacks(samples: i32) -> Vec<ContextConfig> {
+        let mut sample_counts = Vec::new();
+        for s in [samples, 4, 0].iter() {
+            if *s <= samples && sample_counts.iter().all(|c| c != s) {
+                sample_counts.push(*s);
+            }
+        }
+
+        let mut v = Vec::new();
+        for (major, minor) in [(2, 0), (3, 0)].iter() {
+            for api in [ContextApi::Egl, ContextApi::Native].iter() {
+                for s in sample_counts.iter() {
+                    v.push(ContextConfig::new(*api, *major, *minor, *s));
+                }
+            }
+        }
+        v
+    }
+
+    unsafe fn apply(&self) {
+        let api = match self.api { ContextApi::Egl => GLFW_EGL_CONTEXT_API, ContextApi::Native => GLFW_NATIVE_CONTEXT_API };
+        glfwWindowHint(GLFW_CONTEXT_CREATION_API as c_int, api as c_int);
+        glfwWindowHint(GLFW_CLIENT_API as c_int, GLFW_OPENGL_ES_API as c_int);
+        glfwWindowHint(GLFW_CONTEXT_VERSION_MAJOR as c_int, self.major as c_int);
+        glfwWindowHint(GLFW_CONTEXT_VERSION_MINOR as c_int, self.minor as c_int);
+        glfwWindowHint(GLFW_SAMPLES as c_int, self.samples as c_int);
+    }
+}
+
+impl core::fmt::Display for ContextConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let api = match self.api { ContextApi::Egl => "EGL", ContextApi::Native => "native" };
+        if self.samples > 0 {
+            write!(f, "OpenGL ES {}.{} ({}, {}x MSAA)", self.major, self.minor, api, self.samples)
+        } else {
+            write!(f, "OpenGL ES {}.{} ({}, no MSAA)", self.major, self.minor, api)
+        }
+    }
+}
+
+pub struct WindowConfig {
+    title       : String,
+    width       : i32,
+    height      : i32,
+    resizable   : bool,
+    mode        : WindowMode,
+    monitor     : usize,
+    samples     : i32,
+    vsync       : bool,
+    visible     : bool,
+    contexts    : Vec<ContextConfig>,
+}
+
+impl WindowConfig {
+    pub fn new(title: &str) -> Self {
+        Self { title: String::from(title), width: 1024, height: 900, resizable: true, mode: WindowMode::Windowed, monitor: 0, samples: 8, vsync: true, visible: true, contexts: Vec::new() }
+    }
+
+    /// size in screen coordinates, which are pixels unless the platform scales them
+    pub fn with_size(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    // the viewer's window always resizes
+    #[allow(dead_code)]
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// index in `monitors()`, 0 is the primary monitor
+    // the viewer goes full screen on the primary monitor
+    #[allow(dead_code)]
+    pub fn with_monitor(mut self, monitor: usize) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// MSAA samples, 0 disables multisampling. Drivers refusing them fall back to fewer.
+    pub fn with_samples(mut self, samples: i32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// context configurations to try in order, replacing `ContextConfig::fallbacks(samples)`
+    // the viewer is fine with the fallbacks
+    #[allow(dead_code)]
+    pub fn with_contexts(mut self, contexts: &[ContextConfig]) -> Self {
+        self.contexts = Vec::new();
+        self.contexts.append(contexts);
+        self
+    }
+
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// a hidden window still has a context and a framebuffer to render to
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn title(&self) -> &str { self.title.as_str() }
+    pub fn width(&self) -> i32 { self.width }
+    pub fn height(&self) -> i32 { self.height }
+    pub fn resizable(&self) -> bool { self.resizable }
+    pub fn mode(&self) -> WindowMode { self.mode }
+    pub fn monitor(&self) -> usize { self.monitor }
+    pub fn vsync(&self) -> bool { self.vsync }
+    pub fn visible(&self) -> bool { self.visible }
+
+    pub fn contexts(&self) -> Vec<ContextConfig> {
+        if self.contexts.len() == 0 {
+            ContextConfig::fallbacks(self.samples)
+        } else {
+            self.contexts.clone()
+        }
+    }
+}
+
+fn c_string(s: &str) -> String {
+    let mut c = String::from(s);
+    c.push(0);
+    c
+}
+
+fn glfw_bool(b: bool) -> c_int { if b { GLFW_TRUE as c_int } else { GLFW_FALSE as c_int } }
+
+unsafe fn from_c_str(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new()
+    }
+    let bytes = core::slice::from_raw_parts(s as *const u8, libc::strlen(s as *const libc::c_char));
+    String::from(core::str::from_utf8(bytes).unwrap_or(""))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// GLFW errors
+////////////////////////////////////////////////////////////////////////////////
+// where the error callback stores messages while `collect_errors` runs, null otherwise
+static mut ERRORS: *mut Vec<String> = core::ptr::null_mut();
+
+unsafe extern "C" fn error_callback(code: c_int, desc: *const c_char) {
+    let msg = format!("{} (0x{:x})", from_c_str(desc).as_str(), code);
+    if ERRORS.is_null() {
+        errorln!("GLFW error: {}", msg.as_str());
+    } else {
+        (*ERRORS).push(msg);
+    }
+}
+
+// runs f, returning what it returned and the GLFW errors it raised
+fn collect_errors<R, F: FnOnce() -> R>(f: F) -> (R, Vec<String>) {
+    let mut errors = Vec::new();
+    unsafe {
+        glfwSetErrorCallback(Some(error_callback));
+        ERRORS = &mut errors;
+    }
+    let res = f();
+    unsafe { ERRORS = core::ptr::null_mut() };
+    (res, errors)
+}
+
+fn join_errors(errors: &[String]) -> String {
+    if errors.len() == 0 {
+        return String::from("no error reported")
+    }
+
+    let mut s = String::new();
+    for (i, e) in errors.iter().enumerate() {
+        if i != 0 {
+            s.push_str("; ");
+        }
+        s.push_str(e.as_str());
+    }
+    s
+}
+
+/// Initializes GLFW and installs the error callback, later errors are printed
+pub fn init_glfw() -> Result<(), String> {
+    let (ok, errors) = collect_errors(|| unsafe { glfwInit() != GLFW_FALSE as c_int });
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("unable to initialize GLFW: {}", join_errors(errors.as_slice()).as_str()))
+    }
+}
+
+/// Connected monitors, the primary one first
+pub fn monitors() -> Vec<*mut GLFWmonitor> {
+    let mut v = Vec::new();
+    unsafe {
+        let primary = glfwGetPrimaryMonitor();
+        if !primary.is_null() {
+            v.push(primary);
+        }
+
+        let mut count = 0;
+        let list = glfwGetMonitors(&mut count);
+        for i in 0..(if list.is_null() { 0 } else { count as usize }) {
+            let m = *list.add(i);
+            if m != primary {
+                v.push(m);
+            }
+        }
+    }
+    v
+}
+
+/// GLFW 3.2 has no content scale, estimate it from the monitor's physical size against a
+/// 96 dpi reference, in quarter steps like the desktop settings
+fn monitor_scale(monitor: *mut GLFWmonitor) -> f32 {
+    unsafe {
+        let mode = glfwGetVideoMode(monitor);
+        let mut mm_w = 0;
+        let mut mm_h = 0;
+        glfwGetMonitorPhysicalSize(monitor, &mut mm_w, &mut mm_h);
+        if mode.is_null() || mm_w <= 0 {
+            return 1.0
+        }
+
+        let dpi = (*mode).width as f32 / (mm_w as f32 / 25.4);
+        let scale = ((dpi / 96.0) * 4.0 + 0.5) as i32 as f32 / 4.0;
+        scale.max(1.0).min(4.0)
+    }
+}
+
+/// A GLFW window, tracking its size in screen coordinates and in framebuffer pixels
+pub struct Window {
+    handle      : *mut GLFWwindow,
+    mode        : WindowMode,
+    monitor     : usize,
+    config_size : (i32, i32),
+    // windowed placement to come back to after full screen
+    windowed_pos  : (i32, i32),
+    windowed_size : (i32, i32),
+    size        : (i32, i32),
+    framebuffer : (i32, i32),
+    content_scale : f32,
+    vsync       : bool,
+    context     : ContextConfig,
+}
+
+impl Window {
+    // creates the window and makes its context current, null when either fails
+    unsafe fn try_create(config: &WindowConfig, context: &ContextConfig) -> *mut GLFWwindow {
+        glfwDefaultWindowHints();
+        context.apply();
+        glfwWindowHint(GLFW_ALPHA_BITS as c_int, 0);
+        glfwWindowHint(GLFW_RESIZABLE as c_int, glfw_bool(config.resizable()));
+        glfwWindowHint(GLFW_VISIBLE as c_int, glfw_bool(config.visible()));
+
+        let title = c_string(config.title());
+        let handle = glfwCreateWindow(config.width() as c_int, config.height() as c_int,
+            title.as_bytes().as_ptr() as *const i8,
+            core::ptr::null::<GLFWmonitor>() as *mut GLFWmonitor,
+            core::ptr::null::<GLFWwindow>() as *mut GLFWwindow);
+        if handle.is_null() {
+            return handle
+        }
+
+        glfwMakeContextCurrent(handle);
+        if glfwGetCurrentContext() != handle {
+            glfwDestroyWindow(handle);
+            return core::ptr::null_mut()
+        }
+        handle
+    }
+
+    /// Creates the window with the first of the configuration's contexts that works and makes
+    /// it current. When none does, the error lists what GLFW said about each attempt.
+    pub fn create(config: &WindowConfig) -> Result<Window, String> {
+        let contexts = config.contexts();
+        let mut report = String::new();
+        for context in contexts.iter() {
+            let (handle, errors) = collect_errors(|| unsafe { Self::try_create(config, context) });
+            if handle.is_null() {
+                report.push_str(format!("\n  {}: {}", context, join_errors(errors.as_slice()).as_str()).as_str());
+                continue
+            }
+
+            let mut win = Window {
+                handle: handle, mode: WindowMode::Windowed, monitor: config.monitor(), config_size: (config.width(), config.height()),
+                windowed_pos: (0, 0), windowed_size: (config.width(), config.height()),
+                size: (0, 0), framebuffer: (0, 0), content_scale: 1.0, vsync: config.vsync(), context: *context,
+            };
+            win.set_vsync(config.vsync());
+            win.set_mode(config.mode());
+            win.refresh();
+            return Ok(win)
+        }
+
+        Err(format!("unable to create the window, every context configuration failed:{}", report.as_str()))
+    }
+
+    pub fn handle(&self) -> *mut GLFWwindow { self.handle }
+    /// the context configuration the window was created with
+    pub fn context(&self) -> &ContextConfig { &self.context }
+    // the viewer only toggles full screen
+    #[allow(dead_code)]
+    pub fn mode(&self) -> WindowMode { self.mode }
+    /// size in screen coordinates, the unit of cursor positions
+    pub fn size(&self) -> (i32, i32) { self.size }
+    /// size in pixels, what the viewport and the projection want
+    pub fn framebuffer_size(&self) -> (i32, i32) { self.framebuffer }
+    /// how much bigger than at 96 dpi user interface elements should be drawn
+    // the viewer draws no user interface
+    #[allow(dead_code)]
+    pub fn content_scale(&self) -> f32 { self.content_scale }
+    pub fn vsync(&self) -> bool { self.vsync }
+
+    pub fn set_title(&mut self, title: &str) {
+        let t = c_string(title);
+        unsafe { glfwSetWindowTitle(self.handle, t.as_bytes().as_ptr() as *const i8) };
+    }
+
+    /// needs the window's context to be current
+    pub fn set_vsync(&mut self, vsync: bool) {
+        unsafe { glfwSwapInterval(if vsync { 1 } else { 0 }) };
+        self.vsync = vsync;
+    }
+
+    /// monitor used by the next full screen switch, an index in `monitors()`
+    // the viewer stays on the monitor its config picked
+    #[allow(dead_code)]
+    pub fn set_monitor(&mut self, monitor: usize) { self.monitor = monitor }
+
+    fn target_monitor(&self) -> *mut GLFWmonitor {
+        let list = monitors();
+        if list.len() == 0 {
+            core::ptr::null_mut()
+        } else {
+            list[self.monitor.min(list.len() - 1)]
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: WindowMode) {
+        if mode == self.mode {
+            return
+        }
+
+        unsafe {
+            if self.mode == WindowMode::Windowed {
+                glfwGetWindowPos(self.handle, &mut self.windowed_pos.0, &mut self.windowed_pos.1);
+                glfwGetWindowSize(self.handle, &mut self.windowed_size.0, &mut self.windowed_size.1);
+            }
+
+            let monitor = self.target_monitor();
+            if mode == WindowMode::Windowed || monitor.is_null() {
+                glfwSetWindowMonitor(self.handle, core::ptr::null_mut(), self.windowed_pos.0, self.windowed_pos.1,
+                                     self.windowed_size.0, self.windowed_size.1, GLFW_DONT_CARE as c_int);
+                self.mode = WindowMode::Windowed;
+                return
+            }
+
+            let vm = glfwGetVideoMode(monitor);
+            let (w, h, rate) =
+                if mode == WindowMode::Fullscreen || vm.is_null() {
+                    (self.config_size.0, self.config_size.1, GLFW_DONT_CARE as c_int)
+                } else {
+                    ((*vm).width, (*vm).height, (*vm).refreshRate)
+                };
+            glfwSetWindowMonitor(self.handle, monitor, 0, 0, w, h, rate);
+            self.mode = mode;
+        }
+
+        // switching monitors can reset the swap interval on some drivers
+        let vsync = self.vsync;
+        self.set_vsync(vsync);
+    }
+
+    /// windowed <-> borderless full screen
+    pub fn toggle_fullscreen(&mut self) {
+        let mode = if self.mode == WindowMode::Windowed { WindowMode::Borderless } else { WindowMode::Windowed };
+        self.set_mode(mode);
+    }
+
+    /// Re-reads the sizes, true when the framebuffer size or the content scale changed
+    pub fn refresh(&mut self) -> bool {
+        let mut size = (0, 0);
+        let mut fb = (0, 0);
+        unsafe {
+            glfwGetWindowSize(self.handle, &mut size.0, &mut size.1);
+            glfwGetFramebufferSize(self.handle, &mut fb.0, &mut fb.1);
+        }
+
+        // high dpi platforms scale screen coordinates already, elsewhere ask the monitor
+        let ratio = fb.0 as f32 / size.0.max(1) as f32;
+        let scale =
+            if ratio > 1.01 {
+                ratio
+            } else {
+                let m = unsafe { glfwGetWindowMonitor(self.handle) };
+                let m = if m.is_null() { self.target_monitor() } else { m };
+                if m.is_null() { 1.0 } else { monitor_scale(m) }
+            };
+
+        let changed = fb != self.framebuffer || scale != self.content_scale;
+        self.size = size;
+        self.framebuffer = fb;
+        self.content_scale = scale;
+        changed
+    }
+
+    pub fn should_close(&self) -> bool {
+        unsafe { glfwWindowShouldClose(self.handle) != GLFW_FALSE as c_int }
+    }
+
+    pub fn swap_buffers(&self) {
+        unsafe { glfwSwapBuffers(self.handle) };
+    }
+}
+
+#[cfg(test)]
+impl Window {
+    /// a window without a GLFW handle, for tests of code that only carries one around
+    pub fn detached() -> Window {
+        Window {
+            handle: core::ptr::null_mut(), mode: WindowMode::Windowed, monitor: 0, config_size: (640, 480),
+            windowed_pos: (0, 0), windowed_size: (640, 480),
+            size: (640, 480), framebuffer: (640, 480), content_scale: 1.0, vsync: true, context: ContextConfig::fallbacks(0)[0],
+        }
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        if self.handle.is_null() {
+            return
+        }
+
+        unsafe {
+            glfwSetWindowUserPointer(self.handle, core::ptr::null_mut());
+            glfwDestroyWindow(self.handle);
+        }
+    }
+}